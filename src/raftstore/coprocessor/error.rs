@@ -13,11 +13,40 @@
 
 use std::result::Result as StdResult;
 use std::error::Error as StdError;
+use std::sync::mpsc::RecvTimeoutError;
+use std::io;
 
+use kvproto::errorpb;
+use util::escape;
+use util::xeval;
 
 quick_error!{
     #[derive(Debug)]
     pub enum Error {
+        Region(err: errorpb::Error) {
+            description("region related failure")
+            display("region {:?}", err)
+        }
+        Locked { key: Vec<u8>, primary: Vec<u8>, ts: u64 } {
+            description("key is locked")
+            display("key {} is locked by primary {} at {}", escape(key), escape(primary), ts)
+        }
+        Timeout {
+            description("request timeout")
+            display("request timeout")
+        }
+        Eval(err: xeval::Error) {
+            from()
+            cause(err)
+            description(err.description())
+            display("{}", err)
+        }
+        Io(err: io::Error) {
+            from()
+            cause(err)
+            description(err.description())
+            display("{}", err)
+        }
         Other(err: Box<StdError + Sync + Send>) {
             from()
             cause(err.as_ref())
@@ -27,4 +56,164 @@ quick_error!{
     }
 }
 
+impl Error {
+    /// Check if the request may succeed after retrying, for example after
+    /// the region cache is updated or the lock is resolved.
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            Error::Region(_) | Error::Locked { .. } | Error::Timeout => true,
+            Error::Eval(_) | Error::Io(_) | Error::Other(_) => false,
+        }
+    }
+
+    /// Get a stable code of the error kind, which can be used as a metric label.
+    pub fn error_code(&self) -> &'static str {
+        match *self {
+            Error::Region(_) => "Region",
+            Error::Locked { .. } => "Locked",
+            Error::Timeout => "Timeout",
+            Error::Eval(_) => "Eval",
+            Error::Io(_) => "Io",
+            Error::Other(_) => "Other",
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        match *self {
+            Error::Timeout => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<RecvTimeoutError> for Error {
+    fn from(e: RecvTimeoutError) -> Error {
+        match e {
+            RecvTimeoutError::Timeout => Error::Timeout,
+            RecvTimeoutError::Disconnected => Error::Other(box e),
+        }
+    }
+}
+
 pub type Result<T> = StdResult<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::time::Duration;
+    use std::io;
+    use std::error::Error as StdError;
+
+    use kvproto::errorpb;
+    use tipb::expression::{Expr, ExprType};
+    use util::codec::{Datum, number};
+    use util::xeval::{self, Evaluator};
+
+    #[test]
+    fn test_is_retryable() {
+        let mut region_err = errorpb::Error::new();
+        region_err.set_message("stale epoch".to_owned());
+        let region = Error::Region(region_err);
+        assert!(region.is_retryable());
+
+        let locked = Error::Locked {
+            key: b"k".to_vec(),
+            primary: b"p".to_vec(),
+            ts: 10,
+        };
+        assert!(locked.is_retryable());
+        assert_eq!(format!("{}", locked), "key k is locked by primary p at 10");
+
+        let other: Error = box_err!("unknown");
+        assert!(!other.is_retryable());
+        assert_eq!(format!("{}", other), "unknown");
+    }
+
+    #[test]
+    fn test_error_code() {
+        let errs = vec![
+            (Error::Region(errorpb::Error::new()), "Region"),
+            (Error::Locked {
+                key: vec![],
+                primary: vec![],
+                ts: 0,
+            },
+             "Locked"),
+            (Error::Timeout, "Timeout"),
+            (Error::Eval(xeval::Error::Expr("invalid".to_owned())), "Eval"),
+            (Error::Io(io::Error::new(io::ErrorKind::Other, "io")), "Io"),
+            (box_err!("unknown"), "Other"),
+        ];
+        for (err, code) in errs {
+            assert_eq!(err.error_code(), code);
+        }
+    }
+
+    #[test]
+    fn test_timeout() {
+        let err: Error = RecvTimeoutError::Timeout.into();
+        assert!(err.is_timeout());
+        assert!(err.is_retryable());
+        assert_eq!(format!("{}", err), "request timeout");
+
+        let (tx, rx) = mpsc::channel::<()>();
+        let err: Error = rx.recv_timeout(Duration::from_millis(10)).unwrap_err().into();
+        assert!(err.is_timeout());
+        drop(tx);
+        let err: Error = rx.recv_timeout(Duration::from_millis(10)).unwrap_err().into();
+        assert!(!err.is_timeout());
+        assert!(!err.is_retryable());
+
+        let region = Error::Region(errorpb::Error::new());
+        assert!(!region.is_timeout());
+    }
+
+    fn read_file() -> Result<()> {
+        let res: io::Result<()> = Err(io::Error::new(io::ErrorKind::NotFound, "file not found"));
+        try!(res);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_io_error() {
+        let err = read_file().unwrap_err();
+        assert_eq!(format!("{}", err), "file not found");
+        assert_eq!(err.description(), "file not found");
+        assert!(err.cause().is_some());
+        assert!(!err.is_retryable());
+        match err {
+            Error::Io(ref e) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+            ref e => panic!("expect io error, got {:?}", e),
+        }
+    }
+
+    fn eval_column(eval: &mut Evaluator, col_id: i64) -> Result<Datum> {
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::ColumnRef);
+        let mut buf = vec![0; 8];
+        number::encode_i64(&mut buf, col_id).unwrap();
+        expr.set_val(buf);
+        let d = try!(eval.eval(&expr));
+        Ok(d)
+    }
+
+    #[test]
+    fn test_from_eval_error() {
+        let mut eval = Evaluator::default();
+        eval.row.insert(1, Datum::I64(1));
+        assert_eq!(eval_column(&mut eval, 1).unwrap(), Datum::I64(1));
+
+        let err = eval_column(&mut eval, 2).unwrap_err();
+        assert_eq!(format!("{}", err), "column 2 not found");
+        assert!(!err.is_retryable());
+        match err {
+            Error::Eval(xeval::Error::ColumnNotFound(2)) => {}
+            e => panic!("expect eval error, got {:?}", e),
+        }
+
+        let err: Error = xeval::Error::Eval("bad operand".to_owned()).into();
+        assert_eq!(format!("{}", err), "bad operand");
+    }
+}
@@ -11,11 +11,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cmp;
+
 use rocksdb::{DB, IteratorMode, Direction, DBVector};
 use rocksdb::rocksdb::Snapshot;
 use raftstore::store::engine::{Iterable, Peekable};
-use raftstore::store::keys::{self, enc_end_key};
-use raftstore::store::{util, PeerStorage};
+use raftstore::store::keys;
+use raftstore::store::PeerStorage;
 use raftstore::Result;
 use kvproto::metapb;
 
@@ -46,12 +48,8 @@ impl<'a> RegionSnapshot<'a> {
     }
 
     fn new_iterator(&'a self, start_key: &[u8]) -> Box<Iterator<Item = Kv> + 'a> {
-        let scan_start_key = if start_key < self.region.get_start_key() {
-            keys::data_key(self.region.get_start_key())
-        } else {
-            keys::data_key(start_key)
-        };
-        let scan_end_key = enc_end_key(&self.region);
+        let (region_start_key, scan_end_key) = keys::data_range(&self.region);
+        let scan_start_key = cmp::max(keys::data_key(start_key), region_start_key);
         box self.snap
                 .new_iterator(&scan_start_key)
                 .take_while(move |&(k, _)| k < &scan_end_key)
@@ -67,17 +65,20 @@ impl<'a> RegionSnapshot<'a> {
     }
 
     fn new_reverse_iterator(&'a self, start_key: &[u8]) -> Box<Iterator<Item = Kv> + 'a> {
-        let scan_start_key = if start_key > self.region.get_end_key() &&
-                                !self.region.get_end_key().is_empty() {
-            enc_end_key(&self.region)
+        let (scan_end_key, region_end_key) = keys::data_range(&self.region);
+        let start_key = keys::data_key(start_key);
+        // the end key of the last region is a sentinel rather than a data key,
+        // so there is nothing to clamp.
+        let scan_start_key = if !keys::is_data_end_bound(&region_end_key) &&
+                                start_key > region_end_key {
+            region_end_key
         } else {
-            keys::data_key(start_key)
+            start_key
         };
         let mut iter = self.snap.iterator(IteratorMode::From(&scan_start_key, Direction::Reverse));
         if !iter.valid() {
             iter = self.snap.iterator(IteratorMode::End);
         }
-        let scan_end_key = keys::data_key(self.region.get_start_key());
         box iter.skip_while(move |&(k, _)| k >= &scan_start_key)
                 .take_while(move |&(k, _)| k >= &scan_end_key)
                 .map(|(k, v)| (keys::origin_key(k), v))
@@ -125,7 +126,7 @@ impl<'a> RegionSnapshot<'a> {
 
 impl<'a> Peekable for RegionSnapshot<'a> {
     fn get_value(&self, key: &[u8]) -> Result<Option<DBVector>> {
-        try!(util::check_key_in_region(key, &self.region));
+        try!(keys::check_key_in_region(key, &self.region));
         let data_key = keys::data_key(key);
         self.snap.get_value(&data_key)
     }
@@ -276,6 +277,9 @@ mod tests {
         pair = snap.reverse_seek(b"a5").unwrap().unwrap();
         assert_eq!(pair, (b"a3".to_vec(), b"v3".to_vec()));
         assert!(snap.reverse_seek(b"a3").unwrap().is_none());
+        // seeking past the end of a bounded region.
+        let pair = snap.reverse_seek(b"a8").unwrap().unwrap();
+        assert_eq!(pair, (b"a5".to_vec(), b"v5".to_vec()));
 
         // test last region
         let store = new_peer_storage(engine.clone(), &Region::new());
@@ -288,5 +292,8 @@ mod tests {
             let pair = snap.reverse_seek(seek_key).unwrap().unwrap();
             assert_eq!(pair, test_data[i]);
         }
+        // seeking past the unbounded end.
+        let pair = snap.reverse_seek(&[0xFF, 0xFF]).unwrap().unwrap();
+        assert_eq!(pair, test_data[test_data.len() - 1]);
     }
 }
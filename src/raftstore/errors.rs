@@ -26,6 +26,7 @@ use raft;
 use kvproto::metapb;
 
 use super::coprocessor::Error as CopError;
+use super::store::keys::KeyPosition;
 use util::escape;
 
 quick_error!{
@@ -43,13 +44,14 @@ quick_error!{
             description("peer is not leader")
             display("peer is not leader for region {}, leader may {:?}", region_id, leader_store_id)
         }
-        KeyNotInRegion(key: Vec<u8>, region: metapb::Region) {
+        KeyNotInRegion(key: Vec<u8>, region: metapb::Region, position: KeyPosition) {
             description("key is not in region")
-            display("key {} is not in region key range [{}, {}) for region {}",
+            display("key {} is not in region key range [{}, {}) for region {}, {:?}",
                     escape(key),
                     escape(region.get_start_key()),
                     escape(region.get_end_key()),
-                    region.get_id())
+                    region.get_id(),
+                    position)
         }
         Other(err: Box<error::Error + Sync + Send>) {
             from()
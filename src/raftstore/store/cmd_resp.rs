@@ -47,7 +47,7 @@ pub fn bind_error(resp: &mut RaftCmdResponse, err: Error) {
             }
             error_header.mut_not_leader().set_region_id(region_id);
         }
-        Error::KeyNotInRegion(key, region) => {
+        Error::KeyNotInRegion(key, region, _) => {
             error_header.mut_key_not_in_region().set_key(key);
             error_header.mut_key_not_in_region().set_region_id(region.get_id());
             error_header.mut_key_not_in_region().set_start_key(region.get_start_key().to_vec());
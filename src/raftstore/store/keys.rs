@@ -12,12 +12,16 @@
 // limitations under the License.
 
 use std::vec::Vec;
+use std::u64;
+use std::cmp::{self, Ordering};
 
 use byteorder::{ByteOrder, BigEndian, WriteBytesExt};
+use protobuf::Message;
 
-use raftstore::Result;
+use raftstore::{Result, Error};
 use util::escape;
 use kvproto::metapb::Region;
+use kvproto::raftpb::HardState;
 use std::mem;
 
 pub const MIN_KEY: &'static [u8] = &[];
@@ -34,9 +38,16 @@ pub const DATA_PREFIX: u8 = b'z';
 pub const DATA_PREFIX_KEY: &'static [u8] = &[DATA_PREFIX];
 pub const DATA_MIN_KEY: &'static [u8] = &[DATA_PREFIX];
 pub const DATA_MAX_KEY: &'static [u8] = &[DATA_PREFIX + 1];
+// Prefixes of the data spaces of the write and lock column families, see
+// `DataPrefix`.
+pub const DATA_WRITE_PREFIX: u8 = b'w';
+pub const DATA_LOCK_PREFIX: u8 = b'l';
 
 // Following keys are all local keys, so the first byte must be 0x01.
 pub const STORE_IDENT_KEY: &'static [u8] = &[LOCAL_PREFIX, 0x01];
+// The key marks the region being bootstrapped before the cluster is initialized,
+// 0x02 and 0x03 are taken by region prefixes below.
+pub const PREPARE_BOOTSTRAP_KEY: &'static [u8] = &[LOCAL_PREFIX, 0x04];
 // We save two types region data in DB, for raft and other meta data.
 // When the store starts, we should iterate all region meta data to
 // construct peer, no need to travel large raft data, so we separate them
@@ -55,26 +66,67 @@ pub const RAFT_HARD_STATE_SUFFIX: u8 = 0x02;
 pub const RAFT_APPLIED_INDEX_SUFFIX: u8 = 0x03;
 pub const RAFT_LAST_INDEX_SUFFIX: u8 = 0x04;
 pub const RAFT_TRUNCATED_STATE_SUFFIX: u8 = 0x05;
+pub const RAFT_APPLY_STATE_SUFFIX: u8 = 0x06;
 
 // For region meta
 pub const REGION_INFO_SUFFIX: u8 = 0x01;
 pub const REGION_TOMBSTONE_SUFFIX: u8 = 0x02;
 
+/// The key space a key belongs to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyPrefixKind {
+    Local,
+    Data,
+    Other,
+}
+
+pub fn is_local_key(key: &[u8]) -> bool {
+    !key.is_empty() && key[0] == LOCAL_PREFIX
+}
+
+pub fn is_data_key(key: &[u8]) -> bool {
+    !key.is_empty() && key[0] == DATA_PREFIX
+}
+
+/// Classify key by its first byte, an empty key belongs to neither local
+/// nor data space.
+pub fn key_prefix_kind(key: &[u8]) -> KeyPrefixKind {
+    if is_local_key(key) {
+        KeyPrefixKind::Local
+    } else if is_data_key(key) {
+        KeyPrefixKind::Data
+    } else {
+        KeyPrefixKind::Other
+    }
+}
+
 pub fn store_ident_key() -> Vec<u8> {
     STORE_IDENT_KEY.to_vec()
 }
 
+pub fn prepare_bootstrap_key() -> Vec<u8> {
+    PREPARE_BOOTSTRAP_KEY.to_vec()
+}
+
+// The length of a key made of a 2 bytes prefix, a region id and a suffix.
+const REGION_ID_KEY_LEN: usize = 2 + 8 + 1;
+
 fn make_region_id_key(region_id: u64, suffix: u8, extra_cap: usize) -> Vec<u8> {
-    let mut key = Vec::with_capacity(REGION_RAFT_PREFIX_KEY.len() + mem::size_of::<u64>() +
-                                     mem::size_of::<u8>() +
-                                     extra_cap);
-    key.extend_from_slice(REGION_RAFT_PREFIX_KEY);
-    // no need check error here, can't panic;
-    key.write_u64::<BigEndian>(region_id).unwrap();
-    key.push(suffix);
+    let mut key = Vec::with_capacity(REGION_ID_KEY_LEN + extra_cap);
+    make_region_id_key_into(&mut key, region_id, suffix);
     key
 }
 
+// Write the region id key into `buf`, the old content of `buf` is cleared
+// so that the buffer can be reused.
+fn make_region_id_key_into(buf: &mut Vec<u8>, region_id: u64, suffix: u8) {
+    buf.clear();
+    buf.extend_from_slice(REGION_RAFT_PREFIX_KEY);
+    // no need check error here, can't panic;
+    buf.write_u64::<BigEndian>(region_id).unwrap();
+    buf.push(suffix);
+}
+
 pub fn region_raft_prefix(region_id: u64) -> Vec<u8> {
     let mut key = Vec::with_capacity(REGION_RAFT_PREFIX_KEY.len() + mem::size_of::<u64>());
     key.extend_from_slice(REGION_RAFT_PREFIX_KEY);
@@ -83,53 +135,163 @@ pub fn region_raft_prefix(region_id: u64) -> Vec<u8> {
     key
 }
 
+/// Get the range `[start, end)` which contains all raft keys of the region.
+pub fn region_raft_range(region_id: u64) -> (Vec<u8>, Vec<u8>) {
+    let start = region_raft_prefix(region_id);
+    let end = if region_id == u64::MAX {
+        vec![LOCAL_PREFIX, REGION_RAFT_PREFIX + 1]
+    } else {
+        region_raft_prefix(region_id + 1)
+    };
+    (start, end)
+}
+
 pub fn raft_log_key(region_id: u64, log_index: u64) -> Vec<u8> {
-    let mut key = make_region_id_key(region_id, RAFT_LOG_SUFFIX, mem::size_of::<u64>());
-    // no need check error here, can't panic;
-    key.write_u64::<BigEndian>(log_index).unwrap();
+    let mut key = Vec::with_capacity(REGION_ID_KEY_LEN + mem::size_of::<u64>());
+    raft_log_key_into(&mut key, region_id, log_index);
     key
 }
 
+/// Same as `raft_log_key` but reuses `buf` instead of allocating, which
+/// helps in loops over many logs.
+pub fn raft_log_key_into(buf: &mut Vec<u8>, region_id: u64, log_index: u64) {
+    make_region_id_key_into(buf, region_id, RAFT_LOG_SUFFIX);
+    // no need check error here, can't panic;
+    buf.write_u64::<BigEndian>(log_index).unwrap();
+}
+
+/// Decode a key in the space of `region_raft_prefix`, return the region id and
+/// the suffix type. The log index of a raft log key is not checked, use
+/// `decode_raft_log_key` to get it.
+pub fn decode_region_raft_key(key: &[u8]) -> Result<(u64, u8)> {
+    let suffix_idx = REGION_RAFT_PREFIX_KEY.len() + mem::size_of::<u64>();
+    if key.len() <= suffix_idx {
+        return Err(box_err!("invalid region raft key length for key {}", escape(key)));
+    }
+
+    if !key.starts_with(REGION_RAFT_PREFIX_KEY) {
+        return Err(box_err!("invalid region raft prefix for key {}", escape(key)));
+    }
+
+    let suffix = key[suffix_idx];
+    let expect_len = suffix_idx + mem::size_of::<u8>();
+    if key.len() != expect_len &&
+       (suffix != RAFT_LOG_SUFFIX || key.len() != expect_len + mem::size_of::<u64>()) {
+        return Err(box_err!("invalid region raft key length for key {}", escape(key)));
+    }
+
+    let region_id = BigEndian::read_u64(&key[REGION_RAFT_PREFIX_KEY.len()..suffix_idx]);
+    Ok((region_id, suffix))
+}
+
+/// Decode raft log key generated by `raft_log_key`, return the region id and log index.
+pub fn decode_raft_log_key(key: &[u8]) -> Result<(u64, u64)> {
+    let suffix_idx = REGION_RAFT_PREFIX_KEY.len() + mem::size_of::<u64>();
+    if suffix_idx + mem::size_of::<u8>() + mem::size_of::<u64>() != key.len() {
+        return Err(box_err!("invalid raft log key length for key {}", escape(key)));
+    }
+
+    if !key.starts_with(REGION_RAFT_PREFIX_KEY) {
+        return Err(box_err!("invalid raft log prefix for key {}", escape(key)));
+    }
+
+    if key[suffix_idx] != RAFT_LOG_SUFFIX {
+        return Err(box_err!("invalid raft log suffix for key {}", escape(key)));
+    }
+
+    let region_id = BigEndian::read_u64(&key[REGION_RAFT_PREFIX_KEY.len()..suffix_idx]);
+    let log_index = BigEndian::read_u64(&key[suffix_idx + mem::size_of::<u8>()..]);
+    Ok((region_id, log_index))
+}
+
 /// Get the log index from raft log key generated by `raft_log_key`.
 pub fn raft_log_index(key: &[u8]) -> Result<u64> {
-    let expect_key_len = REGION_RAFT_PREFIX_KEY.len() + mem::size_of::<u64>() +
-                         mem::size_of::<u8>() + mem::size_of::<u64>();
-    if key.len() != expect_key_len {
-        return Err(box_err!("key {} is not a valid raft log key", escape(key)));
-    }
-    Ok(BigEndian::read_u64(&key[expect_key_len - mem::size_of::<u64>()..]))
+    let (_, log_index) = try!(decode_raft_log_key(key));
+    Ok(log_index)
 }
 
 pub fn raft_log_prefix(region_id: u64) -> Vec<u8> {
     make_region_id_key(region_id, RAFT_LOG_SUFFIX, 0)
 }
 
+/// Get the range `[start, end)` which contains all raft logs of the region
+/// before `truncated_index`, which can be garbage collected.
+pub fn raft_log_gc_range(region_id: u64, truncated_index: u64) -> (Vec<u8>, Vec<u8>) {
+    (raft_log_prefix(region_id), raft_log_key(region_id, truncated_index))
+}
+
 pub fn raft_hard_state_key(region_id: u64) -> Vec<u8> {
     make_region_id_key(region_id, RAFT_HARD_STATE_SUFFIX, 0)
 }
 
+pub fn raft_hard_state_key_into(buf: &mut Vec<u8>, region_id: u64) {
+    make_region_id_key_into(buf, region_id, RAFT_HARD_STATE_SUFFIX)
+}
+
 pub fn raft_applied_index_key(region_id: u64) -> Vec<u8> {
     make_region_id_key(region_id, RAFT_APPLIED_INDEX_SUFFIX, 0)
 }
 
+pub fn raft_applied_index_key_into(buf: &mut Vec<u8>, region_id: u64) {
+    make_region_id_key_into(buf, region_id, RAFT_APPLIED_INDEX_SUFFIX)
+}
+
 pub fn raft_last_index_key(region_id: u64) -> Vec<u8> {
     make_region_id_key(region_id, RAFT_LAST_INDEX_SUFFIX, 0)
 }
 
+pub fn raft_last_index_key_into(buf: &mut Vec<u8>, region_id: u64) {
+    make_region_id_key_into(buf, region_id, RAFT_LAST_INDEX_SUFFIX)
+}
+
 pub fn raft_truncated_state_key(region_id: u64) -> Vec<u8> {
     make_region_id_key(region_id, RAFT_TRUNCATED_STATE_SUFFIX, 0)
 }
 
+pub fn raft_truncated_state_key_into(buf: &mut Vec<u8>, region_id: u64) {
+    make_region_id_key_into(buf, region_id, RAFT_TRUNCATED_STATE_SUFFIX)
+}
+
+pub fn raft_apply_state_key(region_id: u64) -> Vec<u8> {
+    make_region_id_key(region_id, RAFT_APPLY_STATE_SUFFIX, 0)
+}
+
+pub fn raft_apply_state_key_into(buf: &mut Vec<u8>, region_id: u64) {
+    make_region_id_key_into(buf, region_id, RAFT_APPLY_STATE_SUFFIX)
+}
+
+/// Decode apply state key generated by `raft_apply_state_key`, return the region id.
+pub fn decode_raft_apply_state_key(key: &[u8]) -> Result<u64> {
+    let suffix_idx = REGION_RAFT_PREFIX_KEY.len() + mem::size_of::<u64>();
+    if suffix_idx + mem::size_of::<u8>() != key.len() {
+        return Err(box_err!("invalid apply state key length for key {}", escape(key)));
+    }
+
+    if !key.starts_with(REGION_RAFT_PREFIX_KEY) {
+        return Err(box_err!("invalid apply state prefix for key {}", escape(key)));
+    }
+
+    if key[suffix_idx] != RAFT_APPLY_STATE_SUFFIX {
+        return Err(box_err!("invalid apply state suffix for key {}", escape(key)));
+    }
+
+    Ok(BigEndian::read_u64(&key[REGION_RAFT_PREFIX_KEY.len()..suffix_idx]))
+}
+
 fn make_region_meta_key(region_id: u64, suffix: u8) -> Vec<u8> {
-    let mut key = Vec::with_capacity(REGION_META_PREFIX_KEY.len() + mem::size_of::<u64>() +
-                                     mem::size_of::<u8>());
-    key.extend_from_slice(REGION_META_PREFIX_KEY);
-    // no need to check error here, can't panic;
-    key.write_u64::<BigEndian>(region_id).unwrap();
-    key.push(suffix);
+    let mut key = Vec::with_capacity(REGION_ID_KEY_LEN);
+    make_region_meta_key_into(&mut key, region_id, suffix);
     key
 }
 
+fn make_region_meta_key_into(buf: &mut Vec<u8>, region_id: u64, suffix: u8) {
+    buf.clear();
+    buf.extend_from_slice(REGION_META_PREFIX_KEY);
+    // no need to check error here, can't panic;
+    buf.write_u64::<BigEndian>(region_id).unwrap();
+    buf.push(suffix);
+}
+
 // Decode region meta key, return the region key and meta suffix type.
 pub fn decode_region_meta_key(key: &[u8]) -> Result<(u64, u8)> {
     if REGION_META_PREFIX_KEY.len() + mem::size_of::<u64>() + mem::size_of::<u8>() != key.len() {
@@ -147,6 +309,98 @@ pub fn decode_region_meta_key(key: &[u8]) -> Result<(u64, u8)> {
     Ok((region_id, key[key.len() - 1]))
 }
 
+/// Get the range `[start, end)` covering all region meta keys.
+pub fn region_meta_range() -> (Vec<u8>, Vec<u8>) {
+    (REGION_META_MIN_KEY.to_vec(), REGION_META_MAX_KEY.to_vec())
+}
+
+/// `RegionMetaKeys` decodes a sequence of region meta keys into
+/// `(region_id, suffix)`, an error is yielded for every malformed key.
+pub struct RegionMetaKeys<I> {
+    keys: I,
+}
+
+impl<I, K> Iterator for RegionMetaKeys<I>
+    where I: Iterator<Item = K>,
+          K: AsRef<[u8]>
+{
+    type Item = Result<(u64, u8)>;
+
+    fn next(&mut self) -> Option<Result<(u64, u8)>> {
+        self.keys.next().map(|k| decode_region_meta_key(k.as_ref()))
+    }
+}
+
+pub fn decode_region_meta_keys<I>(keys: I) -> RegionMetaKeys<I::IntoIter>
+    where I: IntoIterator,
+          I::Item: AsRef<[u8]>
+{
+    RegionMetaKeys { keys: keys.into_iter() }
+}
+
+/// Call `f` with `(region_id, suffix, value)` for every region meta key in
+/// `kvs`, which are sorted like the pairs of a RocksDB iterator. Keys before
+/// the region meta range are skipped and iteration stops at the end of it.
+/// Keys with suffixes other than `REGION_INFO_SUFFIX` are skipped if
+/// `info_only` is true. Iteration also stops once `f` returns false.
+pub fn each_region_meta<I, K, V, F>(kvs: I, info_only: bool, mut f: F) -> Result<()>
+    where I: IntoIterator<Item = (K, V)>,
+          K: AsRef<[u8]>,
+          V: AsRef<[u8]>,
+          F: FnMut(u64, u8, &[u8]) -> Result<bool>
+{
+    for (key, value) in kvs {
+        let key = key.as_ref();
+        if key < REGION_META_MIN_KEY {
+            continue;
+        }
+        if key >= REGION_META_MAX_KEY {
+            break;
+        }
+        let (region_id, suffix) = try!(decode_region_meta_key(key));
+        if info_only && suffix != REGION_INFO_SUFFIX {
+            continue;
+        }
+        if !try!(f(region_id, suffix, value.as_ref())) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The kind of a key in the local space, see `parse_local_key`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocalKeyKind {
+    StoreIdent,
+    PrepareBootstrap,
+    /// A key in `region_raft_prefix`, with the region id and the suffix type.
+    RegionRaft(u64, u8),
+    /// A key in `region_meta_prefix`, with the region id and the suffix type.
+    RegionMeta(u64, u8),
+}
+
+/// Classify a local key and decode the region id it belongs to if any.
+pub fn parse_local_key(key: &[u8]) -> Result<LocalKeyKind> {
+    if !is_local_key(key) {
+        return Err(box_err!("{} is not a local key", escape(key)));
+    }
+    if key == STORE_IDENT_KEY {
+        return Ok(LocalKeyKind::StoreIdent);
+    }
+    if key == PREPARE_BOOTSTRAP_KEY {
+        return Ok(LocalKeyKind::PrepareBootstrap);
+    }
+    if key.starts_with(REGION_RAFT_PREFIX_KEY) {
+        let (region_id, suffix) = try!(decode_region_raft_key(key));
+        return Ok(LocalKeyKind::RegionRaft(region_id, suffix));
+    }
+    if key.starts_with(REGION_META_PREFIX_KEY) {
+        let (region_id, suffix) = try!(decode_region_meta_key(key));
+        return Ok(LocalKeyKind::RegionMeta(region_id, suffix));
+    }
+    Err(box_err!("unknown local key {}", escape(key)))
+}
+
 pub fn region_meta_prefix(region_id: u64) -> Vec<u8> {
     let mut key = Vec::with_capacity(REGION_META_PREFIX_KEY.len() + mem::size_of::<u64>());
     key.extend_from_slice(REGION_META_PREFIX_KEY);
@@ -158,53 +412,371 @@ pub fn region_info_key(region_id: u64) -> Vec<u8> {
     make_region_meta_key(region_id, REGION_INFO_SUFFIX)
 }
 
-// When a peer is destroyed, we would record current region max peer id as
-// the tombstone value, any peer for this region with a peer id <= tombstone
-// value is not allowed to create in this store.
+pub fn region_info_key_into(buf: &mut Vec<u8>, region_id: u64) {
+    make_region_meta_key_into(buf, region_id, REGION_INFO_SUFFIX)
+}
+
+// When a peer is destroyed, we would record current region as the tombstone
+// value, any peer for this region from an epoch not newer than the tombstone
+// region's is not allowed to create in this store.
 pub fn region_tombstone_key(region_id: u64) -> Vec<u8> {
     make_region_meta_key(region_id, REGION_TOMBSTONE_SUFFIX)
 }
 
+pub fn region_tombstone_key_into(buf: &mut Vec<u8>, region_id: u64) {
+    make_region_meta_key_into(buf, region_id, REGION_TOMBSTONE_SUFFIX)
+}
+
+/// Encode the region of a destroyed peer as the value of `region_tombstone_key`,
+/// the max peer id can be found from its peers and epoch.
+pub fn encode_tombstone(region: &Region) -> Result<Vec<u8>> {
+    let val = try!(region.write_to_bytes());
+    Ok(val)
+}
+
+/// Decode the region from a tombstone value generated by `encode_tombstone`.
+pub fn decode_tombstone(val: &[u8]) -> Result<Region> {
+    let mut region = Region::new();
+    if let Err(e) = region.merge_from_bytes(val) {
+        return Err(box_err!("invalid tombstone value {}: {:?}", escape(val), e));
+    }
+    Ok(region)
+}
+
+/// Encode the raft hard state as the value of `raft_hard_state_key`.
+pub fn encode_hard_state(state: &HardState) -> Result<Vec<u8>> {
+    let val = try!(state.write_to_bytes());
+    Ok(val)
+}
+
+/// Decode the raft hard state from a value generated by `encode_hard_state`.
+pub fn decode_hard_state(val: &[u8]) -> Result<HardState> {
+    let mut state = HardState::new();
+    if let Err(e) = state.merge_from_bytes(val) {
+        return Err(box_err!("invalid hard state {}: {:?}", escape(val), e));
+    }
+    Ok(state)
+}
+
+/// The prefix of a data key space, each column family has its own space so
+/// that their keys never overlap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataPrefix {
+    Default,
+    Write,
+    Lock,
+}
+
+impl DataPrefix {
+    pub fn as_byte(&self) -> u8 {
+        match *self {
+            DataPrefix::Default => DATA_PREFIX,
+            DataPrefix::Write => DATA_WRITE_PREFIX,
+            DataPrefix::Lock => DATA_LOCK_PREFIX,
+        }
+    }
+
+    /// Get the key which is greater than all the keys with this prefix.
+    pub fn max_key(&self) -> Vec<u8> {
+        vec![self.as_byte() + 1]
+    }
+}
+
 pub fn validate_data_key(key: &[u8]) -> Result<()> {
-    if !key.starts_with(DATA_PREFIX_KEY) {
+    validate_data_key_with(DataPrefix::Default, key)
+}
+
+pub fn validate_data_key_with(prefix: DataPrefix, key: &[u8]) -> Result<()> {
+    if key.is_empty() || key[0] != prefix.as_byte() {
         return Err(box_err!("invalid data key {}, must start with {}",
                             escape(key),
-                            DATA_PREFIX));
+                            prefix.as_byte()));
     }
 
     Ok(())
 }
 
 pub fn data_key(key: &[u8]) -> Vec<u8> {
-    let mut v = Vec::with_capacity(DATA_PREFIX_KEY.len() + key.len());
-    v.extend_from_slice(DATA_PREFIX_KEY);
+    data_key_with(DataPrefix::Default, key)
+}
+
+pub fn data_key_with(prefix: DataPrefix, key: &[u8]) -> Vec<u8> {
+    let mut v = Vec::with_capacity(1 + key.len());
+    v.push(prefix.as_byte());
     v.extend_from_slice(key);
     v
 }
 
 pub fn origin_key(key: &[u8]) -> &[u8] {
-    validate_data_key(key).expect("");
-    &key[DATA_PREFIX_KEY.len()..]
+    origin_key_with(DataPrefix::Default, key)
+}
+
+pub fn origin_key_with(prefix: DataPrefix, key: &[u8]) -> &[u8] {
+    validate_data_key_with(prefix, key).expect("");
+    &key[1..]
+}
+
+/// Compare two data keys by their origin keys, which agrees with comparing
+/// the data keys directly, so it can be used as a comparator.
+///
+/// # Panics
+///
+/// Panics if any of them is not a data key, just like `origin_key`.
+pub fn data_key_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    origin_key(a).cmp(origin_key(b))
+}
+
+/// Check if `key` is the sentinel `enc_end_key` returns for a region
+/// without end key, which is not a valid data key.
+pub fn is_data_end_bound(key: &[u8]) -> bool {
+    key == DATA_MAX_KEY
+}
+
+/// Get the origin end key from an encoded end key generated by `enc_end_key`,
+/// an empty key is returned for the unbounded sentinel.
+pub fn origin_end_key(key: &[u8]) -> &[u8] {
+    if is_data_end_bound(key) {
+        return EMPTY_KEY;
+    }
+    origin_key(key)
+}
+
+/// Get the smallest key which is strictly greater than `key`.
+pub fn next_key(key: &[u8]) -> Vec<u8> {
+    let mut next = Vec::with_capacity(key.len() + 1);
+    next.extend_from_slice(key);
+    next.push(0);
+    next
+}
+
+/// Get the smallest key which is greater than all keys prefixed with `key`.
+///
+/// An empty key is returned if there is no such key, which means unbounded.
+pub fn prefix_next(key: &[u8]) -> Vec<u8> {
+    match key.iter().rposition(|&b| b != 0xFF) {
+        Some(pos) => {
+            let mut next = key[..pos + 1].to_vec();
+            next[pos] += 1;
+            next
+        }
+        None => vec![],
+    }
+}
+
+/// Append the bitwise inverted `ts` in big endian to the data key, so that
+/// newer versions of the same key sort first. Notice that it's the reverse of
+/// the order of `Key::encode_ts` in storage.
+pub fn mvcc_encode_key(key: &[u8], ts: u64) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(key.len() + mem::size_of::<u64>());
+    encoded.extend_from_slice(key);
+    // no need check error here, can't panic;
+    encoded.write_u64::<BigEndian>(!ts).unwrap();
+    encoded
+}
+
+/// Decode key generated by `mvcc_encode_key`, return the data key and ts.
+pub fn mvcc_decode_key(encoded: &[u8]) -> Result<(Vec<u8>, u64)> {
+    if encoded.len() < mem::size_of::<u64>() {
+        return Err(box_err!("invalid mvcc key length for key {}", escape(encoded)));
+    }
+    let pos = encoded.len() - mem::size_of::<u64>();
+    let ts = !BigEndian::read_u64(&encoded[pos..]);
+    Ok((encoded[..pos].to_vec(), ts))
 }
 
 /// Get the `start_key` of current region in encoded form.
 pub fn enc_start_key(region: &Region) -> Vec<u8> {
-    data_key(region.get_start_key())
+    enc_start_key_with(DataPrefix::Default, region)
+}
+
+/// Get the `start_key` of current region in the data space of `prefix`.
+pub fn enc_start_key_with(prefix: DataPrefix, region: &Region) -> Vec<u8> {
+    data_key_with(prefix, region.get_start_key())
 }
 
 /// Get the `end_key` of current region in encoded form.
 pub fn enc_end_key(region: &Region) -> Vec<u8> {
+    enc_end_key_with(DataPrefix::Default, region)
+}
+
+/// Get the `end_key` of current region in the data space of `prefix`.
+pub fn enc_end_key_with(prefix: DataPrefix, region: &Region) -> Vec<u8> {
     if region.get_end_key().is_empty() {
-        DATA_MAX_KEY.to_vec()
+        prefix.max_key()
     } else {
-        data_key(region.get_end_key())
+        data_key_with(prefix, region.get_end_key())
+    }
+}
+
+// Read the leading 8 bytes of the key as a big endian number, shorter keys
+// are padded with zeros.
+fn key_prefix_u64(key: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    let len = cmp::min(key.len(), buf.len());
+    buf[..len].copy_from_slice(&key[..len]);
+    BigEndian::read_u64(&buf)
+}
+
+/// Estimate the distance between two keys by treating their leading 8 bytes
+/// as big endian numbers, 0 is returned if `end` is not greater than `start`.
+///
+/// Keys sharing a long common prefix can't be told apart, so it's only a cheap
+/// heuristic for splitting evenly without scanning.
+pub fn approximate_range_span(start: &[u8], end: &[u8]) -> u64 {
+    key_prefix_u64(end).saturating_sub(key_prefix_u64(start))
+}
+
+/// Get the approximate span of the data range of the region, see
+/// `approximate_range_span`.
+pub fn region_approximate_span(region: &Region) -> u64 {
+    approximate_range_span(&enc_start_key(region), &enc_end_key(region))
+}
+
+// Get the midpoint of two keys of the same length, treating them as big
+// endian numbers, the remainder is dropped.
+fn mid_key(start: &[u8], end: &[u8]) -> Vec<u8> {
+    let mut sum = vec![0u16; start.len()];
+    let mut carry = 0;
+    for i in (0..start.len()).rev() {
+        let v = start[i] as u16 + end[i] as u16 + carry;
+        sum[i] = v & 0xFF;
+        carry = v >> 8;
+    }
+    let mut rem = carry;
+    sum.into_iter()
+        .map(|v| {
+            let v = (rem << 8) | v;
+            rem = v & 1;
+            (v >> 1) as u8
+        })
+        .collect()
+}
+
+fn pad_key(key: &[u8], len: usize) -> Vec<u8> {
+    let mut padded = key.to_vec();
+    padded.resize(len, 0);
+    padded
+}
+
+/// Get a key roughly halfway through `[start, end)`, which is strictly between
+/// `start` and `end`.
+///
+/// An error is returned if there is no such key, for example `end` is
+/// `start` followed by zeros. Notice that an empty `end` is not unbounded here.
+pub fn split_key(start: &[u8], end: &[u8]) -> Result<Vec<u8>> {
+    let len = cmp::max(start.len(), end.len());
+    if start >= end || pad_key(start, len) == pad_key(end, len) {
+        return Err(box_err!("no key between {} and {}", escape(start), escape(end)));
+    }
+    let mid = mid_key(&pad_key(start, len), &pad_key(end, len));
+    if &*mid > start && &*mid < end {
+        return Ok(mid);
+    }
+    // one more byte always leaves a key in between.
+    Ok(mid_key(&pad_key(start, len + 1), &pad_key(end, len + 1)))
+}
+
+/// Get the range `[enc_start_key, enc_end_key)` which contains all the data
+/// keys of the region.
+pub fn data_range(region: &Region) -> (Vec<u8>, Vec<u8>) {
+    (enc_start_key(region), enc_end_key(region))
+}
+
+/// Get the ranges `[start, end)` to delete when the region is destroyed,
+/// which are the raft keys, the meta keys and the data keys of the region.
+pub fn region_destroy_ranges(region: &Region) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let region_id = region.get_id();
+    let meta_end = if region_id == u64::MAX {
+        REGION_META_MAX_KEY.to_vec()
+    } else {
+        region_meta_prefix(region_id + 1)
+    };
+    vec![region_raft_range(region_id),
+         (region_meta_prefix(region_id), meta_end),
+         data_range(region)]
+}
+
+/// Check if `key` is in `[enc_start_key, enc_end_key)` of `region`,
+/// the key must be a data key.
+pub fn key_in_region(key: &[u8], region: &Region) -> bool {
+    key >= &*enc_start_key(region) && key < &*enc_end_key(region)
+}
+
+/// Check if the data `key` is exactly the encoded start key of `region`, for a
+/// region without start key it's the smallest data key.
+pub fn is_region_start_key(key: &[u8], region: &Region) -> bool {
+    key == &*enc_start_key(region)
+}
+
+/// Check if `key` is exactly the encoded end key of `region`, for a region
+/// without end key it's the sentinel returned by `enc_end_key`.
+pub fn is_region_end_key(key: &[u8], region: &Region) -> bool {
+    key == &*enc_end_key(region)
+}
+
+/// The position of a raw key relative to the range of a region.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum KeyPosition {
+    BeforeStart,
+    InRegion,
+    AtOrAfterEnd,
+}
+
+/// Get the position of the raw `key` relative to the range of `region`.
+pub fn key_position(key: &[u8], region: &Region) -> KeyPosition {
+    let end_key = region.get_end_key();
+    if key < region.get_start_key() {
+        KeyPosition::BeforeStart
+    } else if end_key.is_empty() || key < end_key {
+        KeyPosition::InRegion
+    } else {
+        KeyPosition::AtOrAfterEnd
+    }
+}
+
+/// Check if the raw `key` is in `region`, the returned `KeyNotInRegion` error
+/// can be converted into an error response directly, and it tells which side
+/// of the region the key is on.
+pub fn check_key_in_region(key: &[u8], region: &Region) -> Result<()> {
+    match key_position(key, region) {
+        KeyPosition::InRegion => Ok(()),
+        pos => Err(Error::KeyNotInRegion(key.to_vec(), region.clone(), pos)),
+    }
+}
+
+/// Check if the ranges of two regions overlap, an empty start key means
+/// -inf and an empty end key means +inf.
+pub fn regions_overlap(a: &Region, b: &Region) -> bool {
+    enc_start_key(a) < enc_end_key(b) && enc_start_key(b) < enc_end_key(a)
+}
+
+/// Check if the range of region `a` contains the whole range of region `b`,
+/// an empty start key means -inf and an empty end key means +inf.
+pub fn region_contains(a: &Region, b: &Region) -> bool {
+    enc_start_key(a) <= enc_start_key(b) && enc_end_key(b) <= enc_end_key(a)
+}
+
+/// Get the boundaries `(left_start, split_key, right_end)` in origin form
+/// after splitting `region` at `at`, the split key must be strictly inside
+/// the region. `KeyNotInRegion` is returned if `at` is out of the region.
+pub fn split_boundaries(region: &Region, at: &[u8]) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+    try!(check_key_in_region(at, region));
+    let start_key = region.get_start_key();
+    if at == start_key {
+        return Err(box_err!("split key {} is the start key of region {}",
+                            escape(at),
+                            region.get_id()));
     }
+    Ok((start_key.to_vec(), at.to_vec(), region.get_end_key().to_vec()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::cmp::Ordering;
+    use kvproto::metapb::{Peer, Region};
+    use kvproto::raftpb::HardState;
+    use util::escape;
 
     #[test]
     fn test_region_id_key() {
@@ -218,6 +790,7 @@ mod tests {
             assert!(raft_applied_index_key(region_id).starts_with(&prefix));
             assert!(raft_last_index_key(region_id).starts_with(&prefix));
             assert!(raft_truncated_state_key(region_id).starts_with(&prefix));
+            assert!(raft_apply_state_key(region_id).starts_with(&prefix));
         }
 
         // test sort.
@@ -229,6 +802,88 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_decode_region_raft_key() {
+        let region_ids = vec![0, 1, 1024, u64::max_value()];
+        for region_id in region_ids {
+            let keys = vec![(raft_log_prefix(region_id), RAFT_LOG_SUFFIX),
+                            (raft_log_key(region_id, 10), RAFT_LOG_SUFFIX),
+                            (raft_hard_state_key(region_id), RAFT_HARD_STATE_SUFFIX),
+                            (raft_applied_index_key(region_id), RAFT_APPLIED_INDEX_SUFFIX),
+                            (raft_last_index_key(region_id), RAFT_LAST_INDEX_SUFFIX),
+                            (raft_truncated_state_key(region_id), RAFT_TRUNCATED_STATE_SUFFIX),
+                            (raft_apply_state_key(region_id), RAFT_APPLY_STATE_SUFFIX)];
+            for (key, suffix) in keys {
+                assert_eq!(decode_region_raft_key(&key).unwrap(), (region_id, suffix));
+            }
+        }
+
+        decode_region_raft_key(&region_raft_prefix(1)).unwrap_err();
+        decode_region_raft_key(&region_info_key(1)).unwrap_err();
+        let mut key = raft_hard_state_key(1);
+        key.extend_from_slice(&[0; 8]);
+        decode_region_raft_key(&key).unwrap_err();
+        let mut key = raft_log_key(1, 1);
+        key.push(0);
+        decode_region_raft_key(&key).unwrap_err();
+    }
+
+    #[test]
+    fn test_region_raft_range() {
+        let region_ids = vec![0, 1, 1024, u64::max_value() - 1, u64::max_value()];
+        for region_id in region_ids {
+            let (start, end) = region_raft_range(region_id);
+            let keys = vec![raft_log_prefix(region_id),
+                            raft_log_key(region_id, 0),
+                            raft_log_key(region_id, u64::max_value()),
+                            raft_hard_state_key(region_id),
+                            raft_applied_index_key(region_id),
+                            raft_last_index_key(region_id),
+                            raft_truncated_state_key(region_id),
+                            raft_apply_state_key(region_id)];
+            for key in keys {
+                assert!(key >= start && key < end, "{:?} not in {:?}", key, (&start, &end));
+            }
+
+            if region_id > 0 {
+                let key = raft_apply_state_key(region_id - 1);
+                assert!(key < start);
+            }
+            if region_id < u64::max_value() {
+                assert!(raft_log_key(region_id + 1, 0) >= end);
+            } else {
+                assert!(REGION_META_MIN_KEY >= &*end);
+            }
+        }
+    }
+
+    #[test]
+    fn test_raft_log_gc_range() {
+        let (start, end) = raft_log_gc_range(3, 10);
+        assert_eq!(start, raft_log_prefix(3));
+        assert_eq!(end, raft_log_key(3, 10));
+
+        for idx in &[0, 1, 9] {
+            let key = raft_log_key(3, *idx);
+            assert!(key >= start && key < end, "{} should be in range", idx);
+        }
+        for idx in &[10, 11, u64::max_value()] {
+            assert!(raft_log_key(3, *idx) >= end, "{} should not be in range", idx);
+        }
+        // keys of other regions and other raft keys are not in range.
+        let keys = vec![raft_log_key(2, 0),
+                        raft_log_key(4, 0),
+                        raft_hard_state_key(3),
+                        raft_truncated_state_key(3)];
+        for key in keys {
+            assert!(key < start || key >= end, "{:?} should not be in range", key);
+        }
+
+        let (start, end) = raft_log_gc_range(3, 0);
+        assert!(start <= end);
+        assert!(raft_log_key(3, 0) >= end);
+    }
+
     #[test]
     fn test_raft_log_sort() {
         let tbls = vec![(1, 1, 1, 2, Ordering::Less),
@@ -242,6 +897,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_raft_apply_state_key() {
+        let suffixes = vec![RAFT_LOG_SUFFIX,
+                            RAFT_HARD_STATE_SUFFIX,
+                            RAFT_APPLIED_INDEX_SUFFIX,
+                            RAFT_LAST_INDEX_SUFFIX,
+                            RAFT_TRUNCATED_STATE_SUFFIX];
+        assert!(!suffixes.contains(&RAFT_APPLY_STATE_SUFFIX));
+
+        let region_ids = vec![0, 1, 1024, u64::max_value()];
+        for region_id in region_ids {
+            let key = raft_apply_state_key(region_id);
+            assert_eq!(decode_raft_apply_state_key(&key).unwrap(), region_id);
+            assert!(key > raft_truncated_state_key(region_id));
+            assert!(key > raft_log_key(region_id, u64::max_value()));
+
+            decode_raft_apply_state_key(&raft_truncated_state_key(region_id)).unwrap_err();
+            decode_raft_apply_state_key(&raft_log_key(region_id, 1)).unwrap_err();
+        }
+        let mut key = raft_apply_state_key(1);
+        key[1] = REGION_META_PREFIX;
+        decode_raft_apply_state_key(&key).unwrap_err();
+    }
+
     #[test]
     fn test_region_meta_key() {
         let ids: Vec<u64> = vec![1, 1024, u64::max_value()];
@@ -268,6 +947,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_tombstone_value() {
+        let mut region = new_region(b"a", b"c");
+        region.set_id(3);
+        region.mut_region_epoch().set_version(2);
+        region.mut_region_epoch().set_conf_ver(5);
+        let mut peer = Peer::new();
+        peer.set_id(8);
+        peer.set_store_id(1);
+        region.mut_peers().push(peer);
+        let val = encode_tombstone(&region).unwrap();
+        assert_eq!(decode_tombstone(&val).unwrap(), region);
+
+        // a field without value and invalid wire types are rejected.
+        assert!(decode_tombstone(&val[..1]).is_err());
+        assert!(decode_tombstone(&val[..val.len() - 1]).is_err());
+        assert!(decode_tombstone(&[0xff]).is_err());
+    }
+
+    #[test]
+    fn test_hard_state_value() {
+        let mut state = HardState::new();
+        state.set_term(5);
+        state.set_vote(3);
+        state.set_commit(10);
+        let val = encode_hard_state(&state).unwrap();
+        assert_eq!(decode_hard_state(&val).unwrap(), state);
+
+        let default = HardState::new();
+        assert_eq!(decode_hard_state(&encode_hard_state(&default).unwrap()).unwrap(),
+                   default);
+
+        // a field without value and invalid wire types are rejected.
+        assert!(decode_hard_state(&val[..1]).is_err());
+        assert!(decode_hard_state(&val[..val.len() - 1]).is_err());
+        assert!(decode_hard_state(&[0xff]).is_err());
+    }
+
     #[test]
     fn test_raft_log_key() {
         for region_id in 1..10 {
@@ -276,6 +993,137 @@ mod tests {
                 assert_eq!(idx_id, raft_log_index(&key).unwrap());
             }
         }
+        let key = raft_log_key(1, u64::max_value());
+        assert_eq!(raft_log_index(&key).unwrap(), u64::max_value());
+
+        raft_log_index(&region_raft_prefix(1)).unwrap_err();
+        raft_log_index(&raft_log_prefix(1)).unwrap_err();
+        raft_log_index(&raft_hard_state_key(1)).unwrap_err();
+        // a key of the same length but with a different suffix.
+        let mut key = raft_hard_state_key(1);
+        key.extend_from_slice(&[0; 8]);
+        raft_log_index(&key).unwrap_err();
+    }
+
+    #[test]
+    fn test_key_into() {
+        let mut buf = vec![];
+        for &(region_id, log_index) in &[(1, 1), (u64::max_value(), 3), (2, u64::max_value())] {
+            raft_log_key_into(&mut buf, region_id, log_index);
+            assert_eq!(buf, raft_log_key(region_id, log_index));
+        }
+
+        let builders: Vec<(fn(u64) -> Vec<u8>, fn(&mut Vec<u8>, u64))> =
+            vec![(raft_hard_state_key, raft_hard_state_key_into),
+                 (raft_applied_index_key, raft_applied_index_key_into),
+                 (raft_last_index_key, raft_last_index_key_into),
+                 (raft_truncated_state_key, raft_truncated_state_key_into),
+                 (raft_apply_state_key, raft_apply_state_key_into),
+                 (region_info_key, region_info_key_into),
+                 (region_tombstone_key, region_tombstone_key_into)];
+        // the buffer is reused across different kinds of keys.
+        for (alloc, into) in builders {
+            for &region_id in &[1, 2, u64::max_value()] {
+                into(&mut buf, region_id);
+                assert_eq!(buf, alloc(region_id));
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_raft_log_key() {
+        let ids = vec![0, 1, 1024, u64::max_value()];
+        for &region_id in &ids {
+            for &log_index in &ids {
+                let key = raft_log_key(region_id, log_index);
+                assert_eq!(decode_raft_log_key(&key).unwrap(), (region_id, log_index));
+            }
+        }
+
+        // wrong length.
+        decode_raft_log_key(&raft_log_prefix(1)).unwrap_err();
+        let mut key = raft_log_key(1, 1);
+        key.push(0);
+        decode_raft_log_key(&key).unwrap_err();
+        // wrong prefix.
+        let mut key = raft_log_key(1, 1);
+        key[1] = REGION_META_PREFIX;
+        decode_raft_log_key(&key).unwrap_err();
+        // wrong suffix.
+        let mut key = raft_log_key(1, 1);
+        key[REGION_RAFT_PREFIX_KEY.len() + 8] = RAFT_HARD_STATE_SUFFIX;
+        decode_raft_log_key(&key).unwrap_err();
+    }
+
+    #[test]
+    fn test_prepare_bootstrap_key() {
+        let key = prepare_bootstrap_key();
+        assert_eq!(key[0], LOCAL_PREFIX);
+        assert!(is_local_key(&key));
+        assert!(&*key > LOCAL_MIN_KEY && &*key < LOCAL_MAX_KEY);
+        assert!(key != store_ident_key());
+        assert!(!key.starts_with(REGION_RAFT_PREFIX_KEY));
+        assert!(!key.starts_with(REGION_META_PREFIX_KEY));
+        assert!(!store_ident_key().starts_with(&key));
+    }
+
+    #[test]
+    fn test_key_prefix_kind() {
+        let local_keys = vec![store_ident_key(),
+                              raft_log_key(1, 1),
+                              raft_hard_state_key(1),
+                              region_info_key(1),
+                              LOCAL_MIN_KEY.to_vec()];
+        for key in local_keys {
+            assert!(is_local_key(&key));
+            assert!(!is_data_key(&key));
+            assert_eq!(key_prefix_kind(&key), KeyPrefixKind::Local);
+        }
+
+        let data_keys =
+            vec![data_key(b""), data_key(b"a"), data_key(b"\x01"), DATA_MIN_KEY.to_vec()];
+        for key in data_keys {
+            assert!(!is_local_key(&key));
+            assert!(is_data_key(&key));
+            assert_eq!(key_prefix_kind(&key), KeyPrefixKind::Data);
+        }
+
+        let other_keys = vec![vec![], b"a".to_vec(), LOCAL_MAX_KEY.to_vec(), DATA_MAX_KEY.to_vec()];
+        for key in other_keys {
+            assert!(!is_local_key(&key));
+            assert!(!is_data_key(&key));
+            assert_eq!(key_prefix_kind(&key), KeyPrefixKind::Other);
+        }
+    }
+
+    #[test]
+    fn test_parse_local_key() {
+        let tests = vec![
+            (store_ident_key(), LocalKeyKind::StoreIdent),
+            (prepare_bootstrap_key(), LocalKeyKind::PrepareBootstrap),
+            (raft_log_key(2, 10), LocalKeyKind::RegionRaft(2, RAFT_LOG_SUFFIX)),
+            (raft_hard_state_key(3), LocalKeyKind::RegionRaft(3, RAFT_HARD_STATE_SUFFIX)),
+            (raft_apply_state_key(::std::u64::MAX),
+             LocalKeyKind::RegionRaft(::std::u64::MAX, RAFT_APPLY_STATE_SUFFIX)),
+            (region_info_key(4), LocalKeyKind::RegionMeta(4, REGION_INFO_SUFFIX)),
+            (region_tombstone_key(5), LocalKeyKind::RegionMeta(5, REGION_TOMBSTONE_SUFFIX)),
+        ];
+        for (key, kind) in tests {
+            assert_eq!(parse_local_key(&key).unwrap(), kind);
+        }
+
+        let mut truncated = raft_log_key(1, 1);
+        truncated.pop();
+        let invalid_keys = vec![vec![],
+                                data_key(b"a"),
+                                LOCAL_MIN_KEY.to_vec(),
+                                vec![LOCAL_PREFIX, 0x05],
+                                region_raft_prefix(1),
+                                region_meta_prefix(1),
+                                truncated];
+        for key in invalid_keys {
+            assert!(parse_local_key(&key).is_err(), "{}", escape(&key));
+        }
     }
 
     #[test]
@@ -283,4 +1131,498 @@ mod tests {
         validate_data_key(&data_key(b"abc")).unwrap();
         validate_data_key(b"abc").unwrap_err();
     }
+
+    #[test]
+    fn test_data_prefix() {
+        let prefixes = vec![DataPrefix::Default, DataPrefix::Write, DataPrefix::Lock];
+        for &p in &prefixes {
+            for key in &[b"".to_vec(), b"a".to_vec(), b"\xff\x00".to_vec()] {
+                let k = data_key_with(p, key);
+                assert!(!is_local_key(&k));
+                assert!(k < p.max_key());
+                validate_data_key_with(p, &k).unwrap();
+                assert_eq!(origin_key_with(p, &k), &**key);
+                for &other in prefixes.iter().filter(|&&o| o != p) {
+                    assert!(validate_data_key_with(other, &k).is_err());
+                }
+            }
+        }
+        assert_eq!(data_key_with(DataPrefix::Default, b"a"), data_key(b"a"));
+        assert_eq!(DataPrefix::Default.max_key(), DATA_MAX_KEY);
+
+        let mut region = Region::new();
+        region.set_start_key(b"a".to_vec());
+        assert_eq!(enc_start_key_with(DataPrefix::Write, &region),
+                   data_key_with(DataPrefix::Write, b"a"));
+        assert_eq!(enc_end_key_with(DataPrefix::Lock, &region), DataPrefix::Lock.max_key());
+        region.set_end_key(b"b".to_vec());
+        assert_eq!(enc_end_key_with(DataPrefix::Lock, &region),
+                   data_key_with(DataPrefix::Lock, b"b"));
+        assert_eq!(enc_end_key(&region), data_key(b"b"));
+    }
+
+    #[test]
+    fn test_next_key() {
+        assert_eq!(next_key(b""), vec![0]);
+        assert_eq!(next_key(b"a"), b"a\x00".to_vec());
+        assert_eq!(next_key(b"\xff"), b"\xff\x00".to_vec());
+
+        assert_eq!(prefix_next(b"a"), b"b".to_vec());
+        assert_eq!(prefix_next(b"ab\xff"), b"ac".to_vec());
+        assert_eq!(prefix_next(b"a\xfe\xff\xff"), b"a\xff".to_vec());
+        assert!(prefix_next(b"\xff\xff").is_empty());
+        assert!(prefix_next(b"").is_empty());
+
+        for key in &[b"a".to_vec(), b"ab\xff".to_vec(), b"\x00".to_vec()] {
+            assert!(next_key(key) > *key);
+            let mut prefixed = key.clone();
+            prefixed.extend_from_slice(b"\xff\xff");
+            assert!(prefix_next(key) > prefixed);
+        }
+    }
+
+    #[test]
+    fn test_mvcc_key() {
+        let keys = vec![data_key(b""), data_key(b"abc"), data_key(b"\x00\xff")];
+        let ts = vec![0, 1, 1024, u64::max_value()];
+        for key in &keys {
+            for &t in &ts {
+                let encoded = mvcc_encode_key(key, t);
+                assert_eq!(mvcc_decode_key(&encoded).unwrap(), (key.clone(), t));
+            }
+
+            // larger ts sorts first.
+            for w in ts.windows(2) {
+                assert!(mvcc_encode_key(key, w[1]) < mvcc_encode_key(key, w[0]));
+            }
+        }
+
+        assert_eq!(mvcc_decode_key(&[0; 8]).unwrap(), (vec![], u64::max_value()));
+        mvcc_decode_key(b"").unwrap_err();
+        mvcc_decode_key(b"zabcdef").unwrap_err();
+    }
+
+    #[test]
+    fn test_key_in_region() {
+        let mut region = Region::new();
+        region.set_start_key(b"b".to_vec());
+        region.set_end_key(b"d".to_vec());
+
+        assert!(key_in_region(&data_key(b"c"), &region));
+        assert!(key_in_region(&data_key(b"b"), &region));
+        assert!(key_in_region(&data_key(b"b\x00"), &region));
+        assert!(!key_in_region(&data_key(b"d"), &region));
+        assert!(!key_in_region(&data_key(b"a"), &region));
+        assert!(!key_in_region(&data_key(b"e"), &region));
+
+        // region with empty start and end key.
+        let region = Region::new();
+        assert!(key_in_region(&data_key(b""), &region));
+        assert!(key_in_region(&data_key(b"a"), &region));
+        assert!(key_in_region(&data_key(b"\xff\xff"), &region));
+        assert!(!key_in_region(b"a", &region));
+    }
+
+    fn new_region(start_key: &[u8], end_key: &[u8]) -> Region {
+        let mut region = Region::new();
+        region.set_start_key(start_key.to_vec());
+        region.set_end_key(end_key.to_vec());
+        region
+    }
+
+    #[test]
+    fn test_data_range() {
+        let mut region = Region::new();
+        region.set_start_key(b"b".to_vec());
+        region.set_end_key(b"d".to_vec());
+        let (start, end) = data_range(&region);
+        assert_eq!(start, data_key(b"b"));
+        assert_eq!(end, data_key(b"d"));
+        for key in &[b"b".as_ref(), b"b\x00", b"c", b"c\xff"] {
+            let key = data_key(key);
+            assert!(start <= key && key < end, "{}", escape(&key));
+        }
+        for key in &[b"a".as_ref(), b"d", b"e"] {
+            let key = data_key(key);
+            assert!(key < start || key >= end, "{}", escape(&key));
+        }
+
+        // the end of an open-ended region is the end of the whole data space.
+        region.set_end_key(vec![]);
+        let (start, end) = data_range(&region);
+        assert_eq!(start, data_key(b"b"));
+        assert_eq!(end, DATA_MAX_KEY.to_vec());
+        for key in &[b"b".as_ref(), b"z", b"\xff\xff\xff"] {
+            let key = data_key(key);
+            assert!(start <= key && key < end, "{}", escape(&key));
+        }
+
+        let (start, end) = data_range(&Region::new());
+        assert_eq!(start, DATA_MIN_KEY.to_vec());
+        assert_eq!(end, DATA_MAX_KEY.to_vec());
+    }
+
+    #[test]
+    fn test_approximate_span() {
+        let ab = region_approximate_span(&new_region(b"a", b"b"));
+        let ac = region_approximate_span(&new_region(b"a", b"c"));
+        assert!(ab > 0);
+        assert_eq!(ac, ab * 2);
+        assert!(region_approximate_span(&new_region(b"a", b"")) > ac);
+        assert!(region_approximate_span(&new_region(b"", b"b")) > ab);
+        assert_eq!(region_approximate_span(&new_region(b"a", b"a")), 0);
+
+        assert_eq!(approximate_range_span(b"b", b"a"), 0);
+        assert_eq!(approximate_range_span(b"", b"\x00\x00\x00\x00\x00\x00\x00\x01"), 1);
+        // only the leading 8 bytes are taken into account.
+        assert_eq!(approximate_range_span(b"aaaaaaaaa", b"aaaaaaaab"), 0);
+    }
+
+    #[test]
+    fn test_split_key() {
+        let tests: Vec<(&[u8], &[u8], &[u8])> = vec![
+            (b"a", b"c", b"b"),
+            (b"a", b"b", b"a\x80"),
+            (b"", b"b", b"1"),
+            (b"a", b"a\x01", b"a\x00"),
+            (b"a\xff", b"b", b"a\xff\x80"),
+            (b"ab", b"b", b"a\xb1"),
+            (b"\xff", b"\xff\xff", b"\xff\x7f"),
+        ];
+        for (start, end, expect) in tests {
+            let mid = split_key(start, end).unwrap();
+            assert_eq!(&*mid, expect);
+            assert!(&*mid > start && &*mid < end, "{}", escape(&mid));
+        }
+
+        let invalid: Vec<(&[u8], &[u8])> =
+            vec![(b"a", b"a"), (b"b", b"a"), (b"a", b"a\x00"), (b"", b"\x00"), (b"a", b"")];
+        for (start, end) in invalid {
+            assert!(split_key(start, end).is_err());
+        }
+    }
+
+    #[test]
+    fn test_regions_overlap() {
+        let tbls = vec![
+            // disjoint
+            (b"a".as_ref(), b"b".as_ref(), b"c".as_ref(), b"d".as_ref(), false),
+            // adjacent
+            (b"a", b"b", b"b", b"c", false),
+            (b"", b"b", b"b", b"", false),
+            // partial overlap
+            (b"a", b"c", b"b", b"d", true),
+            (b"", b"c", b"b", b"", true),
+            (b"a", b"", b"", b"b", true),
+            // fully contained
+            (b"a", b"d", b"b", b"c", true),
+            (b"", b"", b"b", b"c", true),
+            (b"a", b"b", b"a", b"b", true),
+            (b"", b"", b"", b"", true),
+        ];
+        for (a_start, a_end, b_start, b_end, overlap) in tbls {
+            let a = new_region(a_start, a_end);
+            let b = new_region(b_start, b_end);
+            assert!(regions_overlap(&a, &b) == overlap, "{:?} {:?}", a, b);
+            assert!(regions_overlap(&b, &a) == overlap, "{:?} {:?}", b, a);
+        }
+    }
+
+    #[test]
+    fn test_region_boundary_key() {
+        let region = new_region(b"b", b"d");
+        let tbls = vec![(data_key(b"b"), true, false),
+                        (data_key(b"c"), false, false),
+                        (data_key(b"d"), false, true),
+                        (data_key(b"b\x00"), false, false),
+                        (b"b".to_vec(), false, false),
+                        (DATA_MAX_KEY.to_vec(), false, false)];
+        for (key, is_start, is_end) in tbls {
+            assert_eq!(is_region_start_key(&key, &region), is_start);
+            assert_eq!(is_region_end_key(&key, &region), is_end);
+        }
+
+        let region = new_region(b"", b"");
+        assert!(is_region_start_key(&data_key(b""), &region));
+        assert!(is_region_start_key(DATA_MIN_KEY, &region));
+        assert!(!is_region_start_key(&data_key(b"a"), &region));
+        assert!(is_region_end_key(DATA_MAX_KEY, &region));
+        assert!(!is_region_end_key(&data_key(b""), &region));
+    }
+
+    #[test]
+    fn test_region_destroy_ranges() {
+        fn region_keys(region_id: u64) -> Vec<Vec<u8>> {
+            vec![raft_log_key(region_id, 0),
+                 raft_log_key(region_id, u64::max_value()),
+                 raft_hard_state_key(region_id),
+                 raft_applied_index_key(region_id),
+                 raft_last_index_key(region_id),
+                 raft_truncated_state_key(region_id),
+                 raft_apply_state_key(region_id),
+                 region_info_key(region_id),
+                 region_tombstone_key(region_id)]
+        }
+
+        let mut left = new_region(b"", b"b");
+        left.set_id(4);
+        let mut region = new_region(b"b", b"d");
+        region.set_id(5);
+        let mut right = new_region(b"d", b"");
+        right.set_id(6);
+
+        let ranges = region_destroy_ranges(&region);
+        assert_eq!(ranges.len(), 3);
+        let in_ranges = |key: &[u8]| {
+            ranges.iter().filter(|r| key >= &*r.0 && key < &*r.1).count()
+        };
+
+        let mut keys = region_keys(5);
+        keys.extend(vec![data_key(b"b"), data_key(b"c"), data_key(b"cz\xff")]);
+        for key in &keys {
+            assert!(in_ranges(&key[..]) == 1, "{:?} should be in exactly one range", key);
+        }
+        let mut neighbors = region_keys(4);
+        neighbors.extend(region_keys(6));
+        neighbors.extend(vec![data_key(b""),
+                              data_key(b"a"),
+                              data_key(b"a\xff"),
+                              data_key(b"d"),
+                              data_key(b"e"),
+                              STORE_IDENT_KEY.to_vec()]);
+        for key in &neighbors {
+            assert!(in_ranges(&key[..]) == 0, "{:?} should not be in range", key);
+        }
+
+        // the last region id doesn't overflow.
+        let mut last = new_region(b"", b"");
+        last.set_id(u64::max_value());
+        let ranges = region_destroy_ranges(&last);
+        for key in region_keys(u64::max_value()) {
+            assert!(ranges.iter().filter(|r| key >= r.0 && key < r.1).count() == 1);
+        }
+        for key in region_keys(u64::max_value() - 1) {
+            assert!(ranges.iter().all(|r| key < r.0 || key >= r.1));
+        }
+    }
+
+    #[test]
+    fn test_region_contains() {
+        let tbls = vec![
+            // contained
+            (b"a".as_ref(), b"d".as_ref(), b"b".as_ref(), b"c".as_ref(), true),
+            (b"a", b"d", b"a", b"d", true),
+            (b"a", b"d", b"a", b"b", true),
+            (b"a", b"d", b"c", b"d", true),
+            // partial overlap
+            (b"a", b"c", b"b", b"d", false),
+            (b"b", b"d", b"a", b"c", false),
+            (b"a", b"c", b"b", b"", false),
+            (b"b", b"c", b"", b"c", false),
+            // disjoint
+            (b"a", b"b", b"c", b"d", false),
+            (b"a", b"b", b"b", b"c", false),
+            // unbounded a
+            (b"", b"", b"a", b"b", true),
+            (b"", b"", b"", b"", true),
+            (b"", b"c", b"", b"b", true),
+            (b"a", b"", b"b", b"", true),
+            (b"a", b"", b"", b"b", false),
+        ];
+        for (a_start, a_end, b_start, b_end, contains) in tbls {
+            let a = new_region(a_start, a_end);
+            let b = new_region(b_start, b_end);
+            assert!(region_contains(&a, &b) == contains, "{:?} {:?}", a, b);
+            if contains {
+                assert!(regions_overlap(&a, &b));
+            }
+        }
+    }
+
+    #[test]
+    fn test_data_key_cmp() {
+        let keys: Vec<&[u8]> =
+            vec![b"", b"\x00", b"a", b"a\x00", b"ab", b"b", b"\xff", b"\xff\xff"];
+        for l in &keys {
+            for r in &keys {
+                let (lhs, rhs) = (data_key(l), data_key(r));
+                assert_eq!(data_key_cmp(&lhs, &rhs), lhs.cmp(&rhs));
+                assert_eq!(data_key_cmp(&lhs, &rhs), l.cmp(r));
+            }
+        }
+
+        let mut sorted: Vec<_> = keys.iter().rev().map(|k| data_key(k)).collect();
+        sorted.sort_by(|a, b| data_key_cmp(a, b));
+        assert_eq!(sorted, keys.iter().map(|k| data_key(k)).collect::<Vec<_>>());
+
+        // non-data keys are not comparable.
+        let a = data_key(b"a");
+        assert!(recover_safe!(|| data_key_cmp(b"a", &a)).is_err());
+        assert!(recover_safe!(|| data_key_cmp(&a, b"")).is_err());
+        assert!(recover_safe!(|| data_key_cmp(DATA_MAX_KEY, &a)).is_err());
+    }
+
+    #[test]
+    fn test_data_end_bound() {
+        let region = new_region(b"", b"");
+        let end_key = enc_end_key(&region);
+        assert!(is_data_end_bound(&end_key));
+        validate_data_key(&end_key).unwrap_err();
+        assert_eq!(origin_end_key(&end_key), EMPTY_KEY);
+        assert!(!key_in_region(&end_key, &region));
+
+        let region = new_region(b"a", b"b");
+        let end_key = enc_end_key(&region);
+        assert!(!is_data_end_bound(&end_key));
+        assert_eq!(origin_end_key(&end_key), b"b");
+        assert!(!is_data_end_bound(&data_key(b"")));
+        assert!(!is_data_end_bound(&data_key(b"\xff")));
+
+        // the round trip of an end key.
+        for end in &[b"".as_ref(), b"a", b"\xff"] {
+            let region = new_region(b"", end);
+            assert_eq!(origin_end_key(&enc_end_key(&region)), *end);
+        }
+    }
+
+    #[test]
+    fn test_split_boundaries() {
+        let mut region = Region::new();
+        region.set_start_key(b"b".to_vec());
+        region.set_end_key(b"d".to_vec());
+
+        let (left_start, split_key, right_end) = split_boundaries(&region, b"c").unwrap();
+        assert_eq!(left_start, b"b".to_vec());
+        assert_eq!(split_key, b"c".to_vec());
+        assert_eq!(right_end, b"d".to_vec());
+
+        split_boundaries(&region, b"b").unwrap_err();
+        let tbls = vec![
+            (b"a", KeyPosition::BeforeStart),
+            (b"d", KeyPosition::AtOrAfterEnd),
+            (b"e", KeyPosition::AtOrAfterEnd),
+        ];
+        for (key, pos) in tbls {
+            match split_boundaries(&region, key) {
+                Err(Error::KeyNotInRegion(k, r, p)) => {
+                    assert_eq!(k, key.to_vec());
+                    assert_eq!(r, region);
+                    assert_eq!(p, pos);
+                }
+                r => panic!("expect key not in region, but got {:?}", r),
+            }
+        }
+
+        // region with empty start and end key.
+        let region = Region::new();
+        let (left_start, split_key, right_end) = split_boundaries(&region, b"a").unwrap();
+        assert!(left_start.is_empty());
+        assert_eq!(split_key, b"a".to_vec());
+        assert!(right_end.is_empty());
+        split_boundaries(&region, b"").unwrap_err();
+    }
+
+    #[test]
+    fn test_check_key_in_region() {
+        let tbls = vec![
+            (b"" as &[u8], b"" as &[u8], b"" as &[u8], KeyPosition::InRegion),
+            (b"", b"", b"\xff", KeyPosition::InRegion),
+            (b"b", b"d", b"a", KeyPosition::BeforeStart),
+            (b"b", b"d", b"", KeyPosition::BeforeStart),
+            (b"b", b"d", b"b", KeyPosition::InRegion),
+            (b"b", b"d", b"c\xff", KeyPosition::InRegion),
+            (b"b", b"d", b"d", KeyPosition::AtOrAfterEnd),
+            (b"b", b"d", b"e", KeyPosition::AtOrAfterEnd),
+            (b"b", b"", b"e", KeyPosition::InRegion),
+        ];
+        for (start_key, end_key, key, pos) in tbls {
+            let region = new_region(start_key, end_key);
+            assert_eq!(key_position(key, &region), pos);
+            let res = check_key_in_region(key, &region);
+            if pos == KeyPosition::InRegion {
+                res.unwrap();
+                continue;
+            }
+            match res {
+                Err(Error::KeyNotInRegion(k, r, p)) => {
+                    assert_eq!(k, key.to_vec());
+                    assert_eq!(r, region);
+                    assert_eq!(p, pos);
+                }
+                r => panic!("expect key not in region, but got {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_region_meta_keys() {
+        let (start, end) = region_meta_range();
+        let keys = vec![region_info_key(1),
+                        region_tombstone_key(1),
+                        region_info_key(2),
+                        raft_log_key(2, 1),
+                        region_tombstone_key(::std::u64::MAX)];
+        for key in &keys[..3] {
+            assert!(start <= *key && *key < end);
+        }
+        assert!(raft_log_key(2, 1) < start);
+
+        let decoded: Vec<_> = decode_region_meta_keys(&keys).collect();
+        assert_eq!(decoded.len(), keys.len());
+        assert_eq!(decoded[0].as_ref().unwrap(), &(1, REGION_INFO_SUFFIX));
+        assert_eq!(decoded[1].as_ref().unwrap(), &(1, REGION_TOMBSTONE_SUFFIX));
+        assert_eq!(decoded[2].as_ref().unwrap(), &(2, REGION_INFO_SUFFIX));
+        assert!(decoded[3].is_err());
+        assert_eq!(decoded[4].as_ref().unwrap(),
+                   &(::std::u64::MAX, REGION_TOMBSTONE_SUFFIX));
+    }
+
+    #[test]
+    fn test_each_region_meta() {
+        let kvs = vec![(raft_log_key(1, 1), b"log".to_vec()),
+                       (region_info_key(1), b"r1".to_vec()),
+                       (region_tombstone_key(1), b"t1".to_vec()),
+                       (region_tombstone_key(2), b"t2".to_vec()),
+                       (region_info_key(3), b"r3".to_vec()),
+                       (region_tombstone_key(3), b"t3".to_vec()),
+                       (data_key(b"a"), b"data".to_vec())];
+
+        let mut surfaced = vec![];
+        each_region_meta(kvs.clone(), true, |region_id, suffix, value| {
+                surfaced.push((region_id, suffix, value.to_vec()));
+                Ok(true)
+            })
+            .unwrap();
+        assert_eq!(surfaced,
+                   vec![(1, REGION_INFO_SUFFIX, b"r1".to_vec()),
+                        (3, REGION_INFO_SUFFIX, b"r3".to_vec())]);
+
+        let mut surfaced = vec![];
+        each_region_meta(kvs.clone(), false, |region_id, suffix, _| {
+                surfaced.push((region_id, suffix));
+                Ok(true)
+            })
+            .unwrap();
+        assert_eq!(surfaced,
+                   vec![(1, REGION_INFO_SUFFIX),
+                        (1, REGION_TOMBSTONE_SUFFIX),
+                        (2, REGION_TOMBSTONE_SUFFIX),
+                        (3, REGION_INFO_SUFFIX),
+                        (3, REGION_TOMBSTONE_SUFFIX)]);
+
+        // stop once the callback returns false.
+        let mut count = 0;
+        each_region_meta(kvs, true, |_, _, _| {
+                count += 1;
+                Ok(false)
+            })
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // malformed region meta keys are reported.
+        let mut bad_key = region_info_key(1);
+        bad_key.push(0);
+        let kvs = vec![(bad_key, b"".to_vec())];
+        assert!(each_region_meta(kvs, true, |_, _, _| Ok(true)).is_err());
+    }
 }
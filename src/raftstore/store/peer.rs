@@ -154,7 +154,8 @@ impl Peer {
                                                 -> Result<Peer> {
         let store_id = store.store_id();
         let tombstone_key = &keys::region_tombstone_key(region_id);
-        if let Some(region) = try!(store.engine().get_msg::<metapb::Region>(tombstone_key)) {
+        if let Some(val) = try!(store.engine().get_value(tombstone_key)) {
+            let region = try!(keys::decode_tombstone(&val));
             let region_epoch = region.get_region_epoch();
             // The region in this peer already destroyed
             if !(from_epoch.get_version() >= region_epoch.get_version() &&
@@ -233,7 +234,8 @@ impl Peer {
                                                Ok(true)
                                            }));
 
-        try!(wb.put_msg(&keys::region_tombstone_key(self.region_id), &self.region()));
+        let tombstone = try!(keys::encode_tombstone(&self.region()));
+        try!(wb.put(&keys::region_tombstone_key(self.region_id), &tombstone));
         try!(self.engine.write(wb));
 
         self.coprocessor_host.shutdown();
@@ -792,15 +794,10 @@ impl Peer {
             return Err(box_err!("missing split key"));
         }
 
-        let split_key = split_req.get_split_key();
         let mut region = self.region();
-        if split_key <= region.get_start_key() {
-            return Err(box_err!("invalid split request: {:?}", split_req));
-        }
-
-        try!(util::check_key_in_region(split_key, &region));
+        let (_, split_key, _) = try!(keys::split_boundaries(&region, split_req.get_split_key()));
 
-        info!("split at key: {}, region: {:?}", escape(split_key), region);
+        info!("split at key: {}, region: {:?}", escape(&split_key), region);
 
         // TODO: check new region id validation.
         let new_region_id = split_req.get_new_region_id();
@@ -808,9 +805,9 @@ impl Peer {
         // After split, the origin region key range is [start_key, split_key),
         // the new split region is [split_key, end).
         let mut new_region = region.clone();
-        region.set_end_key(split_key.to_vec());
+        region.set_end_key(split_key.clone());
 
-        new_region.set_start_key(split_key.to_vec());
+        new_region.set_start_key(split_key);
         new_region.set_id(new_region_id);
 
         // New region has the same store ids with origin split region.
@@ -882,7 +879,7 @@ impl Peer {
 
     fn check_data_key(&self, key: &[u8]) -> Result<()> {
         // region key range has no data prefix, so we must use origin key to check.
-        try!(util::check_key_in_region(key, self.storage.rl().get_region()));
+        try!(keys::check_key_in_region(key, self.storage.rl().get_region()));
 
         Ok(())
     }
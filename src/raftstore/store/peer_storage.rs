@@ -26,7 +26,7 @@ use kvproto::raft_serverpb::{RaftSnapshotData, KeyValue, RaftTruncatedState};
 use util::HandyRwLock;
 use raft::{self, Storage, RaftState, StorageError, Error as RaftError, Ready};
 use raftstore::{Result, Error};
-use super::keys::{self, enc_start_key, enc_end_key};
+use super::keys;
 use super::engine::{Peekable, Iterable, Mutable};
 
 // When we create a region peer, we should initialize its log term/index > 0,
@@ -110,10 +110,12 @@ impl PeerStorage {
 
     pub fn initial_state(&mut self) -> raft::Result<RaftState> {
         let initialized = self.is_initialized();
-        let hs = try!(self.engine
-                          .get_msg(&keys::raft_hard_state_key(self.get_region_id())));
+        let hs = try!(self.engine.get_value(&keys::raft_hard_state_key(self.get_region_id())));
 
-        let (mut hard_state, found) = hs.map_or((HardState::new(), false), |s| (s, true));
+        let (mut hard_state, found) = match hs {
+            Some(val) => (try!(keys::decode_hard_state(&val)), true),
+            None => (HardState::new(), false),
+        };
 
         if !found {
             if initialized {
@@ -267,16 +269,18 @@ impl PeerStorage {
             return Ok(prev_last_index);
         }
 
+        let mut key = vec![];
         for entry in entries {
-            try!(w.put_msg(&keys::raft_log_key(self.get_region_id(), entry.get_index()),
-                           entry));
+            keys::raft_log_key_into(&mut key, self.get_region_id(), entry.get_index());
+            try!(w.put_msg(&key, entry));
         }
 
         let last_index = entries[entries.len() - 1].get_index();
 
         // Delete any previously appended log entries which never committed.
         for i in (last_index + 1)..(prev_last_index + 1) {
-            try!(w.delete(&keys::raft_log_key(self.get_region_id(), i)));
+            keys::raft_log_key_into(&mut key, self.get_region_id(), i);
+            try!(w.delete(&key));
         }
 
         try!(save_last_index(w, self.get_region_id(), last_index));
@@ -298,7 +302,10 @@ impl PeerStorage {
         // records the previous vote.
         // TODO: maybe exclude hard state when do snapshot.
         let hard_state_key = keys::raft_hard_state_key(region_id);
-        let hard_state: Option<HardState> = try!(self.engine.get_msg(&hard_state_key));
+        let hard_state = match try!(self.engine.get_value(&hard_state_key)) {
+            Some(val) => Some(try!(keys::decode_hard_state(&val))),
+            None => None,
+        };
 
         let region = snap_data.get_region();
         if region.get_id() != region_id {
@@ -321,7 +328,7 @@ impl PeerStorage {
         // Restore the hard state
         match hard_state {
             None => try!(w.delete(&hard_state_key)),
-            Some(state) => try!(w.put_msg(&hard_state_key, &state)),
+            Some(state) => try!(save_hard_state(w, region_id, &state)),
         }
 
         let last_index = snap.get_metadata().get_index();
@@ -436,14 +443,7 @@ impl PeerStorage {
     // [region meta start, region meta end) -> saving region meta information except raft.
     // [region data start, region data end) -> saving region data.
     pub fn region_key_ranges(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
-        let (start_key, end_key) = (enc_start_key(self.get_region()),
-                                    enc_end_key(self.get_region()));
-
-        let region_id = self.get_region_id();
-        vec![(keys::region_raft_prefix(region_id), keys::region_raft_prefix(region_id + 1)),
-             (keys::region_meta_prefix(region_id), keys::region_meta_prefix(region_id + 1)),
-             (start_key, end_key)]
-
+        keys::region_destroy_ranges(self.get_region())
     }
 
     /// scan all region related kv
@@ -574,7 +574,9 @@ pub fn do_snapshot(snap: &RocksDbSnapshot,
 }
 
 pub fn save_hard_state<T: Mutable>(w: &T, region_id: u64, state: &HardState) -> Result<()> {
-    w.put_msg(&keys::raft_hard_state_key(region_id), state)
+    let val = try!(keys::encode_hard_state(state));
+    try!(w.put(&keys::raft_hard_state_key(region_id), &val));
+    Ok(())
 }
 
 pub fn save_truncated_state<T: Mutable>(w: &T,
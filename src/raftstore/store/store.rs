@@ -18,7 +18,7 @@ use std::boxed::{Box, FnBox};
 use std::collections::Bound::{Excluded, Unbounded};
 use std::time::Duration;
 
-use rocksdb::DB;
+use rocksdb::{DB, Range};
 use mio::{self, EventLoop, EventLoopBuilder};
 use protobuf;
 use uuid::Uuid;
@@ -128,26 +128,20 @@ impl<T: Transport, C: PdClient> Store<T, C> {
     // Do something before store runs.
     fn prepare(&mut self) -> Result<()> {
         // Scan region meta to get saved regions.
-        let start_key = keys::REGION_META_MIN_KEY;
-        let end_key = keys::REGION_META_MAX_KEY;
+        let (start_key, _) = keys::region_meta_range();
         let engine = self.engine.clone();
-        try!(engine.scan(start_key,
-                         end_key,
-                         &mut |key, value|{
-                             let (region_id, suffix) = try!(keys::decode_region_meta_key(key));
-                             if suffix != keys::REGION_INFO_SUFFIX {
-                                 return Ok(true);
-                             }
-
-                             let region = try!(protobuf::parse_from_bytes::<metapb::Region>(value));
-                             let peer = try!(Peer::create(self, &region));
-
-                             self.region_ranges.insert(enc_end_key(&region), region_id);
-        // No need to check duplicated here, because we use region id as the key
-        // in DB.
-                             self.region_peers.insert(region_id, peer);
-                             Ok(true)
-                         }));
+        try!(keys::each_region_meta(engine.new_iterator(&start_key),
+                                    true,
+                                    |region_id, _, value| {
+            let region = try!(protobuf::parse_from_bytes::<metapb::Region>(value));
+            let peer = try!(Peer::create(self, &region));
+
+            self.region_ranges.insert(enc_end_key(&region), region_id);
+            // No need to check duplicated here, because we use region id as the key
+            // in DB.
+            self.region_peers.insert(region_id, peer);
+            Ok(true)
+        }));
 
         Ok(())
     }
@@ -835,7 +829,7 @@ impl<T: Transport, C: PdClient> Store<T, C> {
         let mut response = try!(match cmd_type {
             StatusCmdType::RegionLeader => self.execute_region_leader(request),
             StatusCmdType::RegionDetail => self.execute_region_detail(request),
-            StatusCmdType::StoreStats => Err(box_err!("unsupported store statistic now")),
+            StatusCmdType::StoreStats => self.execute_store_stats(),
             StatusCmdType::InvalidStatus => Err(box_err!("invalid status command!")),
         });
         response.set_cmd_type(cmd_type);
@@ -860,6 +854,26 @@ impl<T: Transport, C: PdClient> Store<T, C> {
         Ok(resp)
     }
 
+    fn execute_store_stats(&self) -> Result<StatusResponse> {
+        let leader_count = self.region_peers.values().filter(|p| p.is_leader()).count();
+        // scanning all the data here would block the raftstore thread, so use
+        // the approximate size from rocksdb, which is estimated from the sst
+        // files' index without reading any data.
+        let range = Range::new(keys::DATA_MIN_KEY, keys::DATA_MAX_KEY);
+        let used_size = self.engine.get_approximate_sizes(&[range])[0];
+
+        let mut resp = StatusResponse::new();
+        {
+            let stats = resp.mut_store_stats();
+            stats.set_store_id(self.store_id());
+            stats.set_region_count(self.region_peers.len() as u64);
+            stats.set_leader_count(leader_count as u64);
+            stats.set_used_size(used_size);
+        }
+
+        Ok(resp)
+    }
+
     fn execute_region_detail(&mut self, request: RaftCmdRequest) -> Result<StatusResponse> {
         let peer = try!(self.mut_target_peer(&request));
         if !peer.storage.rl().is_initialized() {
@@ -18,7 +18,6 @@ use uuid::Uuid;
 use kvproto::metapb;
 use kvproto::raftpb::{self, ConfChangeType};
 use kvproto::raft_cmdpb::RaftCmdRequest;
-use raftstore::{Result, Error};
 
 pub fn find_peer(region: &metapb::Region, store_id: u64) -> bool {
     region.get_store_ids().iter().any(|&id| id == store_id)
@@ -40,16 +39,6 @@ pub fn get_uuid_from_req(cmd: &RaftCmdRequest) -> Option<Uuid> {
     Uuid::from_bytes(cmd.get_header().get_uuid())
 }
 
-pub fn check_key_in_region(key: &[u8], region: &metapb::Region) -> Result<()> {
-    let end_key = region.get_end_key();
-    let start_key = region.get_start_key();
-    if key >= start_key && (end_key.is_empty() || key < end_key) {
-        Ok(())
-    } else {
-        Err(Error::KeyNotInRegion(key.to_vec(), region.clone()))
-    }
-}
-
 pub fn conf_change_type_str(conf_type: &raftpb::ConfChangeType) -> String {
     match *conf_type {
         ConfChangeType::AddNode => "AddNode".to_owned(),
@@ -63,18 +63,21 @@ pub struct Runner;
 impl Runner {
     /// Do the compact job and return the count of log compacted.
     fn compact(&mut self, task: Task) -> Result<u64, Error> {
-        let start_key = keys::raft_log_key(task.region_id, 0);
+        let (start_key, end_key) = keys::raft_log_gc_range(task.region_id, task.compact_idx);
         let mut first_idx = task.compact_idx;
         if let Some((k, _)) = box_try!(task.engine.seek(&start_key)) {
-            first_idx = box_try!(keys::raft_log_index(&k));
+            if k < end_key {
+                first_idx = box_try!(keys::raft_log_index(&k));
+            }
         }
         if first_idx >= task.compact_idx {
             info!("no need to compact");
             return Ok(0);
         }
         let wb = WriteBatch::new();
+        let mut key = vec![];
         for idx in first_idx..task.compact_idx {
-            let key = keys::raft_log_key(task.region_id, idx);
+            keys::raft_log_key_into(&mut key, task.region_id, idx);
             box_try!(wb.delete(&key));
         }
         box_try!(task.engine.write(wb));
@@ -33,11 +33,12 @@ pub struct Task {
 
 impl Task {
     pub fn new(ps: &PeerStorage) -> Task {
+        let (start_key, end_key) = keys::data_range(&ps.region);
         Task {
             region_id: ps.get_region_id(),
             epoch: ps.get_region().get_region_epoch().clone(),
-            start_key: keys::enc_start_key(&ps.region),
-            end_key: keys::enc_end_key(&ps.region),
+            start_key: start_key,
+            end_key: end_key,
             engine: ps.get_engine().clone(),
         }
     }
@@ -67,9 +68,10 @@ impl Runner {
 
 impl Runnable<Task> for Runner {
     fn run(&mut self, task: Task) {
+        // the end key may be the unbounded sentinel which is not a data key.
         debug!("executing task {} {}",
-               escape(&task.start_key),
-               escape(&task.end_key));
+               escape(keys::origin_key(&task.start_key)),
+               escape(keys::origin_end_key(&task.end_key)));
         let mut size = 0;
         let mut split_key = vec![];
         let res = task.engine.scan(&task.start_key,
@@ -30,7 +30,7 @@ use kvproto::coprocessor::{Request, Response, KeyRange};
 use kvproto::errorpb;
 use storage::Key;
 use util::codec::{Datum, table, datum, number};
-use util::xeval::Evaluator;
+use util::xeval::{Evaluator, Collation};
 use util::{as_slice, escape};
 use util::SlowTimer;
 use server::{SendCh, Msg, ConnData};
@@ -290,8 +290,13 @@ impl<'a> SelectContext<'a> {
             } else {
                 ctx.sel.get_index_info().get_columns()
             };
+            // reject malformed pushdowns before any row is scanned.
+            box_try!(Evaluator::validate(ctx.sel.get_field_where()));
             try!(collect_col_in_expr(&mut ctx.cond_cols, cols, ctx.sel.get_field_where()));
         }
+        for (&col_id, col) in &ctx.cond_cols {
+            ctx.eval.collations.insert(col_id, Collation::from_id(col.get_collation()));
+        }
         Ok(ctx)
     }
 
@@ -27,7 +27,7 @@ mod types;
 pub use self::engine::{Engine, Snapshot, Dsn, TEMP_DIR, new_engine, Modify, Error as EngineError};
 pub use self::engine::raftkv::RaftKv;
 pub use self::txn::SnapshotStore;
-pub use self::types::{Key, Value, KvPair};
+pub use self::types::{Key, Value, KvPair, mvcc_key_cmp};
 pub type Callback<T> = Box<FnBox(Result<T>) + Send>;
 
 #[cfg(test)]
@@ -13,9 +13,12 @@
 
 use std::hash::{Hash, Hasher};
 use std::mem;
+use std::cmp::Ordering;
+use std::u64;
 use std::fmt::{self, Formatter, Display};
-use byteorder::{BigEndian, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
 use util::{escape, codec};
+use util::codec::bytes;
 
 pub type Value = Vec<u8>;
 pub type KvPair = (Vec<u8>, Value);
@@ -59,8 +62,73 @@ impl Display for Key {
     }
 }
 
+/// Split an mvcc key into the encoded user key and its timestamp.
+///
+/// The user key is memcomparable encoded, whose length is a multiple of the
+/// encoded group size, so a key with a timestamp has 8 extra bytes. A key
+/// without the timestamp suffix is treated as having the max timestamp.
+fn split_ts(key: &[u8]) -> (&[u8], u64) {
+    let ts_len = mem::size_of::<u64>();
+    if key.len() < ts_len || key.len() % (bytes::ENC_GROUP_SIZE + 1) != ts_len {
+        return (key, u64::MAX);
+    }
+    let (user_key, ts) = key.split_at(key.len() - ts_len);
+    (user_key, BigEndian::read_u64(ts))
+}
+
+/// Compare two mvcc keys, user keys are in ascending order while timestamps
+/// of the same user key are in descending order, so the newest version
+/// comes first.
+pub fn mvcc_key_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    let (a_key, a_ts) = split_ts(a);
+    let (b_key, b_ts) = split_ts(b);
+    match a_key.cmp(b_key) {
+        Ordering::Equal => b_ts.cmp(&a_ts),
+        ord => ord,
+    }
+}
+
 #[cfg(test)]
 pub fn make_key(k: &[u8]) -> Key {
     use util::codec::bytes;
     Key::from_raw(bytes::encode_bytes(k))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn test_mvcc_key_cmp() {
+        let a = make_key(b"a");
+        let b = make_key(b"b");
+        let a_long = make_key(b"a\x00\x00\x00\x00\x00\x00\x00\x00");
+
+        // the same user key, newest first.
+        assert_eq!(mvcc_key_cmp(a.encode_ts(10).raw(), a.encode_ts(5).raw()),
+                   Ordering::Less);
+        assert_eq!(mvcc_key_cmp(a.encode_ts(5).raw(), a.encode_ts(10).raw()),
+                   Ordering::Greater);
+        assert_eq!(mvcc_key_cmp(a.encode_ts(5).raw(), a.encode_ts(5).raw()),
+                   Ordering::Equal);
+        // no timestamp is the newest.
+        assert_eq!(mvcc_key_cmp(a.raw(), a.encode_ts(u64::max_value() - 1).raw()),
+                   Ordering::Less);
+        assert_eq!(mvcc_key_cmp(a.raw(), a.encode_ts(u64::max_value()).raw()),
+                   Ordering::Equal);
+
+        // different user keys are in ascending order regardless of timestamps.
+        let cases = vec![
+            (a.encode_ts(1), b.encode_ts(10)),
+            (a.encode_ts(10), b.encode_ts(1)),
+            (a.encode_ts(1), a_long.encode_ts(10)),
+            (a.clone(), b.encode_ts(1)),
+            (a.encode_ts(1), b.clone()),
+        ];
+        for (l, r) in cases {
+            assert_eq!(mvcc_key_cmp(l.raw(), r.raw()), Ordering::Less);
+            assert_eq!(mvcc_key_cmp(r.raw(), l.raw()), Ordering::Greater);
+        }
+    }
+}
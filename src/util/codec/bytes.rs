@@ -17,7 +17,7 @@ use std::io::Write;
 use super::{Result, Error, number};
 use util::codec;
 
-const ENC_GROUP_SIZE: usize = 8;
+pub const ENC_GROUP_SIZE: usize = 8;
 const ENC_MARKER: u8 = b'\xff';
 const ENC_PADDING: [u8; ENC_GROUP_SIZE] = [0; ENC_GROUP_SIZE];
 
@@ -83,6 +83,32 @@ pub fn decode_bytes(data: &[u8]) -> Result<(Vec<u8>, usize)> {
     Err(Error::KeyLength)
 }
 
+/// `encoded_bytes_len` gets the length of the bytes encoded by `encode_bytes`
+/// at the beginning of `data` without decoding them.
+pub fn encoded_bytes_len(data: &[u8]) -> Result<usize> {
+    let mut read: usize = 0;
+    for chunk in data.chunks(ENC_GROUP_SIZE + 1) {
+        if chunk.len() != ENC_GROUP_SIZE + 1 {
+            return Err(Error::KeyLength);
+        }
+        read += ENC_GROUP_SIZE + 1;
+
+        let (marker, bytes) = chunk.split_last().unwrap();
+        let pad_size = (ENC_MARKER - *marker) as usize;
+        if pad_size == 0 {
+            continue;
+        }
+        if pad_size > ENC_GROUP_SIZE {
+            return Err(Error::KeyPadding);
+        }
+        if bytes[ENC_GROUP_SIZE - pad_size..].iter().any(|x| *x != 0) {
+            return Err(Error::KeyPadding);
+        }
+        return Ok(read);
+    }
+    Err(Error::KeyLength)
+}
+
 /// `encode_compact_bytes` joins bytes with its length into a byte slice. It is more
 /// efficient in both space and time compare to `encode_bytes`. Note that the encoded
 /// result is not memcomparable.
@@ -101,6 +127,14 @@ pub fn decode_compact_bytes(buf: &[u8]) -> Result<(Vec<u8>, usize)> {
     Ok((buf[vn..readed].to_vec(), readed))
 }
 
+/// `encoded_compact_bytes_len` gets the length of the bytes encoded by
+/// `encode_compact_bytes` at the beginning of `buf` without decoding them.
+pub fn encoded_compact_bytes_len(buf: &[u8]) -> Result<usize> {
+    let (v, vn) = try!(number::decode_var_i64(buf));
+    try!(codec::check_bound(&buf[vn..], v as usize));
+    Ok(vn + v as usize)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,6 +164,9 @@ mod tests {
             let (key, size) = decode_bytes(&y).unwrap();
             assert_eq!(key, x);
             assert_eq!(size, y.len());
+            let mut with_tail = y.clone();
+            with_tail.extend_from_slice(&[1, 2, 3]);
+            assert_eq!(encoded_bytes_len(&with_tail).unwrap(), y.len());
         }
     }
 
@@ -147,6 +184,7 @@ mod tests {
 
         for x in invalid_bytes {
             assert!(decode_bytes(&x).is_err());
+            assert!(encoded_bytes_len(&x).is_err());
         }
     }
 
@@ -211,6 +249,8 @@ mod tests {
             let (decoded, readed) = decode_compact_bytes(&buf).unwrap();
             assert_eq!(readed, written);
             assert_eq!(decoded, s.as_bytes());
+            assert_eq!(encoded_compact_bytes_len(&buf).unwrap(), written);
+            assert!(encoded_compact_bytes_len(&buf[..written - 1]).is_err());
         }
     }
 
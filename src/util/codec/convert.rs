@@ -14,29 +14,31 @@
 
 use std;
 
-use super::Result;
+use super::{Result, Error};
 
-/// `bytes_to_int` converts a byte arrays to an i64 in best effort.
-/// TODO: handle overflow.
+/// `bytes_to_int` converts the leading integer of a byte array to an i64 in
+/// best effort, an error is returned if it overflows.
 pub fn bytes_to_int(bytes: &[u8]) -> Result<i64> {
     // trim
-    let mut trimed = bytes.iter().skip_while(|&&b| b == b' ' || b == b'\t');
-    let mut negative = false;
+    let mut trimed = bytes.iter().skip_while(|&&b| b == b' ' || b == b'\t').peekable();
+    let negative = trimed.peek() == Some(&&b'-');
+    if negative || trimed.peek() == Some(&&b'+') {
+        trimed.next();
+    }
     let mut r = 0i64;
-    if let Some(&c) = trimed.next() {
-        if c == b'-' {
-            negative = true;
-        } else if c >= b'0' && c <= b'9' {
-            r = c as i64 - b'0' as i64;
-        } else if c != b'+' {
-            return Ok(0);
-        }
-
-        r = trimed.take_while(|&&c| c >= b'0' && c <= b'9')
-                  .fold(r, |l, &r| l * 10 + (r - b'0') as i64);
-        if negative {
-            r = -r;
-        }
+    for &c in trimed.take_while(|&&c| c >= b'0' && c <= b'9') {
+        let d = (c - b'0') as i64;
+        // accumulate negative numbers negatively so that i64::MIN fits.
+        let next = r.checked_mul(10).and_then(|r| {
+            if negative {
+                r.checked_sub(d)
+            } else {
+                r.checked_add(d)
+            }
+        });
+        r = try!(next.ok_or_else(|| {
+            Error::InvalidDataType(format!("{:?} overflows i64", String::from_utf8_lossy(bytes)))
+        }));
     }
     Ok(r)
 }
@@ -61,11 +63,61 @@ pub fn bytes_to_f64(bytes: &[u8]) -> Result<f64> {
     Ok(f)
 }
 
+/// `bytes_prefix_to_f64` converts the longest numeric prefix of a byte array
+/// to a float64 like MySQL does, 0 is returned if there is no such prefix.
+pub fn bytes_prefix_to_f64(bytes: &[u8]) -> Result<f64> {
+    let start = bytes.iter().take_while(|&&b| b == b' ' || b == b'\t').count();
+    let is_digit = |i: usize| i < bytes.len() && bytes[i] >= b'0' && bytes[i] <= b'9';
+    let mut end = start;
+    if end < bytes.len() && (bytes[end] == b'-' || bytes[end] == b'+') {
+        end += 1;
+    }
+    let mut has_digit = false;
+    while is_digit(end) {
+        end += 1;
+        has_digit = true;
+    }
+    if end < bytes.len() && bytes[end] == b'.' {
+        end += 1;
+        while is_digit(end) {
+            end += 1;
+            has_digit = true;
+        }
+    }
+    if !has_digit {
+        return Ok(0.0);
+    }
+    // the exponent is only valid when followed by digits.
+    if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+        let mut exp_end = end + 1;
+        if exp_end < bytes.len() && (bytes[exp_end] == b'-' || bytes[exp_end] == b'+') {
+            exp_end += 1;
+        }
+        if is_digit(exp_end) {
+            while is_digit(exp_end) {
+                exp_end += 1;
+            }
+            end = exp_end;
+        }
+    }
+    // the prefix only contains ascii, so it's always valid utf8.
+    let s = std::str::from_utf8(&bytes[start..end]).unwrap();
+    let f = match s.parse::<f64>() {
+        Ok(f) => f,
+        Err(e) => {
+            error!("failed to parse float from {}: {}", s, e);
+            0.0
+        }
+    };
+    Ok(f)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     use std::f64::EPSILON;
+    use std::i64;
 
     #[test]
     fn test_bytes_to_i64() {
@@ -82,6 +134,9 @@ mod test {
             (b"+1024", 1024),
             (b"-231", -231),
             (b"", 0),
+            (b"-", 0),
+            (b"9223372036854775807", i64::MAX),
+            (b"-9223372036854775808", i64::MIN),
         ];
         for (bs, n) in tests {
             let t = bytes_to_int(bs).unwrap();
@@ -89,6 +144,9 @@ mod test {
                 panic!("expect convert {:?} to {}, but got {}", bs, n, t);
             }
         }
+
+        assert!(bytes_to_int(b"9223372036854775808").is_err());
+        assert!(bytes_to_int(b"-9223372036854775809").is_err());
     }
 
     #[test]
@@ -112,4 +170,34 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_bytes_prefix_to_f64() {
+        let tests: Vec<(&'static [u8], f64)> = vec![
+            (b"", 0.0),
+            (b" 23", 23.0),
+            (b"\t-1", -1.0),
+            (b"1.11", 1.11),
+            (b"1.11.00", 1.11),
+            (b"11.xx", 11.0),
+            (b".5a", 0.5),
+            (b"-.5", -0.5),
+            (b"1e3x", 1000.0),
+            (b"1.5E-1", 0.15),
+            (b"2e", 2.0),
+            (b"2e+", 2.0),
+            (b"xx", 0.0),
+            (b"xx.11", 0.0),
+            (b"-", 0.0),
+            (b".", 0.0),
+            (b"0x10", 0.0),
+        ];
+
+        for (v, f) in tests {
+            let ff = bytes_prefix_to_f64(v).unwrap();
+            if (ff - f).abs() > EPSILON {
+                panic!("{:?} should be decode to {}, but got {}", v, f, ff);
+            }
+        }
+    }
 }
@@ -0,0 +1,464 @@
+// Copyright 2016 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::fmt::{self, Display, Formatter};
+use std::str;
+
+use super::{Result, Error};
+
+const NANOS_PER_MICRO: i64 = 1_000;
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+const MICROS_PER_SEC: i64 = 1_000_000;
+const SECS_PER_DAY: i64 = 86_400;
+/// The max hours of a `Duration`, same as MySQL.
+pub const MAX_DURATION_HOUR: i64 = 838;
+/// The max nanoseconds of a `Duration`, which is `838:59:59.999999`.
+pub const MAX_DURATION_NANOS: i64 = ((MAX_DURATION_HOUR * 60 + 59) * 60 + 59) * NANOS_PER_SEC +
+                                    999_999 * NANOS_PER_MICRO;
+
+fn invalid(kind: &str, s: &[u8]) -> Error {
+    Error::InvalidDataType(format!("invalid {} {:?}", kind, String::from_utf8_lossy(s)))
+}
+
+fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Get the days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// The inverse of `days_from_civil`, returns (year, month, day).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// parse a number with at most `max_len` digits.
+fn parse_num(s: &str, max_len: usize) -> Option<u32> {
+    if s.is_empty() || s.len() > max_len || !s.bytes().all(|b| b >= b'0' && b <= b'9') {
+        return None;
+    }
+    s.parse().ok()
+}
+
+// parse fraction digits into microseconds, extra digits are truncated.
+fn parse_micro(s: &str) -> Option<u32> {
+    // check every byte is a digit before slicing, so the cut is always on a
+    // char boundary.
+    if !s.bytes().all(|b| b >= b'0' && b <= b'9') {
+        return None;
+    }
+    let s = if s.len() > 6 { &s[..6] } else { s };
+    parse_num(s, 6).map(|v| v * 10u32.pow(6 - s.len() as u32))
+}
+
+// parse `HH:MM:SS[.ffffff]` into (hour, minute, second, micro).
+fn parse_hms(s: &str, max_hour: u32) -> Option<(u32, u32, u32, u32)> {
+    let (s, micro) = match s.find('.') {
+        Some(idx) => {
+            match parse_micro(&s[idx + 1..]) {
+                Some(micro) => (&s[..idx], micro),
+                None => return None,
+            }
+        }
+        None => (s, 0),
+    };
+    let parts: Vec<_> = s.split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    match (parse_num(parts[0], 3), parse_num(parts[1], 2), parse_num(parts[2], 2)) {
+        (Some(h), Some(m), Some(s)) if h <= max_hour && m < 60 && s < 60 => Some((h, m, s, micro)),
+        _ => None,
+    }
+}
+
+// Division rounding towards negative infinity.
+fn div_floor(a: i64, b: i64) -> i64 {
+    let q = a / b;
+    if a % b < 0 { q - 1 } else { q }
+}
+
+/// `Time` is a MySQL `DATETIME` with microsecond precision.
+///
+/// Fields are declared from the most significant one so that the derived
+/// ordering is chronological.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    micro: u32,
+}
+
+impl Time {
+    pub fn new(year: u16,
+               month: u8,
+               day: u8,
+               hour: u8,
+               minute: u8,
+               second: u8,
+               micro: u32)
+               -> Result<Time> {
+        if year > 9999 || month < 1 || month > 12 || day < 1 ||
+           day > days_in_month(year, month) || hour > 23 || minute > 59 ||
+           second > 59 || micro > 999_999 {
+            return Err(Error::InvalidDataType(format!("invalid datetime {}-{}-{} {}:{}:{}.{}",
+                                                      year,
+                                                      month,
+                                                      day,
+                                                      hour,
+                                                      minute,
+                                                      second,
+                                                      micro)));
+        }
+        Ok(Time {
+            year: year,
+            month: month,
+            day: day,
+            hour: hour,
+            minute: minute,
+            second: second,
+            micro: micro,
+        })
+    }
+
+    /// `parse` parses a time like `2016-07-01` or `2016-07-01 12:30:00.5`.
+    pub fn parse(bs: &[u8]) -> Result<Time> {
+        let s = try!(str::from_utf8(bs)).trim();
+        let (date, time) = match s.find(|c: char| c == ' ' || c == 'T') {
+            Some(idx) => (&s[..idx], Some(&s[idx + 1..])),
+            None => (s, None),
+        };
+        let parts: Vec<_> = date.split('-').collect();
+        if parts.len() != 3 {
+            return Err(invalid("datetime", bs));
+        }
+        let (year, month, day) =
+            match (parse_num(parts[0], 4), parse_num(parts[1], 2), parse_num(parts[2], 2)) {
+                (Some(y), Some(m), Some(d)) => (y, m, d),
+                _ => return Err(invalid("datetime", bs)),
+            };
+        let (hour, minute, second, micro) = match time {
+            Some(time) => try!(parse_hms(time, 23).ok_or_else(|| invalid("datetime", bs))),
+            None => (0, 0, 0, 0),
+        };
+        Time::new(year as u16,
+                  month as u8,
+                  day as u8,
+                  hour as u8,
+                  minute as u8,
+                  second as u8,
+                  micro)
+    }
+
+    /// `from_packed_u64` decodes a time packed by `to_packed_u64`.
+    pub fn from_packed_u64(packed: u64) -> Result<Time> {
+        let micro = packed & ((1 << 24) - 1);
+        let ymdhms = packed >> 24;
+        let ymd = ymdhms >> 17;
+        let ym = ymd >> 5;
+        let hms = ymdhms & ((1 << 17) - 1);
+        if ym / 13 > 9999 {
+            return Err(Error::InvalidDataType(format!("invalid packed datetime {}", packed)));
+        }
+        Time::new((ym / 13) as u16,
+                  (ym % 13) as u8,
+                  (ymd & 31) as u8,
+                  (hms >> 12) as u8,
+                  ((hms >> 6) & 63) as u8,
+                  (hms & 63) as u8,
+                  micro as u32)
+    }
+
+    /// `to_packed_u64` packs the time into an u64 the same way as MySQL does,
+    /// the packed value keeps the chronological order.
+    pub fn to_packed_u64(&self) -> u64 {
+        let ymd = ((self.year as u64 * 13 + self.month as u64) << 5) | self.day as u64;
+        let hms = ((self.hour as u64) << 12) | ((self.minute as u64) << 6) | self.second as u64;
+        (((ymd << 17) | hms) << 24) | self.micro as u64
+    }
+
+    // Get the microseconds since 1970-01-01 00:00:00.
+    fn to_micros(&self) -> i64 {
+        let days = days_from_civil(self.year as i64, self.month as i64, self.day as i64);
+        let secs = days * SECS_PER_DAY + self.hour as i64 * 3600 + self.minute as i64 * 60 +
+                   self.second as i64;
+        secs * MICROS_PER_SEC + self.micro as i64
+    }
+
+    // The inverse of `to_micros`, None is returned if the time is out of range.
+    fn from_micros(micros: i64) -> Option<Time> {
+        let secs = div_floor(micros, MICROS_PER_SEC);
+        let micro = micros - secs * MICROS_PER_SEC;
+        let days = div_floor(secs, SECS_PER_DAY);
+        let secs = secs - days * SECS_PER_DAY;
+        let (year, month, day) = civil_from_days(days);
+        if year < 0 || year > 9999 {
+            return None;
+        }
+        Time::new(year as u16,
+                  month as u8,
+                  day as u8,
+                  (secs / 3600) as u8,
+                  (secs / 60 % 60) as u8,
+                  (secs % 60) as u8,
+                  micro as u32)
+            .ok()
+    }
+
+    /// `checked_add` shifts the time by the duration, None is returned if the
+    /// result is out of range. Nanoseconds beyond microseconds are truncated.
+    pub fn checked_add(&self, d: &Duration) -> Option<Time> {
+        Time::from_micros(self.to_micros() + d.to_nanos() / NANOS_PER_MICRO)
+    }
+
+    /// `checked_sub` shifts the time back by the duration, see `checked_add`.
+    pub fn checked_sub(&self, d: &Duration) -> Option<Time> {
+        Time::from_micros(self.to_micros() - d.to_nanos() / NANOS_PER_MICRO)
+    }
+
+    /// `checked_sub_time` gets the duration from `other` to self, None is
+    /// returned if it's out of the range of `Duration`.
+    pub fn checked_sub_time(&self, other: &Time) -> Option<Duration> {
+        let micros = self.to_micros() - other.to_micros();
+        micros.checked_mul(NANOS_PER_MICRO).and_then(|nanos| Duration::from_nanos(nanos).ok())
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+
+    pub fn micro(&self) -> u32 {
+        self.micro
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        try!(write!(f,
+                    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                    self.year,
+                    self.month,
+                    self.day,
+                    self.hour,
+                    self.minute,
+                    self.second));
+        if self.micro > 0 {
+            try!(write!(f, ".{:06}", self.micro));
+        }
+        Ok(())
+    }
+}
+
+/// `Duration` is a MySQL `TIME`, which may be negative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration {
+    nanos: i64,
+}
+
+impl Duration {
+    pub fn from_nanos(nanos: i64) -> Result<Duration> {
+        if nanos > MAX_DURATION_NANOS || nanos < -MAX_DURATION_NANOS {
+            return Err(Error::InvalidDataType(format!("duration {}ns overflows", nanos)));
+        }
+        Ok(Duration { nanos: nanos })
+    }
+
+    /// `parse` parses a duration like `12:30:00` or `-838:59:59.999999`.
+    pub fn parse(bs: &[u8]) -> Result<Duration> {
+        let s = try!(str::from_utf8(bs)).trim();
+        let (negative, s) = if s.starts_with('-') {
+            (true, &s[1..])
+        } else {
+            (false, s)
+        };
+        let (hour, minute, second, micro) =
+            try!(parse_hms(s, MAX_DURATION_HOUR as u32).ok_or_else(|| invalid("duration", bs)));
+        let secs = (hour as i64 * 60 + minute as i64) * 60 + second as i64;
+        let nanos = secs * NANOS_PER_SEC + micro as i64 * NANOS_PER_MICRO;
+        Duration::from_nanos(if negative { -nanos } else { nanos })
+    }
+
+    pub fn to_nanos(&self) -> i64 {
+        self.nanos
+    }
+}
+
+impl Display for Duration {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.nanos < 0 {
+            try!(write!(f, "-"));
+        }
+        let nanos = self.nanos.abs();
+        let secs = nanos / NANOS_PER_SEC;
+        try!(write!(f, "{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60));
+        let micro = nanos % NANOS_PER_SEC / NANOS_PER_MICRO;
+        if micro > 0 {
+            try!(write!(f, ".{:06}", micro));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_time_parse() {
+        let tests = vec![
+            ("2016-07-01", "2016-07-01 00:00:00"),
+            ("2016-07-01 12:30:05", "2016-07-01 12:30:05"),
+            ("2016-7-1T1:2:3", "2016-07-01 01:02:03"),
+            ("2016-02-29 23:59:59.5", "2016-02-29 23:59:59.500000"),
+            ("0001-01-01 00:00:00.1234567", "0001-01-01 00:00:00.123456"),
+        ];
+        for (s, expect) in tests {
+            let t = Time::parse(s.as_bytes()).unwrap();
+            assert_eq!(format!("{}", t), expect);
+            assert_eq!(Time::from_packed_u64(t.to_packed_u64()).unwrap(), t);
+        }
+
+        let bad = vec!["",
+                       "2016",
+                       "2016-13-01",
+                       "2015-02-29",
+                       "2016-07-01 24:00:00",
+                       "2016-07-01 12:60:00",
+                       "2016-07-01 12:00",
+                       "abcd-ef-gh",
+                       "2016-07-01 1:2:3.",
+                       // a multibyte char must not be split by the truncation.
+                       "2016-07-01 00:00:00.12345é",
+                       "2016-07-01 00:00:00.1234567é"];
+        for s in bad {
+            assert!(Time::parse(s.as_bytes()).is_err(), "{} should fail", s);
+        }
+    }
+
+    #[test]
+    fn test_time_order() {
+        let tests = vec!["1999-12-31 23:59:59.999999",
+                         "2000-01-01",
+                         "2000-01-01 00:00:00.000001",
+                         "2000-01-01 00:00:01",
+                         "2000-01-02",
+                         "2000-02-01",
+                         "2001-01-01"];
+        for w in tests.windows(2) {
+            let l = Time::parse(w[0].as_bytes()).unwrap();
+            let r = Time::parse(w[1].as_bytes()).unwrap();
+            assert!(l < r, "{} < {}", l, r);
+            assert!(l.to_packed_u64() < r.to_packed_u64(), "{} < {}", l, r);
+        }
+    }
+
+    #[test]
+    fn test_time_arith() {
+        let t = |s: &str| Time::parse(s.as_bytes()).unwrap();
+        let d = |s: &str| Duration::parse(s.as_bytes()).unwrap();
+        let tests = vec![
+            ("2016-07-01 12:00:00", "01:30:00", "2016-07-01 13:30:00"),
+            ("2016-07-01 23:00:00", "01:00:00.5", "2016-07-02 00:00:00.5"),
+            ("2016-12-31 23:59:59.999999", "00:00:00.000001", "2017-01-01"),
+            ("2016-02-28 12:00:00", "24:00:00", "2016-02-29 12:00:00"),
+            ("2015-02-28 12:00:00", "24:00:00", "2015-03-01 12:00:00"),
+            ("2000-02-28", "838:59:59", "2000-04-02 22:59:59"),
+            ("1969-12-31 23:00:00", "02:00:00", "1970-01-01 01:00:00"),
+            ("0000-01-01", "-00:00:00", "0000-01-01"),
+        ];
+        for (start, delta, end) in tests {
+            assert_eq!(t(start).checked_add(&d(delta)).unwrap(), t(end));
+            assert_eq!(t(end).checked_sub(&d(delta)).unwrap(), t(start));
+            assert_eq!(t(end).checked_sub_time(&t(start)).unwrap(), d(delta));
+            let neg = Duration::from_nanos(-d(delta).to_nanos()).unwrap();
+            assert_eq!(t(start).checked_sub_time(&t(end)).unwrap(), neg);
+            assert_eq!(t(end).checked_add(&neg).unwrap(), t(start));
+        }
+
+        assert!(t("9999-12-31 23:00:00").checked_add(&d("01:00:00")).is_none());
+        assert!(t("0000-01-01 00:30:00").checked_sub(&d("01:00:00")).is_none());
+        assert!(t("2016-07-01").checked_sub_time(&t("2016-01-01")).is_none());
+        assert!(t("9999-12-31").checked_sub_time(&t("0000-01-01")).is_none());
+    }
+
+    #[test]
+    fn test_duration() {
+        let tests = vec![
+            ("00:00:00", 0, "00:00:00"),
+            ("12:30:05", 45005 * 1_000_000_000, "12:30:05"),
+            ("-1:00:00.5", -3600_500_000_000, "-01:00:00.500000"),
+            ("838:59:59.999999", MAX_DURATION_NANOS, "838:59:59.999999"),
+        ];
+        for (s, nanos, expect) in tests {
+            let d = Duration::parse(s.as_bytes()).unwrap();
+            assert_eq!(d.to_nanos(), nanos);
+            assert_eq!(format!("{}", d), expect);
+        }
+        assert!(Duration::parse(b"839:00:00").is_err());
+        assert!(Duration::parse(b"1:2").is_err());
+        assert!(Duration::parse("00:00:00.12345é".as_bytes()).is_err());
+        assert!(Duration::from_nanos(MAX_DURATION_NANOS + 1).is_err());
+        assert!(Duration::parse(b"-01:00:00").unwrap() < Duration::parse(b"00:59:59").unwrap());
+    }
+}
@@ -13,22 +13,37 @@
 
 
 use std::cmp::Ordering;
-use std::i64;
+use std::hash::{Hash, Hasher};
+use std::{f64, i64, mem, str};
+
+use rustc_serialize::base64::{self, FromBase64, ToBase64};
+use rustc_serialize::json::{Json, Object};
 
 use util::codec;
-use super::{number, Result, Error, bytes, convert};
+use super::{number, Result, Error, bytes, convert, Decimal, Time, Duration};
 
 const NIL_FLAG: u8 = 0;
 const BYTES_FLAG: u8 = 1;
 const COMPACT_BYTES_FLAG: u8 = 2;
 const INT_FLAG: u8 = 3;
 const UINT_FLAG: u8 = 4;
-// TODO: support following flag
-// const FLOAT_FLAG: u8 = 5;
-// const DECIMAL_FLAG: u8 = 6;
-// const DURATION_FLAG: u8 = 7;
+const FLOAT_FLAG: u8 = 5;
+// TiDB encodes decimals with flag 6 in a format that isn't supported yet,
+// so decimals are encoded as the count of fraction digits followed by the
+// unscaled i64 value under a flag TiDB doesn't use.
+const FIXED_DECIMAL_FLAG: u8 = 128;
+const DURATION_FLAG: u8 = 7;
+const JSON_FLAG: u8 = 10;
 const MAX_FLAG: u8 = 250;
 
+// The key of the json object holding non-utf8 bytes.
+const JSON_BASE64_KEY: &'static str = "base64";
+
+/// `Datum` is the value of a column.
+///
+/// Note that `PartialEq`, `Eq` and `Hash` test whether two datums are the
+/// same value of the same type rather than SQL equality, so `Null` equals
+/// `Null` and `I64(1)` doesn't equal `U64(1)`. Use `cmp` for SQL semantics.
 #[derive(PartialEq, Debug, Clone)]
 pub enum Datum {
     Null,
@@ -37,6 +52,10 @@ pub enum Datum {
     F32(f32),
     F64(f64),
     Bytes(Vec<u8>),
+    Decimal(Decimal),
+    Time(Time),
+    Duration(Duration),
+    Json(Json),
     Min,
     Max,
 }
@@ -46,6 +65,122 @@ fn cmp_f64(l: f64, r: f64) -> Result<Ordering> {
      .ok_or_else(|| Error::InvalidDataType(format!("{} and {} can't be compared", l, r)))
 }
 
+// Integers are compared with floats exactly rather than being converted to
+// floats, which loses precision and makes `cmp` intransitive, for example
+// `i64::MAX` would be equal to both `2^63` and `i64::MAX - 1`.
+fn cmp_i64_f64(i: i64, f: f64) -> Result<Ordering> {
+    // 2^63, which is exactly representable.
+    const I64_BOUND: f64 = 9223372036854775808f64;
+    if f.is_nan() {
+        return cmp_f64(i as f64, f);
+    }
+    if f >= I64_BOUND {
+        return Ok(Ordering::Less);
+    }
+    if f < -I64_BOUND {
+        return Ok(Ordering::Greater);
+    }
+    let t = f.trunc();
+    match i.cmp(&(t as i64)) {
+        Ordering::Equal => cmp_f64(t, f),
+        ord => Ok(ord),
+    }
+}
+
+fn cmp_u64_f64(u: u64, f: f64) -> Result<Ordering> {
+    // 2^64, which is exactly representable.
+    const U64_BOUND: f64 = 18446744073709551616f64;
+    if f.is_nan() {
+        return cmp_f64(u as f64, f);
+    }
+    if f >= U64_BOUND {
+        return Ok(Ordering::Less);
+    }
+    if f < 0f64 {
+        return Ok(Ordering::Greater);
+    }
+    let t = f.trunc();
+    match u.cmp(&(t as u64)) {
+        Ordering::Equal => cmp_f64(t, f),
+        ord => Ok(ord),
+    }
+}
+
+// Decimals are compared with floats exactly for the same reason as `cmp_i64_f64`,
+// the integral parts first and then the fractions.
+fn cmp_dec_f64(d: &Decimal, f: f64) -> Result<Ordering> {
+    let (int_part, frac_part) = d.split();
+    match try!(cmp_i64_f64(int_part, f.trunc())) {
+        Ordering::Equal => Ok(cmp_frac_f64(frac_part, d.frac(), f.fract())),
+        ord => Ok(ord),
+    }
+}
+
+// Compare `frac / 10^digits` with the float `f` exactly, both are in (-1, 1).
+fn cmp_frac_f64(frac: i64, digits: u8, f: f64) -> Ordering {
+    let f_sign = if f > 0f64 {
+        1
+    } else if f < 0f64 {
+        -1
+    } else {
+        0
+    };
+    match frac.signum().cmp(&f_sign) {
+        Ordering::Equal if frac != 0 => {}
+        ord => return ord,
+    }
+    let ord = cmp_abs_frac_f64(frac.abs() as u64, digits, f.abs());
+    if frac < 0 { ord.reverse() } else { ord }
+}
+
+// Compare `frac / 10^digits` with the float `f`, both are in (0, 1).
+//
+// `f` is exactly `mant / 2^shift`, so `f * 10^digits` is
+// `mant * 5^digits / 2^(shift - digits)`, whose integral part is compared
+// with `frac` and whose remainder breaks the tie.
+fn cmp_abs_frac_f64(frac: u64, digits: u8, f: f64) -> Ordering {
+    let bits: u64 = unsafe { mem::transmute(f) };
+    let exp = ((bits >> 52) & 0x7ff) as i64;
+    let mut mant = bits & ((1 << 52) - 1);
+    let shift = if exp == 0 {
+        1074
+    } else {
+        mant |= 1 << 52;
+        1075 - exp
+    };
+    let (hi, lo) = mul_u64(mant, 5u64.pow(digits as u32));
+    let shift = shift - digits as i64;
+    if shift <= 0 {
+        // the product is an integer less than 10^digits, so `hi` is 0.
+        return frac.cmp(&(lo << -shift));
+    }
+    let shift = shift as u32;
+    let (int_part, has_rem) = if shift >= 128 {
+        (0, true)
+    } else if shift >= 64 {
+        (hi >> (shift - 64), lo != 0 || hi & ((1 << (shift - 64)) - 1) != 0)
+    } else {
+        ((lo >> shift) | (hi << (64 - shift)), lo & ((1 << shift) - 1) != 0)
+    };
+    match frac.cmp(&int_part) {
+        Ordering::Equal if has_rem => Ordering::Less,
+        ord => ord,
+    }
+}
+
+// Multiply two u64 into a 128 bits product, which is returned as (high, low).
+fn mul_u64(a: u64, b: u64) -> (u64, u64) {
+    const MASK: u64 = 0xffffffff;
+    let (a_hi, a_lo) = (a >> 32, a & MASK);
+    let (b_hi, b_lo) = (b >> 32, b & MASK);
+    let lo_lo = a_lo * b_lo;
+    let hi_lo = a_hi * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_hi = a_hi * b_hi;
+    let mid = (lo_lo >> 32) + (hi_lo & MASK) + lo_hi;
+    (hi_hi + (hi_lo >> 32) + (mid >> 32), (mid << 32) | (lo_lo & MASK))
+}
+
 #[allow(should_implement_trait)]
 impl Datum {
     pub fn cmp(&self, datum: &Datum) -> Result<Ordering> {
@@ -74,6 +209,10 @@ impl Datum {
             Datum::F32(f) => self.cmp_f64(f as f64),
             Datum::F64(f) => self.cmp_f64(f),
             Datum::Bytes(ref bs) => self.cmp_bytes(bs),
+            Datum::Decimal(ref d) => self.cmp_dec(d),
+            Datum::Time(ref t) => self.cmp_time(t),
+            Datum::Duration(ref d) => self.cmp_duration(d),
+            Datum::Json(ref j) => self.cmp_json(j),
         }
     }
 
@@ -87,6 +226,13 @@ impl Datum {
                     Ok(u.cmp(&(i as u64)))
                 }
             }
+            Datum::Decimal(ref d) => Ok(d.cmp(&Decimal::from_i64(i))),
+            Datum::Time(_) | Datum::Duration(_) => {
+                let ord = try!(Datum::I64(i).cmp(self));
+                Ok(ord.reverse())
+            }
+            Datum::F32(f) => cmp_i64_f64(i, f as f64).map(Ordering::reverse),
+            Datum::F64(f) => cmp_i64_f64(i, f).map(Ordering::reverse),
             _ => self.cmp_f64(i as f64),
         }
     }
@@ -101,6 +247,19 @@ impl Datum {
                 }
             }
             Datum::U64(uu) => Ok(uu.cmp(&u)),
+            Datum::Decimal(ref d) => {
+                match Decimal::from_u64(u) {
+                    Ok(dd) => Ok(d.cmp(&dd)),
+                    // u is larger than any decimal.
+                    Err(_) => Ok(Ordering::Less),
+                }
+            }
+            Datum::Time(_) | Datum::Duration(_) => {
+                let ord = try!(Datum::U64(u).cmp(self));
+                Ok(ord.reverse())
+            }
+            Datum::F32(f) => cmp_u64_f64(u, f as f64).map(Ordering::reverse),
+            Datum::F64(f) => cmp_u64_f64(u, f).map(Ordering::reverse),
             _ => self.cmp_f64(u as f64),
         }
     }
@@ -109,14 +268,19 @@ impl Datum {
         match *self {
             Datum::Null | Datum::Min => Ok(Ordering::Less),
             Datum::Max => Ok(Ordering::Greater),
-            Datum::I64(i) => cmp_f64(i as f64, f),
-            Datum::U64(u) => cmp_f64(u as f64, f),
+            Datum::I64(i) => cmp_i64_f64(i, f),
+            Datum::U64(u) => cmp_u64_f64(u, f),
             Datum::F32(ff) => cmp_f64(ff as f64, f),
             Datum::F64(ff) => cmp_f64(ff, f),
             Datum::Bytes(ref bs) => {
                 let ff = try!(convert::bytes_to_f64(bs));
                 cmp_f64(ff, f)
             }
+            Datum::Decimal(ref d) => cmp_dec_f64(d, f),
+            Datum::Time(_) | Datum::Duration(_) | Datum::Json(_) => {
+                let ord = try!(Datum::F64(f).cmp(self));
+                Ok(ord.reverse())
+            }
         }
     }
 
@@ -125,6 +289,16 @@ impl Datum {
             Datum::Null | Datum::Min => Ok(Ordering::Less),
             Datum::Max => Ok(Ordering::Greater),
             Datum::Bytes(ref bss) => Ok((bss as &[u8]).cmp(bs)),
+            Datum::Decimal(ref d) => {
+                match Decimal::from_bytes(bs) {
+                    Ok(dd) => Ok(d.cmp(&dd)),
+                    Err(_) => cmp_f64(d.to_f64(), try!(convert::bytes_to_f64(bs))),
+                }
+            }
+            Datum::Time(_) | Datum::Duration(_) => {
+                let ord = try!(Datum::Bytes(bs.to_vec()).cmp(self));
+                Ok(ord.reverse())
+            }
             _ => {
                 let f = try!(convert::bytes_to_f64(bs));
                 self.cmp_f64(f)
@@ -132,15 +306,94 @@ impl Datum {
         }
     }
 
+    fn cmp_dec(&self, d: &Decimal) -> Result<Ordering> {
+        match *self {
+            Datum::Null | Datum::Min => Ok(Ordering::Less),
+            Datum::Max => Ok(Ordering::Greater),
+            Datum::Decimal(ref dd) => Ok(dd.cmp(d)),
+            Datum::F32(f) => cmp_dec_f64(d, f as f64).map(Ordering::reverse),
+            Datum::F64(f) => cmp_dec_f64(d, f).map(Ordering::reverse),
+            // comparing with other types is symmetric.
+            Datum::I64(_) | Datum::U64(_) | Datum::Bytes(_) | Datum::Time(_) |
+            Datum::Duration(_) | Datum::Json(_) => {
+                let ord = try!(Datum::Decimal(*d).cmp(self));
+                Ok(ord.reverse())
+            }
+        }
+    }
+
+    fn cmp_time(&self, t: &Time) -> Result<Ordering> {
+        match *self {
+            Datum::Null | Datum::Min => Ok(Ordering::Less),
+            Datum::Max => Ok(Ordering::Greater),
+            Datum::Time(ref tt) => Ok(tt.cmp(t)),
+            // time is stored as a packed u64.
+            Datum::U64(u) => Ok(try!(Time::from_packed_u64(u)).cmp(t)),
+            Datum::Bytes(ref bs) => Ok(try!(Time::parse(bs)).cmp(t)),
+            _ => Err(Error::InvalidDataType(format!("can't compare {} with time", self.kind()))),
+        }
+    }
+
+    fn cmp_duration(&self, d: &Duration) -> Result<Ordering> {
+        match *self {
+            Datum::Null | Datum::Min => Ok(Ordering::Less),
+            Datum::Max => Ok(Ordering::Greater),
+            Datum::Duration(ref dd) => Ok(dd.cmp(d)),
+            Datum::Bytes(ref bs) => Ok(try!(Duration::parse(bs)).cmp(d)),
+            _ => {
+                Err(Error::InvalidDataType(format!("can't compare {} with duration", self.kind())))
+            }
+        }
+    }
+
+    /// Json can only be compared with json, two json values are compared by
+    /// their text forms unless they are equal.
+    fn cmp_json(&self, j: &Json) -> Result<Ordering> {
+        match *self {
+            Datum::Null | Datum::Min => Ok(Ordering::Less),
+            Datum::Max => Ok(Ordering::Greater),
+            Datum::Json(ref jj) if jj == j => Ok(Ordering::Equal),
+            Datum::Json(ref jj) => Ok(jj.to_string().cmp(&j.to_string())),
+            _ => Err(Error::InvalidDataType(format!("can't compare {} with json", self.kind()))),
+        }
+    }
+
+    /// `kind` returns the name of the datum type for diagnostics.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Datum::Null => "null",
+            Datum::I64(_) => "int",
+            Datum::U64(_) => "uint",
+            Datum::F32(_) | Datum::F64(_) => "float",
+            Datum::Bytes(_) => "bytes",
+            Datum::Decimal(_) => "decimal",
+            Datum::Time(_) => "time",
+            Datum::Duration(_) => "duration",
+            Datum::Json(_) => "json",
+            Datum::Min => "min",
+            Datum::Max => "max",
+        }
+    }
+
     // into_bool converts self to a bool.
     pub fn into_bool(self) -> Result<bool> {
         let b = match self {
             Datum::I64(i) => i != 0,
             Datum::U64(u) => u != 0,
-            Datum::F32(f) => f.round() != 0f32,
-            Datum::F64(f) => f.round() != 0f64,
-            Datum::Bytes(ref bs) => !bs.is_empty() && try!(convert::bytes_to_int(bs)) != 0,
-            _ => return Err(Error::InvalidDataType(format!("can't convert {:?} to bool", self))),
+            // any nonzero float is true, and -0.0 == 0.0 is false.
+            Datum::F32(f) => f != 0f32,
+            Datum::F64(f) => f != 0f64,
+            // like MySQL, bytes are converted from their numeric prefix, so "abc"
+            // is false while "1x" and "0.5" are true.
+            Datum::Bytes(ref bs) => try!(convert::bytes_prefix_to_f64(bs)) != 0f64,
+            Datum::Decimal(ref d) => !d.is_zero(),
+            Datum::Time(_) => true,
+            Datum::Duration(ref d) => d.to_nanos() != 0,
+            _ => {
+                return Err(Error::InvalidDataType(format!("can't convert {} {:?} to bool",
+                                                          self.kind(),
+                                                          self)))
+            }
         };
         Ok(b)
     }
@@ -153,10 +406,253 @@ impl Datum {
             Datum::F64(f) => format!("{}", f),
             Datum::F32(f) => format!("{}", f),
             Datum::Bytes(bs) => try!(String::from_utf8(bs)),
-            d => return Err(Error::InvalidDataType(format!("can't convert {:?} to string", d))),
+            Datum::Decimal(d) => format!("{}", d),
+            Datum::Time(t) => format!("{}", t),
+            Datum::Duration(d) => format!("{}", d),
+            Datum::Json(j) => j.to_string(),
+            d => {
+                return Err(Error::InvalidDataType(format!("can't convert {} {:?} to string",
+                                                          d.kind(),
+                                                          d)))
+            }
         };
         Ok(s)
     }
+
+    /// `into_f64` converts self into a f64, bytes are converted from their
+    /// longest numeric prefix just like MySQL does.
+    pub fn into_f64(self) -> Result<f64> {
+        match self {
+            Datum::I64(i) => Ok(i as f64),
+            Datum::U64(u) => Ok(u as f64),
+            Datum::F32(f) => Ok(f as f64),
+            Datum::F64(f) => Ok(f),
+            Datum::Decimal(d) => Ok(d.to_f64()),
+            Datum::Bytes(ref bs) => convert::bytes_prefix_to_f64(bs),
+            d => {
+                Err(Error::InvalidDataType(format!("can't convert {} {:?} to float", d.kind(), d)))
+            }
+        }
+    }
+
+    /// `into_i64` converts self into an i64, floats and decimals are truncated
+    /// toward zero and bytes are converted from their leading integer.
+    pub fn into_i64(self) -> Result<i64> {
+        match self {
+            Datum::I64(i) => Ok(i),
+            Datum::U64(u) => {
+                if u > i64::MAX as u64 {
+                    return Err(Error::InvalidDataType(format!("{} overflows i64", u)));
+                }
+                Ok(u as i64)
+            }
+            Datum::F32(f) => Datum::F64(f as f64).into_i64(),
+            Datum::F64(f) => {
+                let f = f.trunc();
+                // i64::MAX as f64 is rounded up to 2^63.
+                if f.is_nan() || f < i64::MIN as f64 || f >= i64::MAX as f64 {
+                    return Err(Error::InvalidDataType(format!("{} overflows i64", f)));
+                }
+                Ok(f as i64)
+            }
+            Datum::Decimal(d) => {
+                let mut v = d.value();
+                for _ in 0..d.frac() {
+                    v /= 10;
+                }
+                Ok(v)
+            }
+            Datum::Bytes(ref bs) => convert::bytes_to_int(bs),
+            d => {
+                Err(Error::InvalidDataType(format!("can't convert {} {:?} to int", d.kind(), d)))
+            }
+        }
+    }
+
+    /// `coerce_numeric_pair` converts self and other into the same numeric type
+    /// following the rules of TiDB: float dominates, then decimal, and a pair of
+    /// integers is unsigned if any of them is unsigned. Bytes are treated as float.
+    ///
+    /// Like MySQL, mixing a negative integer with an unsigned one still results
+    /// in unsigned, but the negative one can't be converted, so such a pair is
+    /// returned as is and the caller should take care of the sign.
+    pub fn coerce_numeric_pair(self, other: Datum) -> Result<(Datum, Datum)> {
+        let is_float = |d: &Datum| {
+            match *d {
+                Datum::F32(_) | Datum::F64(_) | Datum::Bytes(_) => true,
+                _ => false,
+            }
+        };
+        if is_float(&self) || is_float(&other) {
+            let l = try!(self.into_f64());
+            let r = try!(other.into_f64());
+            return Ok((Datum::F64(l), Datum::F64(r)));
+        }
+        match (self, other) {
+            (Datum::I64(l), Datum::I64(r)) => Ok((Datum::I64(l), Datum::I64(r))),
+            (Datum::U64(l), Datum::U64(r)) => Ok((Datum::U64(l), Datum::U64(r))),
+            (Datum::I64(i), Datum::U64(u)) => Ok((coerce_int_to_uint(i), Datum::U64(u))),
+            (Datum::U64(u), Datum::I64(i)) => Ok((Datum::U64(u), coerce_int_to_uint(i))),
+            (Datum::Decimal(l), r) => {
+                let r = try!(r.coerce_dec());
+                Ok((Datum::Decimal(l), Datum::Decimal(r)))
+            }
+            (l, Datum::Decimal(r)) => {
+                let l = try!(l.coerce_dec());
+                Ok((Datum::Decimal(l), Datum::Decimal(r)))
+            }
+            (l, r) => Err(Error::InvalidDataType(format!("{:?} and {:?} are not numeric", l, r))),
+        }
+    }
+
+    fn coerce_dec(self) -> Result<Decimal> {
+        match self {
+            Datum::I64(i) => Ok(Decimal::from_i64(i)),
+            Datum::U64(u) => Decimal::from_u64(u),
+            Datum::Decimal(d) => Ok(d),
+            d => Err(Error::InvalidDataType(format!("can't convert {:?} to decimal", d))),
+        }
+    }
+
+    /// into_bytes converts self into the bytes of its string form.
+    pub fn into_bytes(self) -> Result<Vec<u8>> {
+        if let Datum::Bytes(bs) = self {
+            return Ok(bs);
+        }
+        let s = try!(self.into_string());
+        Ok(s.into_bytes())
+    }
+
+    /// `to_json` converts the datum into json for debugging and exporting.
+    ///
+    /// Bytes are converted into a json string if they are valid utf8,
+    /// otherwise into an object like `{"base64": "..."}`. Only null, integers,
+    /// floats, bytes and json are supported.
+    pub fn to_json(&self) -> Result<Json> {
+        let json = match *self {
+            Datum::Null => Json::Null,
+            Datum::I64(i) => Json::I64(i),
+            Datum::U64(u) => Json::U64(u),
+            Datum::F32(f) => Json::F64(f as f64),
+            Datum::F64(f) => Json::F64(f),
+            Datum::Json(ref j) => j.clone(),
+            Datum::Bytes(ref bs) => {
+                match str::from_utf8(bs) {
+                    Ok(s) => Json::String(s.to_owned()),
+                    Err(_) => {
+                        let mut obj = Object::new();
+                        obj.insert(JSON_BASE64_KEY.to_owned(),
+                                   Json::String(bs.to_base64(base64::STANDARD)));
+                        Json::Object(obj)
+                    }
+                }
+            }
+            ref d => {
+                return Err(Error::InvalidDataType(format!("can't convert {} {:?} to json",
+                                                          d.kind(),
+                                                          d)))
+            }
+        };
+        Ok(json)
+    }
+
+    /// `from_json` converts json generated by `to_json` back into a datum.
+    pub fn from_json(json: &Json) -> Result<Datum> {
+        match *json {
+            Json::Null => Ok(Datum::Null),
+            Json::I64(i) => Ok(Datum::I64(i)),
+            Json::U64(u) => Ok(Datum::U64(u)),
+            Json::F64(f) => Ok(Datum::F64(f)),
+            Json::String(ref s) => Ok(Datum::Bytes(s.as_bytes().to_vec())),
+            Json::Object(ref obj) if obj.len() == 1 && obj.contains_key(JSON_BASE64_KEY) => {
+                if let Json::String(ref s) = obj[JSON_BASE64_KEY] {
+                    return s.from_base64().map(Datum::Bytes).map_err(|e| {
+                        Error::InvalidDataType(format!("invalid base64 {:?}: {}", s, e))
+                    });
+                }
+                Err(Error::InvalidDataType(format!("can't convert json {} to datum", json)))
+            }
+            _ => Err(Error::InvalidDataType(format!("can't convert json {} to datum", json))),
+        }
+    }
+}
+
+/// `parse_json` parses the json text in `bs`.
+pub fn parse_json(bs: &[u8]) -> Result<Json> {
+    let s = try!(str::from_utf8(bs));
+    Json::from_str(s).map_err(|e| Error::InvalidDataType(format!("invalid json {:?}: {}", s, e)))
+}
+
+// a negative integer is kept signed, see `Datum::coerce_numeric_pair`.
+fn coerce_int_to_uint(i: i64) -> Datum {
+    if i >= 0 {
+        Datum::U64(i as u64)
+    } else {
+        Datum::I64(i)
+    }
+}
+
+// NaN is never equal to itself, so it's the only float that breaks the
+// reflexivity of `Eq`; callers should never use it as a hash key.
+impl Eq for Datum {}
+
+fn hash_f64<H: Hasher>(f: f64, state: &mut H) {
+    // 0.0 == -0.0, and all NaNs share the same hash.
+    let f = if f == 0.0 {
+        0.0
+    } else if f.is_nan() {
+        f64::NAN
+    } else {
+        f
+    };
+    let bits: u64 = unsafe { mem::transmute(f) };
+    bits.hash(state);
+}
+
+impl Hash for Datum {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match *self {
+            Datum::Null => 0u8.hash(state),
+            Datum::I64(i) => {
+                1u8.hash(state);
+                i.hash(state);
+            }
+            Datum::U64(u) => {
+                2u8.hash(state);
+                u.hash(state);
+            }
+            Datum::F32(f) => {
+                3u8.hash(state);
+                hash_f64(f as f64, state);
+            }
+            Datum::F64(f) => {
+                4u8.hash(state);
+                hash_f64(f, state);
+            }
+            Datum::Bytes(ref bs) => {
+                5u8.hash(state);
+                bs.hash(state);
+            }
+            Datum::Decimal(ref d) => {
+                6u8.hash(state);
+                d.hash(state);
+            }
+            Datum::Min => 7u8.hash(state),
+            Datum::Max => 8u8.hash(state),
+            Datum::Time(ref t) => {
+                9u8.hash(state);
+                t.hash(state);
+            }
+            Datum::Duration(ref d) => {
+                10u8.hash(state);
+                d.hash(state);
+            }
+            Datum::Json(ref j) => {
+                11u8.hash(state);
+                j.to_string().hash(state);
+            }
+        }
+    }
 }
 
 impl From<bool> for Datum {
@@ -212,9 +708,34 @@ pub fn decode_datum(buf: &[u8]) -> Result<(Datum, usize)> {
             datum = Datum::Bytes(v);
             readed += l;
         }
+        FLOAT_FLAG => {
+            let v = try!(number::decode_f64(&buf[1..]));
+            datum = Datum::F64(v);
+            readed += 8;
+        }
+        FIXED_DECIMAL_FLAG => {
+            try!(codec::check_bound(&buf[1..], 9));
+            let frac = buf[1];
+            let v = try!(number::decode_i64(&buf[2..]));
+            datum = Datum::Decimal(try!(Decimal::checked_new(v, frac)));
+            readed += 9;
+        }
+        DURATION_FLAG => {
+            let v = try!(number::decode_i64(&buf[1..]));
+            datum = Datum::Duration(try!(Duration::from_nanos(v)));
+            readed += 8;
+        }
+        JSON_FLAG => {
+            let (v, l) = try!(bytes::decode_compact_bytes(&buf[1..]));
+            datum = Datum::Json(try!(parse_json(&v)));
+            readed += l;
+        }
         NIL_FLAG => {
             datum = Datum::Null;
         }
+        MAX_FLAG => {
+            datum = Datum::Max;
+        }
         f => {
             return Err(Error::InvalidDataType(format!("unsupported data type `{}` of {:?}",
                                                       f,
@@ -224,16 +745,66 @@ pub fn decode_datum(buf: &[u8]) -> Result<(Datum, usize)> {
     Ok((datum, readed))
 }
 
+/// `skip` gets the length of the first encoded datum in `buf` without decoding
+/// it, so that unwanted columns can be skipped cheaply.
+pub fn skip(buf: &[u8]) -> Result<usize> {
+    try!(codec::check_bound(buf, 1));
+    let l = match buf[0] {
+        INT_FLAG | UINT_FLAG | FLOAT_FLAG | DURATION_FLAG => {
+            try!(codec::check_bound(&buf[1..], 8));
+            8
+        }
+        FIXED_DECIMAL_FLAG => {
+            try!(codec::check_bound(&buf[1..], 9));
+            9
+        }
+        BYTES_FLAG => try!(bytes::encoded_bytes_len(&buf[1..])),
+        COMPACT_BYTES_FLAG | JSON_FLAG => try!(bytes::encoded_compact_bytes_len(&buf[1..])),
+        NIL_FLAG | MAX_FLAG => 0,
+        f => {
+            return Err(Error::InvalidDataType(format!("unsupported data type `{}` of {:?}",
+                                                      f,
+                                                      buf)));
+        }
+    };
+    Ok(l + 1)
+}
+
 /// `decode` decodes all datum from a byte slice generated by tidb.
 pub fn decode(buf: &[u8]) -> Result<Vec<Datum>> {
-    let mut res = vec![];
-    let mut readed = 0;
-    while readed < buf.len() {
-        let (v, vn) = try!(decode_datum(&buf[readed..]));
-        res.push(v);
-        readed += vn;
-    }
-    Ok(res)
+    decode_iter(buf).collect()
+}
+
+/// `DecodeIter` decodes datums from a byte slice lazily.
+///
+/// It stops after the first error.
+pub struct DecodeIter<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for DecodeIter<'a> {
+    type Item = Result<Datum>;
+
+    fn next(&mut self) -> Option<Result<Datum>> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        match decode_datum(self.buf) {
+            Ok((v, vn)) => {
+                self.buf = &self.buf[vn..];
+                Some(Ok(v))
+            }
+            Err(e) => {
+                self.buf = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// `decode_iter` is the lazy version of `decode`.
+pub fn decode_iter(buf: &[u8]) -> DecodeIter {
+    DecodeIter { buf: buf }
 }
 
 /// Get the approximate needed buffer size of values.
@@ -243,7 +814,9 @@ pub fn approximate_size(values: &[Datum], comparable: bool) -> usize {
     values.iter()
           .map(|v| {
               match *v {
-                  Datum::I64(_) | Datum::U64(_) => 9,
+                  Datum::I64(_) | Datum::U64(_) | Datum::F32(_) | Datum::F64(_) |
+                  Datum::Time(_) | Datum::Duration(_) => 9,
+                  Datum::Decimal(_) => 10,
                   Datum::Bytes(ref bs) => {
                       if comparable {
                           bytes::max_encoded_bytes_size(bs.len()) + 1
@@ -251,8 +824,8 @@ pub fn approximate_size(values: &[Datum], comparable: bool) -> usize {
                           bs.len() + number::MAX_VAR_I64_LEN + 1
                       }
                   }
+                  Datum::Json(ref j) => j.to_string().len() + number::MAX_VAR_I64_LEN + 1,
                   Datum::Null | Datum::Min | Datum::Max => 1,
-                  _ => unimplemented!(),
               }
           })
           .sum()
@@ -291,9 +864,50 @@ pub fn encode(buf: &mut [u8], values: &[Datum], comparable: bool) -> Result<usiz
                 try!(number::encode_u64(&mut buf[idx..], u));
                 idx += 8;
             }
+            Datum::F32(f) => {
+                buf[idx] = FLOAT_FLAG;
+                idx += 1;
+                try!(number::encode_f64(&mut buf[idx..], f as f64));
+                idx += 8;
+            }
+            Datum::F64(f) => {
+                buf[idx] = FLOAT_FLAG;
+                idx += 1;
+                try!(number::encode_f64(&mut buf[idx..], f));
+                idx += 8;
+            }
             Datum::Bytes(ref bs) => {
                 idx += try!(encode_bytes(&mut buf[idx..], bs, comparable));
             }
+            Datum::Time(ref t) => {
+                // same as TiDB, time is encoded as a packed u64 and decoded
+                // as `U64`, the column type tells how to interpret it.
+                buf[idx] = UINT_FLAG;
+                idx += 1;
+                try!(number::encode_u64(&mut buf[idx..], t.to_packed_u64()));
+                idx += 8;
+            }
+            Datum::Duration(ref d) => {
+                buf[idx] = DURATION_FLAG;
+                idx += 1;
+                try!(number::encode_i64(&mut buf[idx..], d.to_nanos()));
+                idx += 8;
+            }
+            Datum::Json(ref j) => {
+                // json is encoded in its text form and is not memory-comparable.
+                buf[idx] = JSON_FLAG;
+                idx += 1;
+                idx += try!(bytes::encode_compact_bytes(&mut buf[idx..],
+                                                        j.to_string().as_bytes()));
+            }
+            Datum::Decimal(ref d) => {
+                // TODO: make the key encoding of decimal memory-comparable.
+                buf[idx] = FIXED_DECIMAL_FLAG;
+                buf[idx + 1] = d.frac();
+                idx += 2;
+                try!(number::encode_i64(&mut buf[idx..], d.value()));
+                idx += 8;
+            }
             Datum::Null => {
                 buf[idx] = NIL_FLAG;
                 idx += 1;
@@ -307,12 +921,13 @@ pub fn encode(buf: &mut [u8], values: &[Datum], comparable: bool) -> Result<usiz
                 buf[idx] = MAX_FLAG;
                 idx += 1;
             }
-            _ => unimplemented!(),
         }
     }
     Ok(idx)
 }
 
+/// `encode_key` encodes values so that the encoded keys are in the same order
+/// as the values, except for decimals whose encoding is not memcomparable yet.
 pub fn encode_key(values: &[Datum]) -> Result<Vec<u8>> {
     let mut buf = vec![0; approximate_size(values, true)];
     let written = try!(encode(&mut buf, values, true));
@@ -327,10 +942,23 @@ pub fn encode_value(values: &[Datum]) -> Result<Vec<u8>> {
     Ok(v)
 }
 
+/// `decode_key` decodes a key encoded by `encode_key`.
+pub fn decode_key(buf: &[u8]) -> Result<Vec<Datum>> {
+    decode(buf)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use std::cmp::Ordering;
+    use std::{i64, u64};
+    use std::collections::HashSet;
+    use rustc_serialize::json::Json;
+    use util::codec::{Decimal, Time, Duration};
+
+    fn dec(s: &str) -> Datum {
+        Datum::Decimal(Decimal::from_bytes(s.as_bytes()).unwrap())
+    }
 
     #[test]
     fn test_datum_codec() {
@@ -338,6 +966,9 @@ mod test {
 			vec![Datum::I64(1)],
 			vec![Datum::U64(1), b"123".as_ref().into(), Datum::I64(-1)],
 			vec![Datum::Null],
+			vec![Datum::Decimal(Decimal::new(-12345, 3)), Datum::I64(2)],
+			vec![Datum::F64(-1.5), Datum::F64(2.25)],
+			vec![Datum::Json(Json::from_str(r#"{"a": [1, "x"]}"#).unwrap()), Datum::I64(1)],
 		];
 
         for vs in table.drain(..) {
@@ -351,6 +982,194 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_decode_decimal() {
+        let mut buf = encode_value(&[Datum::Decimal(Decimal::new(-12345, 3))]).unwrap();
+        assert_eq!(buf[0], FIXED_DECIMAL_FLAG);
+        assert_eq!(decode(&buf).unwrap(), vec![Datum::Decimal(Decimal::new(-12345, 3))]);
+
+        // too many fraction digits.
+        buf[1] = 19;
+        assert!(decode(&buf).is_err());
+        // TiDB's decimal format is not supported.
+        buf[0] = 6;
+        buf[1] = 3;
+        assert!(decode(&buf).is_err());
+    }
+
+    #[test]
+    fn test_skip() {
+        let values = vec![
+            Datum::Null,
+            Datum::I64(-1),
+            Datum::U64(u64::MAX),
+            Datum::F64(1.5),
+            b"".as_ref().into(),
+            b"abcdefghijk".as_ref().into(),
+            Datum::Decimal(Decimal::new(-12345, 3)),
+            Datum::Time(Time::parse(b"2016-07-01 12:30:00").unwrap()),
+            Datum::Duration(Duration::parse(b"12:30:00").unwrap()),
+            Datum::Json(Json::from_str(r#"{"a": [1, "x"]}"#).unwrap()),
+            Datum::Max,
+        ];
+        for buf in vec![encode_key(&values).unwrap(), encode_value(&values).unwrap()] {
+            let mut rest = &buf[..];
+            while !rest.is_empty() {
+                let (_, decoded) = decode_datum(rest).unwrap();
+                assert_eq!(skip(rest).unwrap(), decoded);
+                rest = &rest[decoded..];
+            }
+        }
+
+        let invalid: Vec<&[u8]> = vec![b"", b"\x03\x00", b"\x80\x01", b"\x01abcdefg", b"\xff"];
+        for buf in invalid {
+            assert!(skip(buf).is_err());
+        }
+    }
+
+    #[test]
+    fn test_datum_json() {
+        let tests = vec![
+            Datum::Null,
+            Datum::I64(-1),
+            Datum::I64(i64::MIN),
+            Datum::U64(u64::MAX),
+            Datum::F64(1.5),
+            Datum::F64(-0.25),
+            b"".as_ref().into(),
+            b"abc".as_ref().into(),
+            "日本語".as_bytes().into(),
+            b"\xff\x00\x80abc".as_ref().into(),
+        ];
+        for d in tests {
+            let json = d.to_json().unwrap();
+            assert_eq!(Datum::from_json(&json).unwrap(), d);
+        }
+
+        assert_eq!(Datum::I64(1).to_json().unwrap(), Json::I64(1));
+        assert_eq!(Datum::Bytes(b"abc".to_vec()).to_json().unwrap(),
+                   Json::String("abc".to_owned()));
+        let json = Datum::Bytes(vec![0xff, 0xfe]).to_json().unwrap();
+        assert_eq!(format!("{}", json), r#"{"base64":"//4="}"#);
+        // json parsed from text round-trips too.
+        assert_eq!(Datum::from_json(&Json::from_str(&format!("{}", json)).unwrap()).unwrap(),
+                   Datum::Bytes(vec![0xff, 0xfe]));
+        assert_eq!(Datum::F32(0.5).to_json().unwrap(), Json::F64(0.5));
+
+        let j = Json::from_str(r#"{"a": [1, "x"]}"#).unwrap();
+        assert_eq!(Datum::Json(j.clone()).to_json().unwrap(), j);
+
+        assert!(dec("1.5").to_json().is_err());
+        assert!(Datum::Max.to_json().is_err());
+        assert!(Datum::from_json(&Json::Boolean(true)).is_err());
+        assert!(Datum::from_json(&Json::Array(vec![])).is_err());
+        assert!(Datum::from_json(&Json::from_str(r#"{"base64":"!!"}"#).unwrap()).is_err());
+        assert!(Datum::from_json(&Json::from_str(r#"{"a":"b"}"#).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_datum_json_cmp() {
+        let json = |s: &str| Datum::Json(Json::from_str(s).unwrap());
+        let tests = vec![
+            (json(r#"{"a": 1}"#), json(r#"{ "a":1 }"#), Ordering::Equal),
+            (json("[1, 2]"), json("[1, 3]"), Ordering::Less),
+            (json("1"), Datum::Null, Ordering::Greater),
+            (json("1"), Datum::Max, Ordering::Less),
+        ];
+        for (l, r, ord) in tests {
+            assert!(l.cmp(&r).unwrap() == ord, "{:?} vs {:?}", l, r);
+            assert!(r.cmp(&l).unwrap() == ord.reverse(), "{:?} vs {:?}", r, l);
+        }
+        assert!(json("1").cmp(&Datum::I64(1)).is_err());
+        assert!(Datum::I64(1).cmp(&json("1")).is_err());
+        assert!(dec("1").cmp(&json("1")).is_err());
+        assert_eq!(json("[1]").kind(), "json");
+        assert_eq!(json(r#"{"b": 1, "a": 2}"#).into_string().unwrap(), r#"{"a":2,"b":1}"#);
+    }
+
+    #[test]
+    fn test_encode_key_order() {
+        // values of different types are ordered by their flags.
+        let sorted = vec![
+            vec![Datum::Null],
+            vec![b"".as_ref().into()],
+            vec![b"a".as_ref().into(), Datum::I64(1)],
+            vec![b"a\x00".as_ref().into()],
+            vec![b"abcdefgh".as_ref().into()],
+            vec![b"abcdefgh\x00".as_ref().into()],
+            vec![b"b".as_ref().into()],
+            vec![Datum::I64(i64::MIN)],
+            vec![Datum::I64(-1), Datum::Null],
+            vec![Datum::I64(-1), b"".as_ref().into()],
+            vec![Datum::I64(-1), Datum::I64(0)],
+            vec![Datum::I64(0)],
+            vec![Datum::I64(i64::MAX)],
+            vec![Datum::U64(0)],
+            vec![Datum::U64(u64::MAX)],
+            vec![Datum::F64(-1.5)],
+            vec![Datum::F64(0.0)],
+            vec![Datum::F64(2.5)],
+            vec![Datum::Max],
+        ];
+        let keys: Vec<_> = sorted.iter().map(|vs| encode_key(vs).unwrap()).collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(decode_key(key).unwrap(), sorted[i]);
+            if i > 0 {
+                assert!(keys[i - 1] < *key, "{:?} < {:?}", sorted[i - 1], sorted[i]);
+            }
+        }
+
+        // values of the same type are sorted the same as `cmp`.
+        let mut values = vec![Datum::I64(3), Datum::I64(-5), Datum::I64(0), Datum::I64(1 << 40)];
+        let mut keys: Vec<_> = values.iter().map(|v| encode_key(&[v.clone()]).unwrap()).collect();
+        values.sort_by(|l, r| l.cmp(r).unwrap());
+        keys.sort();
+        let decoded: Vec<_> = keys.iter().map(|k| decode_key(k).unwrap().remove(0)).collect();
+        assert_eq!(decoded, values);
+    }
+
+    #[test]
+    fn test_min_max() {
+        let values = vec![
+            Datum::I64(i64::MIN),
+            Datum::I64(-1),
+            Datum::I64(0),
+            Datum::I64(i64::MAX),
+            Datum::U64(u64::MAX),
+            Datum::F64(-1.5),
+            b"".as_ref().into(),
+            b"\xff".as_ref().into(),
+            dec("-1.5"),
+        ];
+        for v in &values {
+            assert_eq!(Datum::Min.cmp(v).unwrap(), Ordering::Less, "{:?}", v);
+            assert_eq!(v.cmp(&Datum::Min).unwrap(), Ordering::Greater, "{:?}", v);
+            assert_eq!(v.cmp(&Datum::Max).unwrap(), Ordering::Less, "{:?}", v);
+            assert_eq!(Datum::Max.cmp(v).unwrap(), Ordering::Greater, "{:?}", v);
+        }
+        // null is not an open bound, it's less than min.
+        assert_eq!(Datum::Null.cmp(&Datum::Min).unwrap(), Ordering::Less);
+        assert_eq!(Datum::Min.cmp(&Datum::Null).unwrap(), Ordering::Greater);
+        assert_eq!(Datum::Min.cmp(&Datum::Min).unwrap(), Ordering::Equal);
+        assert_eq!(Datum::Max.cmp(&Datum::Max).unwrap(), Ordering::Equal);
+        assert!(Datum::Min != Datum::Null);
+
+        // the encoded keys keep the order, min may only be the last datum.
+        for prefix in vec![vec![], vec![Datum::I64(1)]] {
+            for v in &values {
+                let key = |d: Datum| {
+                    let mut vs = prefix.clone();
+                    vs.push(d);
+                    encode_key(&vs).unwrap()
+                };
+                assert!(key(Datum::Null) < key(Datum::Min));
+                assert!(key(Datum::Min) < key(v.clone()), "{:?}", v);
+                assert!(key(v.clone()) < key(Datum::Max), "{:?}", v);
+            }
+        }
+        assert!(encode_key(&[Datum::Min, Datum::I64(1)]).is_err());
+    }
+
     #[test]
     fn test_datum_cmp() {
         let tests = vec![
@@ -399,6 +1218,24 @@ mod test {
             (b"abc".as_ref().into(), b"ab".as_ref().into(), Ordering::Greater),
             (b"123".as_ref().into(), Datum::I64(1234), Ordering::Less),
             (b"".as_ref().into(), Datum::Null, Ordering::Greater),
+
+            (dec("0.3"), dec("0.30"), Ordering::Equal),
+            (dec("0.3"), Datum::Null, Ordering::Greater),
+            (dec("1.0"), Datum::I64(1), Ordering::Equal),
+            (dec("-1.5"), Datum::I64(-1), Ordering::Less),
+            (dec("3.5"), Datum::U64(3), Ordering::Greater),
+            (dec("3.5"), Datum::U64(u64::max_value()), Ordering::Less),
+            (dec("0.125"), Datum::F64(0.125), Ordering::Equal),
+            (dec("-0.125"), Datum::F64(-0.125), Ordering::Equal),
+            (dec("-0.125"), Datum::F64(0.125), Ordering::Less),
+            // 0.1 as a float is 0.1000000000000000055511151231257827...
+            (dec("0.1"), Datum::F64(0.1), Ordering::Less),
+            (dec("0.100000000000000001"), Datum::F64(0.1), Ordering::Less),
+            (dec("0.100000000000000006"), Datum::F64(0.1), Ordering::Greater),
+            (dec("-0.100000000000000005"), Datum::F64(-0.1), Ordering::Greater),
+            (dec("0.000000000000000001"), Datum::F64(5e-324), Ordering::Greater),
+            (dec("1.5"), Datum::F64(1.5), Ordering::Equal),
+            (dec("2.50"), b"2.5".as_ref().into(), Ordering::Equal),
         ];
 
         for (lhs, rhs, ret) in tests {
@@ -426,20 +1263,29 @@ mod test {
             (Datum::U64(0), false),
             (Datum::U64(1), true),
             (Datum::F32(0f32), false),
-            (Datum::F32(0.4), false),
+            (Datum::F32(-0f32), false),
+            (Datum::F32(0.4), true),
             (Datum::F32(0.5), true),
             (Datum::F32(-0.5), true),
-            (Datum::F32(-0.4), false),
+            (Datum::F32(-0.4), true),
             (Datum::F64(0f64), false),
-            (Datum::F64(0.4), false),
+            (Datum::F64(-0f64), false),
+            (Datum::F64(0.4), true),
             (Datum::F64(0.5), true),
             (Datum::F64(-0.5), true),
-            (Datum::F64(-0.4), false),
+            (Datum::F64(-0.4), true),
+            (Datum::F64(1e-300), true),
             (b"".as_ref().into(), false),
-            (b"0.5".as_ref().into(), false),
+            (b"0.5".as_ref().into(), true),
+            (b"-0.0x".as_ref().into(), false),
+            (b"1e-3".as_ref().into(), true),
             (b"0".as_ref().into(), false),
             (b"2".as_ref().into(), true),
             (b"abc".as_ref().into(), false),
+            (b"1x".as_ref().into(), true),
+            (b" -3abc".as_ref().into(), true),
+            (b"x1".as_ref().into(), false),
+            (b"99999999999999999999".as_ref().into(), true),
         ];
         for (d, b) in tests {
             if d.clone().into_bool().unwrap() ^ b {
@@ -447,4 +1293,200 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_datum_into_f64() {
+        let tests = vec![
+            (Datum::I64(-3), -3f64),
+            (Datum::U64(u64::MAX), u64::MAX as f64),
+            (Datum::F32(0.5), 0.5),
+            (Datum::F64(-1.25), -1.25),
+            (dec("-0.75"), -0.75),
+            (b"3.5abc".as_ref().into(), 3.5),
+            (b" -2e2".as_ref().into(), -200f64),
+            (b"abc".as_ref().into(), 0f64),
+        ];
+        for (d, f) in tests {
+            let ff = d.clone().into_f64().unwrap();
+            if ff != f {
+                panic!("expect {:?} to be {}, got {}", d, f, ff);
+            }
+        }
+
+        for d in vec![Datum::Null, Datum::Min, Datum::Max] {
+            assert!(d.clone().into_f64().is_err(), "{:?} should not be float", d);
+        }
+    }
+
+    #[test]
+    fn test_coerce_numeric_pair() {
+        let big = i64::MAX as u64 + 1;
+        let tests = vec![
+            (Datum::I64(1), Datum::I64(-2), Datum::I64(1), Datum::I64(-2)),
+            (Datum::U64(1), Datum::U64(big), Datum::U64(1), Datum::U64(big)),
+            (Datum::I64(1), Datum::U64(2), Datum::U64(1), Datum::U64(2)),
+            (Datum::U64(big), Datum::I64(i64::MAX), Datum::U64(big), Datum::U64(i64::MAX as u64)),
+            (Datum::I64(-1), Datum::U64(2), Datum::I64(-1), Datum::U64(2)),
+            (Datum::U64(2), Datum::I64(-1), Datum::U64(2), Datum::I64(-1)),
+            (Datum::I64(-1), Datum::U64(big), Datum::I64(-1), Datum::U64(big)),
+            (Datum::U64(big), Datum::I64(i64::MIN), Datum::U64(big), Datum::I64(i64::MIN)),
+            (Datum::I64(1), Datum::F32(0.5), Datum::F64(1f64), Datum::F64(0.5)),
+            (Datum::F64(1.5), Datum::U64(big), Datum::F64(1.5), Datum::F64(big as f64)),
+            (Datum::I64(1), b"2.5".as_ref().into(), Datum::F64(1f64), Datum::F64(2.5)),
+            (Datum::I64(1), dec("1.5"), dec("1"), dec("1.5")),
+            (dec("-1.5"), Datum::U64(3), dec("-1.5"), dec("3")),
+            (dec("1.5"), Datum::F64(1f64), Datum::F64(1.5), Datum::F64(1f64)),
+        ];
+        for (l, r, el, er) in tests {
+            let (cl, cr) = l.clone().coerce_numeric_pair(r.clone()).unwrap();
+            if cl != el || cr != er {
+                panic!("coerce {:?} and {:?} expect {:?} and {:?}, got {:?} and {:?}",
+                       l,
+                       r,
+                       el,
+                       er,
+                       cl,
+                       cr);
+            }
+        }
+
+        let tests = vec![
+            (dec("1"), Datum::U64(big)),
+            (Datum::I64(1), Datum::Null),
+            (Datum::Max, Datum::F64(1f64)),
+        ];
+        for (l, r) in tests {
+            assert!(l.clone().coerce_numeric_pair(r.clone()).is_err(),
+                    "{:?} and {:?} should not be coerced",
+                    l,
+                    r);
+        }
+    }
+
+    #[test]
+    fn test_datum_hash() {
+        let mut set = HashSet::new();
+        set.insert(Datum::I64(1));
+        set.insert(Datum::U64(2));
+        set.insert(b"abc".as_ref().into());
+        set.insert(dec("0.3"));
+        set.insert(Datum::F64(-0.0));
+
+        assert!(set.contains(&Datum::I64(1)));
+        assert!(set.contains(&Datum::U64(2)));
+        assert!(set.contains(&b"abc".as_ref().into()));
+        assert!(set.contains(&dec("0.30")));
+        assert!(set.contains(&Datum::F64(0.0)));
+
+        // types are not coerced.
+        assert!(!set.contains(&Datum::U64(1)));
+        assert!(!set.contains(&Datum::I64(2)));
+        assert!(!set.contains(&b"ab".as_ref().into()));
+        assert!(!set.contains(&Datum::Null));
+        assert!(!set.insert(Datum::I64(1)));
+    }
+
+    #[test]
+    fn test_decode_iter() {
+        let values = vec![Datum::I64(-1),
+                          Datum::Null,
+                          Datum::U64(2),
+                          b"abc".as_ref().into(),
+                          dec("1.23")];
+        let buf = encode_value(&values).unwrap();
+        let decoded: Vec<Datum> = decode_iter(&buf).map(|d| d.unwrap()).collect();
+        assert_eq!(decoded, decode(&buf).unwrap());
+        assert_eq!(decoded, values);
+
+        let mut iter = decode_iter(&buf[..buf.len() - 1]);
+        assert_eq!(iter.next().unwrap().unwrap(), Datum::I64(-1));
+        assert!(iter.by_ref().any(|d| d.is_err()));
+        assert!(iter.next().is_none());
+
+        assert!(decode_iter(&[]).next().is_none());
+    }
+
+    #[test]
+    fn test_datum_kind() {
+        let tests = vec![
+            (Datum::Null, "null"),
+            (Datum::I64(1), "int"),
+            (Datum::U64(1), "uint"),
+            (Datum::F32(1.0), "float"),
+            (Datum::F64(1.0), "float"),
+            (b"abc".as_ref().into(), "bytes"),
+            (dec("1.0"), "decimal"),
+            (Datum::Min, "min"),
+            (Datum::Max, "max"),
+        ];
+        for (d, kind) in tests {
+            assert_eq!(d.kind(), kind);
+        }
+
+        let msg = format!("{:?}", Datum::Max.into_string().unwrap_err());
+        assert!(msg.contains("max"), "unexpected message {}", msg);
+        let msg = format!("{:?}", Datum::Null.into_bool().unwrap_err());
+        assert!(msg.contains("null"), "unexpected message {}", msg);
+    }
+
+    #[test]
+    fn test_datum_time() {
+        let t1 = Time::parse(b"2016-07-01 12:00:00").unwrap();
+        let t2 = Time::parse(b"2016-07-01 12:00:00.000001").unwrap();
+        let d1 = Duration::parse(b"-00:00:01").unwrap();
+        let d2 = Duration::parse(b"00:00:01").unwrap();
+        let tests = vec![
+            (Datum::Time(t1), Datum::Time(t2), Ordering::Less),
+            (Datum::Time(t1), Datum::U64(t1.to_packed_u64()), Ordering::Equal),
+            (Datum::Time(t2), b"2016-07-01".as_ref().into(), Ordering::Greater),
+            (Datum::Time(t1), Datum::Null, Ordering::Greater),
+            (Datum::Time(t1), Datum::Max, Ordering::Less),
+            (Datum::Duration(d1), Datum::Duration(d2), Ordering::Less),
+            (Datum::Duration(d2), b"00:00:01".as_ref().into(), Ordering::Equal),
+            (Datum::Duration(d1), Datum::Min, Ordering::Greater),
+        ];
+        for (l, r, ord) in tests {
+            assert!(l.cmp(&r).unwrap() == ord, "{:?} vs {:?}", l, r);
+            assert!(r.cmp(&l).unwrap() == ord.reverse(), "{:?} vs {:?}", r, l);
+        }
+        assert!(Datum::Time(t1).cmp(&Datum::Duration(d1)).is_err());
+        assert!(Datum::I64(1).cmp(&Datum::Time(t1)).is_err());
+
+        let values = vec![Datum::Time(t1), Datum::Duration(d1)];
+        let decoded = decode(&encode_value(&values).unwrap()).unwrap();
+        assert_eq!(decoded,
+                   vec![Datum::U64(t1.to_packed_u64()), Datum::Duration(d1)]);
+        let key1 = encode_key(&[Datum::Duration(d1)]).unwrap();
+        let key2 = encode_key(&[Datum::Duration(d2)]).unwrap();
+        assert!(key1 < key2);
+    }
+
+    #[test]
+    fn test_datum_into_i64() {
+        let tests = vec![
+            (Datum::I64(-1), -1),
+            (Datum::U64(i64::MAX as u64), i64::MAX),
+            (Datum::F32(1.9), 1),
+            (Datum::F64(-1.9), -1),
+            (Datum::F64(-9223372036854775808.0), i64::MIN),
+            (dec("12.99"), 12),
+            (dec("-0.5"), 0),
+            (b" 12.9abc".as_ref().into(), 12),
+            (b"-3e2".as_ref().into(), -3),
+            (b"abc".as_ref().into(), 0),
+        ];
+        for (d, expect) in tests {
+            assert!(d.clone().into_i64().unwrap() == expect, "{:?}", d);
+        }
+
+        let bad = vec![Datum::U64(i64::MAX as u64 + 1),
+                       Datum::F64(9223372036854775808.0),
+                       Datum::F64(::std::f64::NAN),
+                       b"99999999999999999999".as_ref().into(),
+                       Datum::Null,
+                       Datum::Max];
+        for d in bad {
+            assert!(d.clone().into_i64().is_err(), "{:?}", d);
+        }
+    }
 }
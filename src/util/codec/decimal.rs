@@ -0,0 +1,389 @@
+// Copyright 2016 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::{i64, str};
+
+use super::{Result, Error};
+
+/// The max fraction digits a `Decimal` keeps.
+pub const MAX_FRACTION: u8 = 18;
+/// The extra fraction digits kept by division, same as the default
+/// `div_precision_increment` of MySQL.
+pub const DIV_FRAC_INCR: u8 = 4;
+
+fn pow10(n: u8) -> Option<i64> {
+    let mut r = 1i64;
+    for _ in 0..n {
+        r = match r.checked_mul(10) {
+            Some(v) => v,
+            None => return None,
+        };
+    }
+    Some(r)
+}
+
+// i64::MIN can't be negated, so use u64 for the absolute value.
+fn abs_u64(v: i64) -> u64 {
+    if v < 0 {
+        (!(v as u64)).wrapping_add(1)
+    } else {
+        v as u64
+    }
+}
+
+/// `Decimal` is an exact decimal number whose value is `value / 10^frac`.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    value: i64,
+    frac: u8,
+}
+
+impl Decimal {
+    /// Create a decimal of `value / 10^frac`, `frac` must not be larger than
+    /// `MAX_FRACTION`, use `checked_new` for untrusted input.
+    pub fn new(value: i64, frac: u8) -> Decimal {
+        assert!(frac <= MAX_FRACTION, "fraction digits {} are too many", frac);
+        Decimal {
+            value: value,
+            frac: frac,
+        }
+    }
+
+    /// Like `new`, but an error is returned if `frac` is out of range.
+    pub fn checked_new(value: i64, frac: u8) -> Result<Decimal> {
+        if frac > MAX_FRACTION {
+            return Err(Error::InvalidDataType(format!("decimal can't have {} fraction digits",
+                                                      frac)));
+        }
+        Ok(Decimal::new(value, frac))
+    }
+
+    pub fn from_i64(i: i64) -> Decimal {
+        Decimal::new(i, 0)
+    }
+
+    pub fn from_u64(u: u64) -> Result<Decimal> {
+        if u > i64::MAX as u64 {
+            return Err(Error::InvalidDataType(format!("{} overflows decimal", u)));
+        }
+        Ok(Decimal::from_i64(u as i64))
+    }
+
+    /// `from_bytes` parses a decimal from a string like `-12.345`.
+    ///
+    /// Fraction digits beyond `MAX_FRACTION` are truncated.
+    pub fn from_bytes(bs: &[u8]) -> Result<Decimal> {
+        let s = try!(str::from_utf8(bs)).trim();
+        let (negative, s) = if s.starts_with('-') {
+            (true, &s[1..])
+        } else if s.starts_with('+') {
+            (false, &s[1..])
+        } else {
+            (false, s)
+        };
+        let (int_part, frac_part) = match s.find('.') {
+            Some(idx) => (&s[..idx], &s[idx + 1..]),
+            None => (s, ""),
+        };
+        if int_part.is_empty() && frac_part.is_empty() {
+            return Err(Error::InvalidDataType(format!("invalid decimal {:?}", s)));
+        }
+        let mut value = 0i64;
+        let mut frac = 0u8;
+        for (i, c) in int_part.bytes().chain(frac_part.bytes()).enumerate() {
+            if c < b'0' || c > b'9' {
+                return Err(Error::InvalidDataType(format!("invalid decimal {:?}", s)));
+            }
+            if i >= int_part.len() {
+                if frac == MAX_FRACTION {
+                    continue;
+                }
+                frac += 1;
+            }
+            value = try!(value.checked_mul(10)
+                              .and_then(|v| v.checked_add((c - b'0') as i64))
+                              .ok_or_else(|| {
+                                  Error::InvalidDataType(format!("decimal {:?} overflows", s))
+                              }));
+        }
+        if negative {
+            value = -value;
+        }
+        Ok(Decimal::new(value, frac))
+    }
+
+    /// Get the unscaled value.
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+
+    /// Get the count of fraction digits.
+    pub fn frac(&self) -> u8 {
+        self.frac
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.value == 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.value < 0
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.value as f64 / 10f64.powi(self.frac as i32)
+    }
+
+    // Get the unscaled value when fraction digits are extended to `frac`.
+    fn rescale(&self, frac: u8) -> Option<i64> {
+        pow10(frac - self.frac).and_then(|p| self.value.checked_mul(p))
+    }
+
+    pub fn checked_add(&self, other: &Decimal) -> Option<Decimal> {
+        self.align(other).and_then(|(l, r, frac)| l.checked_add(r).map(|v| Decimal::new(v, frac)))
+    }
+
+    pub fn checked_sub(&self, other: &Decimal) -> Option<Decimal> {
+        other.value.checked_neg().and_then(|v| self.checked_add(&Decimal::new(v, other.frac)))
+    }
+
+    pub fn checked_mul(&self, other: &Decimal) -> Option<Decimal> {
+        let mut value = match self.value.checked_mul(other.value) {
+            Some(v) => v,
+            None => return None,
+        };
+        let mut frac = self.frac + other.frac;
+        if frac > MAX_FRACTION {
+            value /= pow10(frac - MAX_FRACTION).unwrap();
+            frac = MAX_FRACTION;
+        }
+        Some(Decimal::new(value, frac))
+    }
+
+    /// `checked_div` returns None if `other` is zero or the result overflows.
+    pub fn checked_div(&self, other: &Decimal) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        let mut frac = self.frac + DIV_FRAC_INCR;
+        if frac > MAX_FRACTION {
+            frac = MAX_FRACTION;
+        }
+        pow10(other.frac + frac - self.frac)
+            .and_then(|p| self.value.checked_mul(p))
+            .and_then(|v| v.checked_div(other.value))
+            .map(|v| Decimal::new(v, frac))
+    }
+
+    // Get the unscaled values of both decimals with the same count of fraction
+    // digits, which is also returned.
+    fn align(&self, other: &Decimal) -> Option<(i64, i64, u8)> {
+        let frac = if self.frac > other.frac {
+            self.frac
+        } else {
+            other.frac
+        };
+        match (self.rescale(frac), other.rescale(frac)) {
+            (Some(l), Some(r)) => Some((l, r, frac)),
+            _ => None,
+        }
+    }
+
+    /// `checked_int_div` returns the quotient truncated towards zero, None is
+    /// returned if `other` is zero or the result overflows.
+    pub fn checked_int_div(&self, other: &Decimal) -> Option<i64> {
+        self.align(other).and_then(|(l, r, _)| l.checked_div(r))
+    }
+
+    /// `checked_rem` returns the remainder which has the sign of self, None is
+    /// returned if `other` is zero or the result overflows.
+    pub fn checked_rem(&self, other: &Decimal) -> Option<Decimal> {
+        self.align(other).and_then(|(l, r, frac)| {
+            if r == 0 {
+                return None;
+            }
+            // i64::MIN % -1 overflows in rust, but the remainder is 0.
+            Some(Decimal::new(l.wrapping_rem(r), frac))
+        })
+    }
+
+    /// Split into the integral part and the unscaled fractional part, both
+    /// of which have the sign of the decimal.
+    pub fn split(&self) -> (i64, i64) {
+        let p = pow10(self.frac).unwrap();
+        (self.value / p, self.value % p)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        // compare the integral parts first to avoid overflow when rescaling.
+        let (l_int, l_frac) = self.split();
+        let (r_int, r_frac) = other.split();
+        match l_int.cmp(&r_int) {
+            Ordering::Equal => {}
+            ord => return ord,
+        }
+        if self.frac > other.frac {
+            l_frac.cmp(&(r_frac * pow10(self.frac - other.frac).unwrap()))
+        } else {
+            (l_frac * pow10(other.frac - self.frac).unwrap()).cmp(&r_frac)
+        }
+    }
+}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // strip trailing zeros so that equal decimals like 0.3 and 0.30 have
+        // the same hash.
+        let (mut value, mut frac) = (self.value, self.frac);
+        while frac > 0 && value % 10 == 0 {
+            value /= 10;
+            frac -= 1;
+        }
+        value.hash(state);
+        frac.hash(state);
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let (int_part, frac_part) = self.split();
+        if self.value < 0 {
+            try!(write!(f, "-"));
+        }
+        try!(write!(f, "{}", abs_u64(int_part)));
+        if self.frac > 0 {
+            try!(write!(f, ".{:0width$}", abs_u64(frac_part), width = self.frac as usize));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cmp::Ordering;
+    use std::hash::{Hash, Hasher, SipHasher};
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_bytes(s.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_decimal_parse_and_display() {
+        let tests = vec![
+            ("0", "0"),
+            ("0.1", "0.1"),
+            ("-12.345", "-12.345"),
+            ("+3.10", "3.10"),
+            (" 7 ", "7"),
+            (".5", "0.5"),
+            ("-0.05", "-0.05"),
+        ];
+        for (s, expect) in tests {
+            assert_eq!(format!("{}", dec(s)), expect);
+        }
+
+        for s in &["", "abc", "1.2.3", "-", "99999999999999999999"] {
+            assert!(Decimal::from_bytes(s.as_bytes()).is_err(), "{} should fail", s);
+        }
+    }
+
+    #[test]
+    fn test_decimal_checked_new() {
+        assert_eq!(Decimal::checked_new(-12345, 3).unwrap(), dec("-12.345"));
+        let max = Decimal::checked_new(1, MAX_FRACTION).unwrap();
+        assert_eq!(max, dec("0.000000000000000001"));
+        assert!(Decimal::checked_new(1, MAX_FRACTION + 1).is_err());
+        assert!(Decimal::checked_new(0, 255).is_err());
+    }
+
+    #[test]
+    fn test_decimal_arith() {
+        assert_eq!(dec("0.1").checked_add(&dec("0.2")).unwrap(), dec("0.3"));
+        assert_eq!(dec("1.5").checked_sub(&dec("2.25")).unwrap(), dec("-0.75"));
+        assert_eq!(dec("1.5").checked_mul(&dec("-2.2")).unwrap(), dec("-3.3"));
+        assert_eq!(dec("1").checked_div(&dec("3")).unwrap(), dec("0.3333"));
+        assert!(dec("1").checked_div(&dec("0.00")).is_none());
+        assert!(Decimal::from_i64(::std::i64::MAX).checked_add(&dec("1")).is_none());
+
+        assert_eq!(dec("7.5").checked_int_div(&dec("2")).unwrap(), 3);
+        assert_eq!(dec("-7.5").checked_int_div(&dec("2.5")).unwrap(), -3);
+        assert!(dec("1").checked_int_div(&dec("0.0")).is_none());
+        assert_eq!(dec("7.5").checked_rem(&dec("2")).unwrap(), dec("1.5"));
+        assert_eq!(dec("-7.5").checked_rem(&dec("2")).unwrap(), dec("-1.5"));
+        assert_eq!(dec("7").checked_rem(&dec("-0.3")).unwrap(), dec("0.1"));
+        assert!(dec("1").checked_rem(&dec("0")).is_none());
+        let min = Decimal::from_i64(::std::i64::MIN);
+        assert_eq!(min.checked_rem(&dec("-1")).unwrap(), dec("0"));
+        assert!(min.checked_int_div(&dec("-1")).is_none());
+        let min = Decimal::new(::std::i64::MIN, MAX_FRACTION);
+        assert!(min.checked_div(&Decimal::from_i64(-1)).is_none());
+    }
+
+    fn hash(d: &Decimal) -> u64 {
+        let mut hasher = SipHasher::new();
+        d.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_decimal_hash() {
+        let tests = vec![
+            ("0.3", "0.30"),
+            ("0", "0.000"),
+            ("-1.50", "-1.5"),
+            ("10", "10.0"),
+        ];
+        for (l, r) in tests {
+            assert!(hash(&dec(l)) == hash(&dec(r)), "{} vs {}", l, r);
+        }
+        assert!(hash(&dec("10")) != hash(&dec("1")));
+    }
+
+    #[test]
+    fn test_decimal_cmp() {
+        let tests = vec![
+            ("0.3", "0.30", Ordering::Equal),
+            ("0.1", "0.2", Ordering::Less),
+            ("-0.1", "0.01", Ordering::Less),
+            ("-1.5", "-1.25", Ordering::Less),
+            ("10", "9.99", Ordering::Greater),
+            ("0.000000000000000001", "0", Ordering::Greater),
+        ];
+        for (l, r, ord) in tests {
+            assert!(dec(l).cmp(&dec(r)) == ord, "{} vs {}", l, r);
+            assert!(dec(r).cmp(&dec(l)) == ord.reverse(), "{} vs {}", r, l);
+        }
+    }
+}
@@ -21,8 +21,12 @@ pub mod number;
 pub mod datum;
 pub mod table;
 pub mod convert;
+pub mod decimal;
+pub mod datetime;
 
 pub use self::datum::Datum;
+pub use self::decimal::Decimal;
+pub use self::datetime::{Time, Duration};
 
 use std::str::Utf8Error;
 use std::string::FromUtf8Error;
@@ -11,6 +11,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::{f64, mem};
+
 use byteorder::{ByteOrder, BigEndian};
 use super::{check_bound, Result, Error};
 
@@ -85,6 +87,57 @@ pub fn decode_u64_desc(buf: &[u8]) -> Result<u64> {
     Ok(!v)
 }
 
+fn order_encode_f64(v: f64) -> u64 {
+    // -0.0 is encoded as 0.0 and all NaNs share the same encoding, which is
+    // greater than +inf.
+    let v = if v == 0.0 {
+        0.0
+    } else if v.is_nan() {
+        f64::NAN
+    } else {
+        v
+    };
+    let u: u64 = unsafe { mem::transmute(v) };
+    if v.is_sign_negative() {
+        !u
+    } else {
+        u | SIGN_MARK
+    }
+}
+
+fn order_decode_f64(u: u64) -> f64 {
+    let u = if u & SIGN_MARK > 0 {
+        u & !SIGN_MARK
+    } else {
+        !u
+    };
+    unsafe { mem::transmute(u) }
+}
+
+/// `encode_f64` writes the encoded value to buf.
+/// `encode_f64` guarantees that the encoded value is in ascending order for comparison.
+pub fn encode_f64(buf: &mut [u8], v: f64) -> Result<()> {
+    encode_u64(buf, order_encode_f64(v))
+}
+
+/// `encode_f64_desc` writes the encoded value to buf.
+/// `encode_f64_desc` guarantees that the encoded value is in descending order for comparison.
+pub fn encode_f64_desc(buf: &mut [u8], v: f64) -> Result<()> {
+    encode_u64_desc(buf, order_encode_f64(v))
+}
+
+/// `decode_f64` decodes value encoded by `encode_f64` before.
+pub fn decode_f64(buf: &[u8]) -> Result<f64> {
+    let u = try!(decode_u64(buf));
+    Ok(order_decode_f64(u))
+}
+
+/// `decode_f64_desc` decodes value encoded by `encode_f64_desc` before.
+pub fn decode_f64_desc(buf: &[u8]) -> Result<f64> {
+    let u = try!(decode_u64_desc(buf));
+    Ok(order_decode_f64(u))
+}
+
 /// `encode_var_i64` writes the encoded value to slice buf.
 /// Note that the encoded result is not memcomparable.
 pub fn encode_var_i64(buf: &mut [u8], v: i64) -> usize {
@@ -137,7 +190,7 @@ pub fn decode_var_u64(buf: &[u8]) -> Result<(u64, usize)> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::{i64, u64};
+    use std::{f64, i64, u64};
     use util::codec::{Result, Error};
     use std::fmt::Debug;
     use protobuf::CodedOutputStream;
@@ -235,6 +288,52 @@ mod test {
         test_order(&test_func[1], tests, &ordered_case);
     }
 
+    #[test]
+    fn test_f64_codec() {
+        let tests = vec![f64::NEG_INFINITY,
+                         f64::MIN,
+                         -1.0,
+                         -f64::MIN_POSITIVE,
+                         0.0,
+                         f64::MIN_POSITIVE,
+                         1.0,
+                         f64::MAX,
+                         f64::INFINITY];
+        let mut buf = vec![0; 8];
+        let mut encoded = vec![];
+        let mut encoded_desc = vec![];
+        for &v in &tests {
+            encode_f64(&mut buf, v).unwrap();
+            assert_eq!(decode_f64(&buf).unwrap(), v);
+            encoded.push(buf.clone());
+            encode_f64_desc(&mut buf, v).unwrap();
+            assert_eq!(decode_f64_desc(&buf).unwrap(), v);
+            encoded_desc.push(buf.clone());
+        }
+        for i in 1..tests.len() {
+            assert!(encoded[i - 1] < encoded[i], "{} < {}", tests[i - 1], tests[i]);
+            assert!(encoded_desc[i - 1] > encoded_desc[i],
+                    "{} > {}",
+                    tests[i - 1],
+                    tests[i]);
+        }
+    }
+
+    #[test]
+    fn test_f64_codec_special() {
+        let encode = |v: f64| {
+            let mut buf = vec![0; 8];
+            encode_f64(&mut buf, v).unwrap();
+            buf
+        };
+        assert_eq!(encode(-0.0), encode(0.0));
+        assert!(decode_f64(&encode(-0.0)).unwrap().is_sign_positive());
+        assert_eq!(encode(f64::NAN), encode(-f64::NAN));
+        assert!(encode(f64::NAN) > encode(f64::INFINITY));
+        assert!(decode_f64(&encode(f64::NAN)).unwrap().is_nan());
+        assert!(decode_f64(&[0; 7]).is_err());
+    }
+
     #[test]
     fn test_var_i64_codec() {
         for &v in I64_TESTS {
@@ -261,6 +360,51 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_var_codec_len() {
+        let tests = vec![
+            (0, 1),
+            (-1, 1),
+            (63, 1),
+            (-64, 1),
+            (64, 2),
+            (-65, 2),
+            (i64::MAX, MAX_VAR_I64_LEN),
+            (i64::MIN, MAX_VAR_I64_LEN),
+        ];
+        for (v, len) in tests {
+            let mut buf = vec![0; MAX_VAR_I64_LEN + 1];
+            assert_eq!(encode_var_i64(&mut buf, v), len);
+            assert_eq!(decode_var_i64(&buf).unwrap(), (v, len));
+        }
+
+        let tests = vec![(0, 1), (127, 1), (128, 2), (16383, 2), (16384, 3), (u64::MAX, 10)];
+        for (v, len) in tests {
+            let mut buf = vec![0; MAX_VAR_I64_LEN + 1];
+            assert_eq!(encode_var_u64(&mut buf, v), len);
+            assert_eq!(decode_var_u64(&buf).unwrap(), (v, len));
+        }
+    }
+
+    #[test]
+    fn test_decode_var_corrupted() {
+        let mut buf = vec![0; MAX_VAR_I64_LEN];
+        let n = encode_var_u64(&mut buf, u64::MAX);
+        match decode_var_u64(&buf[..n - 1]) {
+            Err(Error::Eof) => {}
+            r => panic!("expect eof, but got {:?}", r),
+        }
+        assert!(decode_var_u64(&[]).is_err());
+        assert!(decode_var_i64(&[0x80]).is_err());
+
+        // the 10th byte can only carry one bit.
+        buf[n - 1] = 2;
+        match decode_var_u64(&buf[..n]) {
+            Err(Error::OutOfBound(_, _)) => {}
+            r => panic!("expect out of bound, but got {:?}", r),
+        }
+    }
+
     fn test_encode_out_of_bound<T, B, R, F: Fn(B, T) -> Result<R>>(f: F, t: T, buf: B) {
         if let Err(Error::OutOfBound(_, _)) = f(buf, t) {
             return;
@@ -14,7 +14,7 @@
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::io::{self, Write};
-use std::slice;
+use std::{slice, str};
 use std::net::{ToSocketAddrs, TcpStream, SocketAddr};
 use std::time::{Duration, Instant};
 use std::collections::hash_map::Entry;
@@ -148,28 +148,32 @@ pub fn to_socket_addr<A: ToSocketAddrs>(addr: A) -> io::Result<SocketAddr> {
 
 /// A function to escape a byte array to a readable ascii string.
 ///
+/// Printable ascii is kept as is, quotes and backslash are escaped by
+/// a backslash, other bytes are written as `\xHH`.
+///
 /// # Examples
 ///
 /// ```
 /// use tikv::util::escape;
 ///
 /// assert_eq!("ab", escape(b"ab"));
-/// assert_eq!("a\\015\\012\\011 \\'\\\"\\", escape(b"a\r\n\t '\"\\"));
-/// assert_eq!("\\342\\235\\244\\360\\237\\220\\267", escape("❤🐷".as_bytes()));
+/// assert_eq!("a\\x0d\\x0a\\x09 \\'\\\"\\\\", escape(b"a\r\n\t '\"\\"));
+/// assert_eq!("\\xe2\\x9d\\xa4\\xf0\\x9f\\x90\\xb7", escape("❤🐷".as_bytes()));
 /// ```
 ///
 pub fn escape(data: &[u8]) -> String {
+    const HEX: &'static [u8] = b"0123456789abcdef";
     let mut escaped = Vec::with_capacity(data.len() * 4);
     for &c in data {
         match c {
             b'\'' => escaped.extend_from_slice(b"\\'"),
             b'"' => escaped.extend_from_slice(b"\\\""),
+            b'\\' => escaped.extend_from_slice(b"\\\\"),
             b'\x20'...b'\x7e' => escaped.push(c),
             _ => {
-                escaped.push(b'\\');
-                escaped.push(b'0' + (c >> 6));
-                escaped.push(b'0' + ((c >> 3) & 7));
-                escaped.push(b'0' + (c & 7));
+                escaped.extend_from_slice(b"\\x");
+                escaped.push(HEX[(c >> 4) as usize]);
+                escaped.push(HEX[(c & 0xf) as usize]);
             }
         }
     }
@@ -177,6 +181,36 @@ pub fn escape(data: &[u8]) -> String {
     unsafe { String::from_utf8_unchecked(escaped) }
 }
 
+/// Convert a string generated by `escape` back to the byte array.
+pub fn unescape(s: &str) -> Result<Vec<u8>, String> {
+    let bs = s.as_bytes();
+    let mut data = Vec::with_capacity(bs.len());
+    let mut i = 0;
+    while i < bs.len() {
+        if bs[i] != b'\\' {
+            data.push(bs[i]);
+            i += 1;
+            continue;
+        }
+        match bs.get(i + 1) {
+            Some(&c) if c == b'\'' || c == b'"' || c == b'\\' => {
+                data.push(c);
+                i += 2;
+            }
+            Some(&b'x') if i + 4 <= bs.len() => {
+                let hex = str::from_utf8(&bs[i + 2..i + 4]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(c) => data.push(c),
+                    None => return Err(format!("invalid escape at {} in {:?}", i, s)),
+                }
+                i += 4;
+            }
+            _ => return Err(format!("invalid escape at {} in {:?}", i, s)),
+        }
+    }
+    Ok(data)
+}
+
 /// Convert a borrow to a slice.
 pub fn as_slice<T>(t: &T) -> &[T] {
     unsafe {
@@ -249,6 +283,28 @@ mod tests {
     use std::net::{SocketAddr, AddrParseError};
     use super::*;
 
+    #[test]
+    fn test_escape() {
+        let keys: Vec<&[u8]> = vec![
+            b"",
+            b"zabc",
+            b"z\x00\x01",
+            b"\xff\xfe\x00z",
+            b"a'b\"c\\d\\x12",
+            "❤🐷".as_bytes(),
+        ];
+        for key in keys {
+            let escaped = escape(key);
+            assert!(escaped.bytes().all(|c| c >= 0x20 && c <= 0x7e), "{}", escaped);
+            assert_eq!(unescape(&escaped).unwrap(), key.to_vec());
+        }
+        assert_eq!(escape(b"z\x00\xff"), "z\\x00\\xff");
+
+        for s in &["\\", "\\x1", "\\xzz", "\\a", "a\\x\u{2764}"] {
+            assert!(unescape(s).is_err(), "{} should be invalid", s);
+        }
+    }
+
     #[test]
     fn test_to_socket_addr() {
         let tbls = vec![
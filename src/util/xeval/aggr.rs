@@ -0,0 +1,414 @@
+// Copyright 2016 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::mem;
+
+use tipb::expression::ExprType;
+
+use util::codec::{Datum, Decimal};
+use super::{Result, Error};
+
+/// The default separator of `GROUP_CONCAT`.
+pub const DEFAULT_GROUP_CONCAT_SEP: &'static [u8] = b",";
+/// The default max length of the `GROUP_CONCAT` result, same as MySQL's
+/// `group_concat_max_len`.
+pub const DEFAULT_GROUP_CONCAT_MAX_LEN: usize = 1024;
+
+/// `Aggregator` folds the values of a column over rows into a single datum.
+pub trait Aggregator {
+    /// Feed the value of a row.
+    fn update(&mut self, value: Datum) -> Result<()>;
+
+    /// Get the result of all the values fed so far, the aggregator is reset
+    /// so that it can be reused for the next group.
+    fn finish(&mut self) -> Datum;
+}
+
+/// Create an aggregator for the aggregate function `tp`.
+pub fn build_aggregator(tp: ExprType) -> Result<Box<Aggregator>> {
+    match tp {
+        ExprType::Count => Ok(box Count::default()),
+        ExprType::Sum => Ok(box Sum::default()),
+        ExprType::Avg => Ok(box Avg::default()),
+        ExprType::GroupConcat => Ok(box GroupConcat::default()),
+        ExprType::Max => Ok(box Extremum::new(Ordering::Greater)),
+        ExprType::Min => Ok(box Extremum::new(Ordering::Less)),
+        tp => Err(Error::Expr(format!("{:?} is not a supported aggregate function", tp))),
+    }
+}
+
+/// `Count` counts the values that are not null.
+#[derive(Default)]
+pub struct Count {
+    count: i64,
+}
+
+impl Aggregator for Count {
+    fn update(&mut self, value: Datum) -> Result<()> {
+        if value != Datum::Null {
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Datum {
+        Datum::I64(mem::replace(&mut self.count, 0))
+    }
+}
+
+/// `Sum` adds up the values that are not null.
+///
+/// Integers and decimals are summed as a decimal, everything else is summed
+/// as a float. The sum of no value is null.
+#[derive(Default)]
+pub struct Sum {
+    sum: Option<Datum>,
+}
+
+impl Aggregator for Sum {
+    fn update(&mut self, value: Datum) -> Result<()> {
+        let value = match value {
+            Datum::Null => return Ok(()),
+            Datum::I64(i) => Datum::Decimal(Decimal::from_i64(i)),
+            Datum::U64(u) => Datum::Decimal(try!(Decimal::from_u64(u))),
+            Datum::Decimal(d) => Datum::Decimal(d),
+            d => Datum::F64(try!(d.into_f64())),
+        };
+        let sum = match (self.sum.take(), value) {
+            (None, v) => v,
+            (Some(Datum::Decimal(l)), Datum::Decimal(r)) => {
+                match l.checked_add(&r) {
+                    Some(d) => Datum::Decimal(d),
+                    None => return Err(Error::Eval(format!("sum overflows in {} + {}", l, r))),
+                }
+            }
+            (Some(l), r) => Datum::F64(try!(l.into_f64()) + try!(r.into_f64())),
+        };
+        self.sum = Some(sum);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Datum {
+        self.sum.take().unwrap_or(Datum::Null)
+    }
+}
+
+/// `Avg` is the average of the values that are not null.
+///
+/// The average is a decimal if there is any decimal value, otherwise it's a
+/// float. The average of no value is null.
+#[derive(Default)]
+pub struct Avg {
+    sum: Sum,
+    count: i64,
+    has_decimal: bool,
+}
+
+impl Aggregator for Avg {
+    fn update(&mut self, value: Datum) -> Result<()> {
+        match value {
+            Datum::Null => return Ok(()),
+            Datum::Decimal(_) => self.has_decimal = true,
+            _ => {}
+        }
+        try!(self.sum.update(value));
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Datum {
+        let count = mem::replace(&mut self.count, 0);
+        let has_decimal = mem::replace(&mut self.has_decimal, false);
+        match self.sum.finish() {
+            Datum::Decimal(d) => {
+                if has_decimal {
+                    if let Some(avg) = d.checked_div(&Decimal::from_i64(count)) {
+                        return Datum::Decimal(avg);
+                    }
+                }
+                Datum::F64(d.to_f64() / count as f64)
+            }
+            Datum::F64(f) => Datum::F64(f / count as f64),
+            _ => Datum::Null,
+        }
+    }
+}
+
+/// `GroupConcat` joins the values that are not null with a separator.
+///
+/// The result is truncated to `max_len` bytes, and it's null if there is
+/// no value.
+pub struct GroupConcat {
+    sep: Vec<u8>,
+    max_len: usize,
+    res: Option<Vec<u8>>,
+}
+
+impl GroupConcat {
+    pub fn new(sep: &[u8], max_len: usize) -> GroupConcat {
+        GroupConcat {
+            sep: sep.to_vec(),
+            max_len: max_len,
+            res: None,
+        }
+    }
+}
+
+impl Default for GroupConcat {
+    fn default() -> GroupConcat {
+        GroupConcat::new(DEFAULT_GROUP_CONCAT_SEP, DEFAULT_GROUP_CONCAT_MAX_LEN)
+    }
+}
+
+impl Aggregator for GroupConcat {
+    fn update(&mut self, value: Datum) -> Result<()> {
+        if value == Datum::Null {
+            return Ok(());
+        }
+        let mut bs = try!(value.into_bytes());
+        if let Some(ref mut res) = self.res {
+            // the remaining values are useless once the limit is reached.
+            if res.len() < self.max_len {
+                res.extend_from_slice(&self.sep);
+                res.extend_from_slice(&bs);
+                res.truncate(self.max_len);
+            }
+            return Ok(());
+        }
+        bs.truncate(self.max_len);
+        self.res = Some(bs);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Datum {
+        self.res.take().map_or(Datum::Null, Datum::Bytes)
+    }
+}
+
+/// `Extremum` keeps the max value if `ord` is `Greater` or the min value
+/// if `ord` is `Less`, nulls are ignored.
+pub struct Extremum {
+    ord: Ordering,
+    value: Datum,
+}
+
+impl Extremum {
+    pub fn new(ord: Ordering) -> Extremum {
+        Extremum {
+            ord: ord,
+            value: Datum::Null,
+        }
+    }
+}
+
+impl Aggregator for Extremum {
+    fn update(&mut self, value: Datum) -> Result<()> {
+        if value == Datum::Null {
+            return Ok(());
+        }
+        if self.value == Datum::Null || try!(value.cmp(&self.value)) == self.ord {
+            self.value = value;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Datum {
+        mem::replace(&mut self.value, Datum::Null)
+    }
+}
+
+/// `DistinctAggregator` feeds only the values not seen before to the inner
+/// aggregator, which is how `COUNT(DISTINCT ...)` and the like work.
+///
+/// Values are deduplicated by `Datum`'s `Eq`, so values of different types
+/// like `I64(1)` and `U64(1)` are considered distinct.
+pub struct DistinctAggregator {
+    inner: Box<Aggregator>,
+    seen: HashSet<Datum>,
+}
+
+impl DistinctAggregator {
+    pub fn new(inner: Box<Aggregator>) -> DistinctAggregator {
+        DistinctAggregator {
+            inner: inner,
+            seen: HashSet::new(),
+        }
+    }
+}
+
+impl Aggregator for DistinctAggregator {
+    fn update(&mut self, value: Datum) -> Result<()> {
+        if value == Datum::Null || self.seen.contains(&value) {
+            return Ok(());
+        }
+        self.seen.insert(value.clone());
+        self.inner.update(value)
+    }
+
+    fn finish(&mut self) -> Datum {
+        self.seen.clear();
+        self.inner.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::i64;
+
+    use tipb::expression::ExprType;
+
+    use util::codec::{Datum, Decimal};
+
+    fn dec(s: &str) -> Datum {
+        Datum::Decimal(Decimal::from_bytes(s.as_bytes()).unwrap())
+    }
+
+    fn aggregate(tp: ExprType, values: Vec<Datum>) -> Datum {
+        let mut aggr = build_aggregator(tp).unwrap();
+        for v in values {
+            aggr.update(v).unwrap();
+        }
+        aggr.finish()
+    }
+
+    #[test]
+    fn test_aggregators() {
+        let tests = vec![
+            (ExprType::Count, vec![Datum::I64(1), Datum::Null, b"a".as_ref().into()],
+             Datum::I64(2)),
+            (ExprType::Count, vec![Datum::Null], Datum::I64(0)),
+            (ExprType::Count, vec![], Datum::I64(0)),
+            (ExprType::Sum, vec![Datum::I64(1), Datum::Null, Datum::I64(-3)], dec("-2")),
+            (ExprType::Sum, vec![Datum::I64(1), Datum::U64(2), dec("0.5")], dec("3.5")),
+            (ExprType::Sum, vec![Datum::I64(1), Datum::F64(1.5)], Datum::F64(2.5)),
+            (ExprType::Sum, vec![Datum::F64(1.5), Datum::I64(1)], Datum::F64(2.5)),
+            (ExprType::Sum, vec![b"1.5".as_ref().into(), Datum::I64(1)], Datum::F64(2.5)),
+            (ExprType::Sum, vec![Datum::Null, Datum::Null], Datum::Null),
+            (ExprType::Sum, vec![], Datum::Null),
+            (ExprType::Max, vec![Datum::I64(1), Datum::Null, Datum::I64(3), Datum::I64(2)],
+             Datum::I64(3)),
+            (ExprType::Max, vec![Datum::I64(1), Datum::F64(1.5), Datum::U64(1)], Datum::F64(1.5)),
+            (ExprType::Min, vec![Datum::Null, Datum::I64(1), Datum::I64(-3), Datum::I64(2)],
+             Datum::I64(-3)),
+            (ExprType::Min, vec![b"b".as_ref().into(), b"a".as_ref().into()],
+             b"a".as_ref().into()),
+            (ExprType::Max, vec![Datum::Null], Datum::Null),
+            (ExprType::Min, vec![], Datum::Null),
+        ];
+        for (tp, values, expect) in tests {
+            let res = aggregate(tp, values.clone());
+            assert!(res == expect, "{:?} of {:?}: {:?} != {:?}", tp, values, res, expect);
+        }
+    }
+
+    #[test]
+    fn test_avg() {
+        let tests = vec![
+            (vec![Datum::I64(1), Datum::Null, Datum::I64(2)], Datum::F64(1.5)),
+            (vec![Datum::I64(1), Datum::I64(2), Datum::Null, Datum::I64(4)],
+             Datum::F64(7f64 / 3f64)),
+            (vec![Datum::U64(3), Datum::Null], Datum::F64(3f64)),
+            (vec![Datum::I64(1), Datum::F64(2.5)], Datum::F64(1.75)),
+            (vec![dec("1.5"), Datum::I64(2), Datum::Null], dec("1.75")),
+            (vec![dec("1"), dec("2"), dec("2")], dec("1.6666")),
+            (vec![Datum::Null, Datum::Null], Datum::Null),
+            (vec![], Datum::Null),
+        ];
+        for (values, expect) in tests {
+            let res = aggregate(ExprType::Avg, values.clone());
+            assert!(res == expect, "avg of {:?}: {:?} != {:?}", values, res, expect);
+        }
+    }
+
+    #[test]
+    fn test_group_concat() {
+        let values = vec![b"a".as_ref().into(), Datum::Null, Datum::I64(12), dec("1.5")];
+        assert_eq!(aggregate(ExprType::GroupConcat, values.clone()), b"a,12,1.5".as_ref().into());
+        assert_eq!(aggregate(ExprType::GroupConcat, vec![Datum::Null, Datum::Null]),
+                   Datum::Null);
+        assert_eq!(aggregate(ExprType::GroupConcat, vec![]), Datum::Null);
+        assert_eq!(aggregate(ExprType::GroupConcat, vec![b"".as_ref().into()]),
+                   b"".as_ref().into());
+
+        let tests = vec![
+            (b"; ".as_ref(), 100, b"a; 12; 1.5".as_ref()),
+            (b"".as_ref(), 100, b"a121.5".as_ref()),
+            (b"; ".as_ref(), 5, b"a; 12".as_ref()),
+            (b"; ".as_ref(), 4, b"a; 1".as_ref()),
+            (b"; ".as_ref(), 0, b"".as_ref()),
+        ];
+        for (sep, max_len, expect) in tests {
+            let mut aggr = GroupConcat::new(sep, max_len);
+            for v in values.clone() {
+                aggr.update(v).unwrap();
+            }
+            assert_eq!(aggr.finish(), expect.into());
+        }
+    }
+
+    #[test]
+    fn test_distinct() {
+        let values = vec![Datum::I64(1),
+                          Datum::I64(2),
+                          Datum::Null,
+                          Datum::I64(1),
+                          Datum::I64(3),
+                          Datum::Null,
+                          Datum::I64(2)];
+        let tests = vec![
+            (ExprType::Count, Datum::I64(3)),
+            (ExprType::Sum, dec("6")),
+            (ExprType::Avg, Datum::F64(2f64)),
+            (ExprType::Max, Datum::I64(3)),
+        ];
+        for (tp, expect) in tests {
+            let mut aggr = DistinctAggregator::new(build_aggregator(tp).unwrap());
+            for v in values.clone() {
+                aggr.update(v).unwrap();
+            }
+            assert!(aggr.finish() == expect, "{:?}", tp);
+            // the seen values are forgotten after finishing a group.
+            aggr.update(Datum::I64(1)).unwrap();
+            aggr.update(Datum::I64(1)).unwrap();
+            assert!(aggr.finish() != expect, "{:?}", tp);
+        }
+
+        let mut aggr = DistinctAggregator::new(build_aggregator(ExprType::Count).unwrap());
+        for v in vec![Datum::Null, Datum::Null] {
+            aggr.update(v).unwrap();
+        }
+        assert_eq!(aggr.finish(), Datum::I64(0));
+    }
+
+    #[test]
+    fn test_aggregator_reuse() {
+        let mut aggr = build_aggregator(ExprType::Sum).unwrap();
+        aggr.update(Datum::I64(1)).unwrap();
+        assert_eq!(aggr.finish(), dec("1"));
+        assert_eq!(aggr.finish(), Datum::Null);
+        aggr.update(Datum::I64(2)).unwrap();
+        assert_eq!(aggr.finish(), dec("2"));
+
+        let mut aggr = build_aggregator(ExprType::Sum).unwrap();
+        aggr.update(Datum::I64(i64::MAX)).unwrap();
+        let big = Datum::Decimal(Decimal::new(i64::MAX, 0));
+        assert!(aggr.update(big).is_err());
+
+        assert!(build_aggregator(ExprType::Plus).is_err());
+    }
+}
@@ -12,28 +12,239 @@
 // limitations under the License.
 
 
-use util::codec::{number, Datum, datum};
+use util::codec::{number, Datum, Decimal, Time, Duration, datum};
 use util::TryInsertWith;
 use super::{Result, Error};
+use super::like::LikeMatcher;
+use super::json;
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::cmp::Ordering;
+use std::{i64, u64, usize, mem};
+use std::fmt::Display;
 use std::ascii::AsciiExt;
 use tipb::expression::{Expr, ExprType};
+use protobuf::RepeatedField;
+
+/// The collation id of MySQL binary collation.
+pub const BINARY_COLLATION_ID: i32 = 63;
+
+// the ids of `utf8mb4_bin`, `latin1_bin` and `utf8_bin`, which compare
+// bytes as is too.
+const BIN_COLLATION_IDS: &'static [i32] = &[46, 47, 83];
+
+/// `Collation` decides how strings are compared.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Collation {
+    /// ASCII letters are compared case insensitively, it's the default.
+    CaseInsensitive,
+    /// Bytes are compared as is.
+    Binary,
+}
+
+impl Collation {
+    /// Get the collation from a MySQL collation id.
+    pub fn from_id(id: i32) -> Collation {
+        if id == BINARY_COLLATION_ID || BIN_COLLATION_IDS.contains(&id) {
+            Collation::Binary
+        } else {
+            Collation::CaseInsensitive
+        }
+    }
+}
+
+impl Default for Collation {
+    fn default() -> Collation {
+        Collation::CaseInsensitive
+    }
+}
+
+/// The default max nesting depth of expressions, see `Evaluator::max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+const TOO_DEEP: &'static str = "expression nesting too deep";
+
+fn is_too_deep(e: &Error) -> bool {
+    match *e {
+        Error::Expr(ref msg) => msg == TOO_DEEP,
+        _ => false,
+    }
+}
+
+/// The expr types `Evaluator::eval` implements, keep it in sync with `eval`.
+const SUPPORTED_EXPR_TYPES: &'static [ExprType] = &[
+    ExprType::Null,
+    ExprType::Int64,
+    ExprType::Uint64,
+    ExprType::String,
+    ExprType::Bytes,
+    ExprType::MysqlDecimal,
+    ExprType::MysqlTime,
+    ExprType::MysqlDuration,
+    ExprType::ColumnRef,
+    ExprType::LT,
+    ExprType::LE,
+    ExprType::EQ,
+    ExprType::NE,
+    ExprType::GE,
+    ExprType::GT,
+    ExprType::NullEQ,
+    ExprType::And,
+    ExprType::Or,
+    ExprType::Not,
+    ExprType::Like,
+    ExprType::In,
+    ExprType::Length,
+    ExprType::Lower,
+    ExprType::Upper,
+    ExprType::Concat,
+    ExprType::Trim,
+    ExprType::LTrim,
+    ExprType::RTrim,
+    ExprType::Substring,
+    ExprType::Locate,
+    ExprType::Replace,
+    ExprType::Left,
+    ExprType::Right,
+    ExprType::JsonExtract,
+    ExprType::Coalesce,
+    ExprType::IfNull,
+    ExprType::Case,
+    ExprType::Year,
+    ExprType::Month,
+    ExprType::DayOfMonth,
+    ExprType::Plus,
+    ExprType::Minus,
+    ExprType::Mul,
+    ExprType::Div,
+    ExprType::IntDiv,
+    ExprType::Mod,
+    ExprType::Neg,
+    ExprType::Pow,
+    ExprType::Sqrt,
+    ExprType::Sign,
+];
 
 /// `Evaluator` evaluates `tipb::Expr`.
-#[derive(Default)]
 pub struct Evaluator {
     // column_id -> column_value
     pub row: HashMap<i64, Datum>,
+    // column_id -> collation, columns not in it use the default collation.
+    pub collations: HashMap<i64, Collation>,
     // expr pointer -> value list
     cached_value_list: HashMap<isize, Vec<Datum>>,
+    // expr pointer -> like matcher of a constant pattern
+    cached_like_matcher: HashMap<isize, LikeMatcher>,
+    // In strict mode an integer overflow is an error, otherwise the result
+    // is saturated and a warning is recorded, just like MySQL's sql_mode.
+    // Compiled expressions are always evaluated in strict mode.
+    pub in_strict_mode: bool,
+    warnings: Vec<Error>,
+    // The max nesting depth of expressions, deeper expressions are rejected
+    // instead of overflowing the stack.
+    pub max_depth: usize,
+    depth: usize,
+}
+
+impl Default for Evaluator {
+    fn default() -> Evaluator {
+        Evaluator {
+            row: HashMap::default(),
+            collations: HashMap::default(),
+            cached_value_list: HashMap::default(),
+            cached_like_matcher: HashMap::default(),
+            in_strict_mode: true,
+            warnings: vec![],
+            max_depth: DEFAULT_MAX_DEPTH,
+            depth: 0,
+        }
+    }
 }
 
 impl Evaluator {
+    /// Get the expr types that can be evaluated, expressions of other types
+    /// shouldn't be pushed down.
+    pub fn supported_expr_types() -> &'static [ExprType] {
+        SUPPORTED_EXPR_TYPES
+    }
+
+    /// Check if expr type `tp` can be evaluated, see `supported_expr_types`.
+    pub fn is_supported(tp: ExprType) -> bool {
+        SUPPORTED_EXPR_TYPES.contains(&tp)
+    }
+
+    /// Check that expr is well-formed without evaluating it: every type is
+    /// supported, has the right number of children, and its literal payload
+    /// can be decoded. The first error found is returned.
+    pub fn validate(expr: &Expr) -> Result<()> {
+        let mut stack = vec![expr];
+        while let Some(e) = stack.pop() {
+            try!(validate_literal(e));
+            let (min, max) = try!(expr_arity(e));
+            let cnt = e.get_children().len();
+            if cnt < min || cnt > max {
+                let expected = if min == max {
+                    format!("{}", min)
+                } else if max == usize::MAX {
+                    format!("at least {}", min)
+                } else {
+                    format!("{} to {}", min, max)
+                };
+                return Err(Error::Expr(format!("{:?} need {} operands, got {}",
+                                               e.get_tp(),
+                                               expected,
+                                               cnt)));
+            }
+            match e.get_tp() {
+                ExprType::In => {
+                    let value_list = &e.get_children()[1];
+                    if value_list.get_tp() != ExprType::ValueList {
+                        return Err(Error::Expr("the second children should be value list type"
+                            .to_owned()));
+                    }
+                    try!(datum::decode(value_list.get_val()));
+                    stack.push(&e.get_children()[0]);
+                }
+                _ => stack.extend(e.get_children()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Take the warnings recorded so far in non-strict mode.
+    pub fn take_warnings(&mut self) -> Vec<Error> {
+        mem::replace(&mut self.warnings, vec![])
+    }
+
     /// Eval evaluates expr to a Datum.
+    ///
+    /// Errors are wrapped with the path to the failing expression, like
+    /// `in And -> EQ: column 7 not found`, use `Error::root_cause` to get the
+    /// original error. Errors of too deep expressions are not wrapped, whose
+    /// path would be as long as the limit.
     pub fn eval(&mut self, expr: &Expr) -> Result<Datum> {
+        try!(self.enter());
+        let res = self.eval_expr(expr);
+        self.depth -= 1;
+        if expr.get_children().is_empty() {
+            return res;
+        }
+        res.map_err(|e| if is_too_deep(&e) { e } else { e.context(expr.get_tp()) })
+    }
+
+    // Increase the nesting depth, an error is returned if it exceeds the limit.
+    fn enter(&mut self) -> Result<()> {
+        if self.depth >= self.max_depth {
+            return Err(Error::Expr(TOO_DEEP.to_owned()));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn eval_expr(&mut self, expr: &Expr) -> Result<Datum> {
         match expr.get_tp() {
+            ExprType::Null => Ok(Datum::Null),
             ExprType::Int64 => self.eval_int(expr),
             ExprType::Uint64 => self.eval_uint(expr),
             // maybe we should use take here?
@@ -52,8 +263,46 @@ impl Evaluator {
             ExprType::Like => self.eval_like(expr),
             ExprType::Float32 |
             ExprType::Float64 => unimplemented!(),
-            ExprType::In => self.eval_in(expr),
-            _ => Ok(Datum::Null),
+            ExprType::MysqlDecimal => self.eval_decimal(expr),
+            ExprType::MysqlTime => self.eval_time(expr),
+            ExprType::MysqlDuration => self.eval_duration(expr),
+            ExprType::In => self.eval_in(expr, false),
+            ExprType::Length => self.eval_length(expr),
+            ExprType::Lower => self.eval_lower(expr),
+            ExprType::Upper => self.eval_upper(expr),
+            ExprType::Concat => self.eval_concat(expr),
+            ExprType::Trim => self.eval_trim(expr, true, true),
+            ExprType::LTrim => self.eval_trim(expr, true, false),
+            ExprType::RTrim => self.eval_trim(expr, false, true),
+            ExprType::Substring => self.eval_substring(expr),
+            ExprType::Locate => self.eval_locate(expr),
+            ExprType::Replace => self.eval_replace(expr),
+            ExprType::Left => self.eval_left_right(expr, true),
+            ExprType::Right => self.eval_left_right(expr, false),
+            ExprType::JsonExtract => self.eval_json_extract(expr),
+            ExprType::Coalesce => self.eval_coalesce(expr),
+            ExprType::Case => self.eval_case(expr),
+            ExprType::IfNull => {
+                if expr.get_children().len() != 2 {
+                    return Err(Error::Expr(format!("IFNULL need 2 operands, got {}",
+                                                   expr.get_children().len())));
+                }
+                self.eval_coalesce(expr)
+            }
+            ExprType::Year => self.eval_time_unit(expr, |t| t.year() as i64),
+            ExprType::Month => self.eval_time_unit(expr, |t| t.month() as i64),
+            ExprType::DayOfMonth => self.eval_time_unit(expr, |t| t.day() as i64),
+            ExprType::Plus => self.eval_arith(expr, arith_plus),
+            ExprType::Minus => self.eval_arith(expr, arith_minus),
+            ExprType::Mul => self.eval_arith(expr, arith_mul),
+            ExprType::Div => self.eval_arith(expr, arith_div),
+            ExprType::IntDiv => self.eval_arith(expr, arith_int_div),
+            ExprType::Mod => self.eval_arith(expr, arith_mod),
+            ExprType::Neg => self.eval_neg(expr),
+            ExprType::Pow => self.eval_pow(expr),
+            ExprType::Sqrt => self.eval_sqrt(expr),
+            ExprType::Sign => self.eval_sign(expr),
+            tp => Err(Error::Expr(format!("unsupported expr type {:?}", tp))),
         }
     }
 
@@ -67,9 +316,28 @@ impl Evaluator {
         Ok(Datum::U64(u))
     }
 
+    fn eval_decimal(&self, expr: &Expr) -> Result<Datum> {
+        let d = try!(Decimal::from_bytes(expr.get_val()));
+        Ok(Datum::Decimal(d))
+    }
+
+    fn eval_time(&self, expr: &Expr) -> Result<Datum> {
+        let u = try!(number::decode_u64(expr.get_val()));
+        Ok(Datum::Time(try!(Time::from_packed_u64(u))))
+    }
+
+    fn eval_duration(&self, expr: &Expr) -> Result<Datum> {
+        let i = try!(number::decode_i64(expr.get_val()));
+        Ok(Datum::Duration(try!(Duration::from_nanos(i))))
+    }
+
     fn eval_column_ref(&self, expr: &Expr) -> Result<Datum> {
+        self.column_ref(expr).map(clone_column)
+    }
+
+    fn column_ref(&self, expr: &Expr) -> Result<&Datum> {
         let i = try!(number::decode_i64(expr.get_val()));
-        self.row.get(&i).cloned().ok_or_else(|| Error::Eval(format!("column {} not found", i)))
+        self.row.get(&i).ok_or(Error::ColumnNotFound(i))
     }
 
     fn eval_lt(&mut self, expr: &Expr) -> Result<Datum> {
@@ -103,17 +371,23 @@ impl Evaluator {
     }
 
     fn eval_null_eq(&mut self, expr: &Expr) -> Result<Datum> {
-        let (left, right) = try!(self.eval_two_children(expr));
-        let cmp = try!(left.cmp(&right));
+        let (left, right) = try!(self.eval_two_operands(expr));
+        let cmp = try!((*left).cmp(&right));
         Ok((cmp == Ordering::Equal).into())
     }
 
     fn cmp_children(&mut self, expr: &Expr) -> Result<Option<Ordering>> {
-        let (left, right) = try!(self.eval_two_children(expr));
-        if left == Datum::Null || right == Datum::Null {
+        let (left, right) = try!(self.eval_two_operands(expr));
+        if *left == Datum::Null || *right == Datum::Null {
             return Ok(None);
         }
-        left.cmp(&right).map(Some).map_err(From::from)
+        cmp_datum(&left, &right).map(Some).map_err(|e| {
+            let children = expr.get_children();
+            Error::Eval(format!("cannot compare {} with {}: {}",
+                                describe_operand(&children[0], &left),
+                                describe_operand(&children[1], &right),
+                                e))
+        })
     }
 
     fn eval_two_children(&mut self, expr: &Expr) -> Result<(Datum, Datum)> {
@@ -127,25 +401,76 @@ impl Evaluator {
         Ok((left, right))
     }
 
+    /// Like `eval_two_children`, but column values are borrowed from the row
+    /// instead of cloned, which matters for large bytes columns that are only
+    /// read, like operands of comparisons.
+    fn eval_two_operands<'a>(&'a mut self,
+                             expr: &Expr)
+                             -> Result<(Cow<'a, Datum>, Cow<'a, Datum>)> {
+        let l = expr.get_children().len();
+        if l != 2 {
+            return Err(Error::Expr(format!("need 2 operands but got {}", l)));
+        }
+        let children = expr.get_children();
+        // evaluate the owned operands first, so the row can be borrowed after.
+        let mut owned = Vec::with_capacity(2);
+        for child in children {
+            if child.get_tp() == ExprType::ColumnRef {
+                owned.push(None);
+            } else {
+                owned.push(Some(try!(self.eval(child))));
+            }
+        }
+        let this = &*self;
+        let mut operands = owned.into_iter().zip(children).map(|(d, child)| {
+            match d {
+                Some(d) => Ok(Cow::Owned(d)),
+                None => this.column_ref(child).map(Cow::Borrowed),
+            }
+        });
+        let left = try!(operands.next().unwrap());
+        let right = try!(operands.next().unwrap());
+        Ok((left, right))
+    }
+
+    fn eval_one_child(&mut self, expr: &Expr) -> Result<Datum> {
+        let l = expr.get_children().len();
+        if l != 1 {
+            return Err(Error::Expr(format!("need 1 operand but got {}", l)));
+        }
+        self.eval(&expr.get_children()[0])
+    }
+
     fn eval_and(&mut self, expr: &Expr) -> Result<Datum> {
-        self.eval_two_children_as_bool(expr)
-            .map(|p| {
-                match p {
-                    (Some(true), Some(true)) => true.into(),
-                    (Some(false), _) | (_, Some(false)) => false.into(),
-                    _ => Datum::Null,
-                }
-            })
+        self.eval_logic(expr, false)
     }
 
     fn eval_or(&mut self, expr: &Expr) -> Result<Datum> {
-        self.eval_two_children_as_bool(expr).map(|p| {
-            match p {
-                (Some(true), _) | (_, Some(true)) => true.into(),
-                (Some(false), Some(false)) => false.into(),
-                _ => Datum::Null,
+        self.eval_logic(expr, true)
+    }
+
+    /// Evaluate an n-ary AND (`short_circuit` is false) or OR (`short_circuit`
+    /// is true), the remaining children are skipped once a child evaluates
+    /// to `short_circuit`.
+    fn eval_logic(&mut self, expr: &Expr, short_circuit: bool) -> Result<Datum> {
+        let children = expr.get_children();
+        if children.len() < 2 {
+            return Err(Error::Expr(format!("need at least 2 operands but got {}",
+                                           children.len())));
+        }
+        let mut has_null = false;
+        for child in children {
+            match try!(eval_into_bool(try!(self.eval(child)))) {
+                Some(b) if b == short_circuit => return Ok(b.into()),
+                Some(_) => {}
+                None => has_null = true,
             }
-        })
+        }
+        if has_null {
+            Ok(Datum::Null)
+        } else {
+            Ok((!short_circuit).into())
+        }
     }
 
     fn eval_not(&mut self, expr: &Expr) -> Result<Datum> {
@@ -153,7 +478,11 @@ impl Evaluator {
         if children_cnt != 1 {
             return Err(Error::Expr(format!("expect 1 operand, got {}", children_cnt)));
         }
-        let d = try!(self.eval(&expr.get_children()[0]));
+        let child = &expr.get_children()[0];
+        if child.get_tp() == ExprType::In {
+            return self.eval_in(child, true);
+        }
+        let d = try!(self.eval(child));
         if d == Datum::Null {
             return Ok(Datum::Null);
         }
@@ -162,39 +491,374 @@ impl Evaluator {
     }
 
     fn eval_like(&mut self, expr: &Expr) -> Result<Datum> {
-        let (target, pattern) = try!(self.eval_two_children(expr));
-        if Datum::Null == target || Datum::Null == pattern {
+        let children = expr.get_children();
+        if children.len() != 2 {
+            return Err(Error::Expr(format!("need 2 operands but got {}", children.len())));
+        }
+        let target = try!(self.eval(&children[0]));
+        if Datum::Null == target {
+            return Ok(Datum::Null);
+        }
+        let binary = try!(self.collation_of(&children[0])) == Collation::Binary;
+        let pattern_expr = &children[1];
+        match pattern_expr.get_tp() {
+            ExprType::String | ExprType::Bytes => {
+                let matcher = try!(self.get_like_matcher(pattern_expr, binary));
+                Ok(try!(matcher.matches_datum(target)).into())
+            }
+            _ => {
+                let pattern = try!(self.eval(pattern_expr));
+                if Datum::Null == pattern {
+                    return Ok(Datum::Null);
+                }
+                let matcher = try!(new_like_matcher(&try!(pattern.into_bytes()), binary));
+                Ok(try!(matcher.matches_datum(target)).into())
+            }
+        }
+    }
+
+    fn get_like_matcher(&mut self, pattern_expr: &Expr, binary: bool) -> Result<&LikeMatcher> {
+        let p = pattern_expr as *const Expr as isize;
+        let matcher = try!(self.cached_like_matcher
+                               .entry(p)
+                               .or_try_insert_with(|| {
+                                   new_like_matcher(pattern_expr.get_val(), binary)
+                               }));
+        Ok(matcher)
+    }
+
+    /// Get the collation of an expression, only column references may have
+    /// a collation other than the default one.
+    fn collation_of(&self, expr: &Expr) -> Result<Collation> {
+        if expr.get_tp() != ExprType::ColumnRef {
+            return Ok(Collation::default());
+        }
+        let i = try!(number::decode_i64(expr.get_val()));
+        Ok(self.collations.get(&i).cloned().unwrap_or_else(Collation::default))
+    }
+
+    fn eval_length(&mut self, expr: &Expr) -> Result<Datum> {
+        let d = try!(self.eval_one_child(expr));
+        if d == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        let bs = try!(d.into_bytes());
+        Ok(Datum::I64(bs.len() as i64))
+    }
+
+    /// Extract a component of a time, invalid times are evaluated to null
+    /// just like MySQL does.
+    fn eval_time_unit<F>(&mut self, expr: &Expr, f: F) -> Result<Datum>
+        where F: FnOnce(&Time) -> i64
+    {
+        let d = try!(self.eval_one_child(expr));
+        let t = match d {
+            Datum::Time(t) => Some(t),
+            // time read from storage is a packed u64.
+            Datum::U64(u) => Time::from_packed_u64(u).ok(),
+            Datum::Bytes(ref bs) => Time::parse(bs).ok(),
+            _ => None,
+        };
+        match t {
+            Some(t) => Ok(Datum::I64(f(&t))),
+            None => Ok(Datum::Null),
+        }
+    }
+
+    fn eval_lower(&mut self, expr: &Expr) -> Result<Datum> {
+        let d = try!(self.eval_one_child(expr));
+        if d == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        let bs = try!(d.into_bytes());
+        Ok(Datum::Bytes(bs.to_ascii_lowercase()))
+    }
+
+    fn eval_upper(&mut self, expr: &Expr) -> Result<Datum> {
+        let d = try!(self.eval_one_child(expr));
+        if d == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        let bs = try!(d.into_bytes());
+        Ok(Datum::Bytes(bs.to_ascii_uppercase()))
+    }
+
+    /// Remove the leading and(or) trailing spaces.
+    // TODO: support trimming other strings.
+    fn eval_trim(&mut self, expr: &Expr, leading: bool, trailing: bool) -> Result<Datum> {
+        let d = try!(self.eval_one_child(expr));
+        if d == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        let bs = try!(d.into_bytes());
+        let mut start = 0;
+        let mut end = bs.len();
+        if leading {
+            while start < end && bs[start] == b' ' {
+                start += 1;
+            }
+        }
+        if trailing {
+            while end > start && bs[end - 1] == b' ' {
+                end -= 1;
+            }
+        }
+        Ok(Datum::Bytes(bs[start..end].to_vec()))
+    }
+
+    fn eval_concat(&mut self, expr: &Expr) -> Result<Datum> {
+        let mut res = vec![];
+        for child in expr.get_children() {
+            let d = try!(self.eval(child));
+            if d == Datum::Null {
+                return Ok(Datum::Null);
+            }
+            let bs = try!(d.into_bytes());
+            res.extend_from_slice(&bs);
+        }
+        Ok(Datum::Bytes(res))
+    }
+
+    fn eval_substring(&mut self, expr: &Expr) -> Result<Datum> {
+        let children = expr.get_children();
+        if children.len() != 2 && children.len() != 3 {
+            return Err(Error::Expr(format!("SUBSTRING need 2 or 3 operands, got {}",
+                                           children.len())));
+        }
+        let s = try!(self.eval(&children[0]));
+        let pos = try!(self.eval(&children[1]));
+        let len = if children.len() == 3 {
+            try!(self.eval(&children[2]))
+        } else {
+            Datum::I64(i64::MAX)
+        };
+        if s == Datum::Null || pos == Datum::Null || len == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        let bs = try!(s.into_bytes());
+        let pos = try!(pos.into_i64());
+        let len = try!(len.into_i64());
+        let total = bs.len() as i64;
+        // pos is 1-based, a negative pos counts from the end.
+        let start = if pos > 0 {
+            pos - 1
+        } else if pos < 0 {
+            total + pos
+        } else {
+            return Ok(Datum::Bytes(vec![]));
+        };
+        if start < 0 || start >= total || len <= 0 {
+            return Ok(Datum::Bytes(vec![]));
+        }
+        let end = if len > total - start {
+            total
+        } else {
+            start + len
+        };
+        Ok(Datum::Bytes(bs[start as usize..end as usize].to_vec()))
+    }
+
+    fn eval_locate(&mut self, expr: &Expr) -> Result<Datum> {
+        let (sub, s) = try!(self.eval_two_children(expr));
+        if sub == Datum::Null || s == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        let sub = try!(sub.into_bytes());
+        let s = try!(s.into_bytes());
+        if sub.is_empty() {
+            return Ok(Datum::I64(1));
+        }
+        let pos = s.windows(sub.len()).position(|w| w == &*sub).map_or(0, |p| p + 1);
+        Ok(Datum::I64(pos as i64))
+    }
+
+    // the warning list to record overflows in, None in strict mode.
+    fn overflow_warnings(&mut self) -> Option<&mut Vec<Error>> {
+        if self.in_strict_mode {
+            None
+        } else {
+            Some(&mut self.warnings)
+        }
+    }
+
+    /// Replace all occurrences of `from` with `to`, the string is unchanged
+    /// if `from` is empty.
+    fn eval_replace(&mut self, expr: &Expr) -> Result<Datum> {
+        let children = expr.get_children();
+        if children.len() != 3 {
+            return Err(Error::Expr(format!("REPLACE need 3 operands, got {}", children.len())));
+        }
+        let s = try!(self.eval(&children[0]));
+        let from = try!(self.eval(&children[1]));
+        let to = try!(self.eval(&children[2]));
+        if s == Datum::Null || from == Datum::Null || to == Datum::Null {
             return Ok(Datum::Null);
         }
-        let mut target_str = try!(target.into_string());
-        let mut pattern_str = try!(pattern.into_string());
-        if pattern_str.chars().any(|x| x.is_ascii() && x.is_alphabetic()) {
-            target_str = target_str.to_ascii_lowercase();
-            pattern_str = pattern_str.to_ascii_lowercase();
-        }
-        // for now, tidb ensures that pattern being pushed down must match ^%?[^\\_%]*%?$.
-        let len = pattern_str.len();
-        if pattern_str.starts_with('%') {
-            if pattern_str[1..].ends_with('%') {
-                Ok(target_str.contains(&pattern_str[1..len - 1]).into())
+        let bs = try!(s.into_bytes());
+        let from = try!(from.into_bytes());
+        let to = try!(to.into_bytes());
+        if from.is_empty() {
+            return Ok(Datum::Bytes(bs));
+        }
+        let mut res = Vec::with_capacity(bs.len());
+        let mut i = 0;
+        while i < bs.len() {
+            if bs[i..].starts_with(&from) {
+                res.extend_from_slice(&to);
+                i += from.len();
             } else {
-                Ok(target_str.ends_with(&pattern_str[1..]).into())
+                res.push(bs[i]);
+                i += 1;
+            }
+        }
+        Ok(Datum::Bytes(res))
+    }
+
+    /// Get the leftmost or rightmost `len` characters.
+    fn eval_left_right(&mut self, expr: &Expr, left: bool) -> Result<Datum> {
+        let (s, len) = try!(self.eval_two_children(expr));
+        if s == Datum::Null || len == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        let s = try!(s.into_string());
+        let len = try!(len.into_i64());
+        if len <= 0 {
+            return Ok(Datum::Bytes(vec![]));
+        }
+        let len = len as usize;
+        let res = if left {
+            match s.char_indices().nth(len) {
+                Some((idx, _)) => &s[..idx],
+                None => &s,
             }
-        } else if pattern_str.ends_with('%') {
-            Ok(target_str.starts_with(&pattern_str[..len - 1]).into())
         } else {
-            Ok(target_str.eq(&pattern_str).into())
+            match s.char_indices().rev().nth(len - 1) {
+                Some((idx, _)) => &s[idx..],
+                None => &s,
+            }
+        };
+        Ok(Datum::Bytes(res.as_bytes().to_vec()))
+    }
+
+    /// Extract the value at a path like `$.a[1]` from a json document, null is
+    /// returned if the path doesn't exist.
+    fn eval_json_extract(&mut self, expr: &Expr) -> Result<Datum> {
+        let (doc, path) = try!(self.eval_two_children(expr));
+        if doc == Datum::Null || path == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        let doc = match doc {
+            Datum::Json(j) => j,
+            Datum::Bytes(bs) => {
+                try!(datum::parse_json(&bs).map_err(|e| Error::Eval(format!("{}", e))))
+            }
+            d => return Err(Error::Eval(format!("{} {:?} is not json", d.kind(), d))),
+        };
+        let path = try!(path.into_string());
+        let res = try!(json::extract(&doc, &path)).cloned();
+        Ok(res.map_or(Datum::Null, Datum::Json))
+    }
+
+    /// Get the first argument that is not null, converted to the type unified
+    /// from all the arguments just like MySQL does, so that `COALESCE(1, 2.5)`
+    /// is a float. All the arguments are evaluated to decide the type.
+    fn eval_coalesce(&mut self, expr: &Expr) -> Result<Datum> {
+        let mut values = Vec::with_capacity(expr.get_children().len());
+        for child in expr.get_children() {
+            values.push(try!(self.eval(child)));
+        }
+        let kind = values.iter().fold(None, |kind, d| unify_kind(kind, d));
+        match (values.into_iter().find(|d| *d != Datum::Null), kind) {
+            (Some(d), Some(kind)) => convert_to_kind(d, kind),
+            _ => Ok(Datum::Null),
+        }
+    }
+
+    /// Evaluate searched `CASE`, the children are pairs of when and then
+    /// followed by an optional else, only the matched branch is evaluated.
+    ///
+    /// tipb can't tell a simple `CASE` from a searched one, so a simple `CASE`
+    /// has to be pushed down with `operand = value` as every when condition.
+    fn eval_case(&mut self, expr: &Expr) -> Result<Datum> {
+        let children = expr.get_children();
+        if children.len() < 2 {
+            return Err(Error::Expr(format!("CASE need at least 1 branch, got {} operands",
+                                           children.len())));
+        }
+        for branch in children.chunks(2) {
+            if branch.len() == 1 {
+                return self.eval(&branch[0]);
+            }
+            let when = try!(self.eval(&branch[0]));
+            if try!(eval_into_bool(when)).unwrap_or(false) {
+                return self.eval(&branch[1]);
+            }
         }
+        Ok(Datum::Null)
     }
 
-    fn eval_two_children_as_bool(&mut self, expr: &Expr) -> Result<(Option<bool>, Option<bool>)> {
+    fn eval_arith(&mut self, expr: &Expr, f: ArithFn) -> Result<Datum> {
         let (left, right) = try!(self.eval_two_children(expr));
-        let left_bool = try!(eval_into_bool(left));
-        let right_bool = try!(eval_into_bool(right));
-        Ok((left_bool, right_bool))
+        if left == Datum::Null || right == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        f(left, right, self.overflow_warnings())
+    }
+
+    fn eval_neg(&mut self, expr: &Expr) -> Result<Datum> {
+        let children_cnt = expr.get_children().len();
+        if children_cnt != 1 {
+            return Err(Error::Expr(format!("expect 1 operand, got {}", children_cnt)));
+        }
+        let d = try!(self.eval(&expr.get_children()[0]));
+        if d == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        arith_neg(d, self.overflow_warnings())
+    }
+
+    fn eval_pow(&mut self, expr: &Expr) -> Result<Datum> {
+        let (base, exp) = try!(self.eval_two_children(expr));
+        if base == Datum::Null || exp == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        let (base, exp) = (try!(base.into_f64()), try!(exp.into_f64()));
+        Ok(Datum::F64(base.powf(exp)))
+    }
+
+    /// The square root of a negative number is null, just like MySQL does.
+    fn eval_sqrt(&mut self, expr: &Expr) -> Result<Datum> {
+        let d = try!(self.eval_one_child(expr));
+        if d == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        let f = try!(d.into_f64());
+        if f < 0f64 {
+            return Ok(Datum::Null);
+        }
+        Ok(Datum::F64(f.sqrt()))
+    }
+
+    /// Evaluate to -1, 0 or 1 according to the sign of the operand.
+    fn eval_sign(&mut self, expr: &Expr) -> Result<Datum> {
+        let d = try!(self.eval_one_child(expr));
+        if d == Datum::Null {
+            return Ok(Datum::Null);
+        }
+        // compare with zero directly so that large decimals and unsigned
+        // integers don't lose precision.
+        let sign = match try!(d.cmp(&Datum::I64(0))) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        };
+        Ok(Datum::I64(sign))
     }
 
-    fn eval_in(&mut self, expr: &Expr) -> Result<Datum> {
+    /// Evaluate `IN`, or `NOT IN` if `negated` is set. tipb has no dedicated
+    /// `NOT IN` type, it's pushed down as `Not` over `In`. Just like `IN`, `NOT IN`
+    /// is null instead of true if the target is not found in a list containing null.
+    fn eval_in(&mut self, expr: &Expr, negated: bool) -> Result<Datum> {
         if expr.get_children().len() != 2 {
             return Err(Error::Expr(format!("IN need 2 operand, got {}",
                                            expr.get_children().len())));
@@ -209,13 +873,8 @@ impl Evaluator {
             return Err(Error::Expr("the second children should be value list type".to_owned()));
         }
         let decoded = try!(self.decode_value_list(value_list_expr));
-        if try!(check_in(target, decoded)) {
-            return Ok(true.into());
-        }
-        if decoded.first().map_or(false, |d| *d == Datum::Null) {
-            return Ok(Datum::Null);
-        }
-        Ok(false.into())
+        let res = try!(check_in(&target, decoded));
+        Ok(res.map(|b| b != negated).into())
     }
 
     fn decode_value_list(&mut self, value_list_expr: &Expr) -> Result<&Vec<Datum>> {
@@ -225,224 +884,2632 @@ impl Evaluator {
                                .or_try_insert_with(|| datum::decode(value_list_expr.get_val())));
         Ok(decoded)
     }
-}
 
-/// eval datum into bool, if expr is Null, then None is return.
-fn eval_into_bool(datum: Datum) -> Result<Option<bool>> {
-    if datum == Datum::Null {
-        Ok(None)
-    } else {
-        let b = try!(datum.into_bool());
-        Ok(Some(b))
+    /// Fold every subtree without column references into a literal expr, so that
+    /// it's not evaluated again for every row.
+    ///
+    /// Subtrees whose value has no literal form, like floats, are kept as is.
+    pub fn fold_constants(&mut self, expr: &Expr) -> Result<Expr> {
+        if expr.get_children().is_empty() {
+            return Ok(expr.clone());
+        }
+        if !has_column_ref(expr) {
+            let d = try!(self.eval(expr));
+            if let Some(literal) = try!(datum_to_expr(d)) {
+                return Ok(literal);
+            }
+        }
+        let mut children = Vec::with_capacity(expr.get_children().len());
+        for child in expr.get_children() {
+            children.push(try!(self.fold_constants(child)));
+        }
+        let mut folded = Expr::new();
+        folded.set_tp(expr.get_tp());
+        folded.set_val(expr.get_val().to_vec());
+        folded.set_children(RepeatedField::from_vec(children));
+        Ok(folded)
     }
-}
 
-/// Check if `target` is in `value_list`.
-fn check_in(target: Datum, value_list: &[Datum]) -> Result<bool> {
-    let mut err = None;
-    let pos = value_list.binary_search_by(|d| {
-        match d.cmp(&target) {
-            Ok(ord) => ord,
-            Err(e) => {
-                err = Some(e);
-                Ordering::Less
+    /// Collapse chains of nested `And`s or `Or`s into single n-ary nodes, so
+    /// that they are evaluated without deep recursion.
+    pub fn flatten_logic(expr: &Expr) -> Expr {
+        let tp = expr.get_tp();
+        let is_logic = tp == ExprType::And || tp == ExprType::Or;
+        let mut children = Vec::with_capacity(expr.get_children().len());
+        for child in expr.get_children() {
+            let mut flattened = Evaluator::flatten_logic(child);
+            if is_logic && flattened.get_tp() == tp {
+                children.extend(flattened.take_children().into_iter());
+            } else {
+                children.push(flattened);
             }
         }
-    });
-    if let Some(e) = err {
-        return Err(e.into());
+        let mut res = expr.clone();
+        res.set_children(RepeatedField::from_vec(children));
+        res
     }
-    Ok(pos.is_ok())
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use util::codec::{Datum, number, datum};
+    /// Build a column reference by name for callers that only have a schema,
+    /// the name is translated into its column id according to `columns`.
+    ///
+    /// tipb has no expr type referring to a column by name, so names have to
+    /// be resolved when the expression is built.
+    pub fn column_ref(name: &str, columns: &HashMap<String, i64>) -> Result<Expr> {
+        let id = match columns.get(name) {
+            Some(&id) => id,
+            None => return Err(Error::Expr(format!("unknown column {:?}", name))),
+        };
+        let mut buf = vec![0; 8];
+        try!(number::encode_i64(&mut buf, id));
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::ColumnRef);
+        expr.set_val(buf);
+        Ok(expr)
+    }
 
-    use tipb::expression::{Expr, ExprType};
-    use protobuf::RepeatedField;
+    /// Compile expr into a `CompiledExpr` for evaluating over many rows.
+    ///
+    /// Subtrees without column references are evaluated only once here.
+    pub fn compile(&mut self, expr: &Expr) -> Result<CompiledExpr> {
+        if !has_column_ref(expr) {
+            let d = try!(self.eval(expr));
+            return Ok(CompiledExpr::Constant(d));
+        }
+        try!(self.enter());
+        let res = self.compile_expr(expr);
+        self.depth -= 1;
+        res
+    }
 
-    fn datum_expr(datum: Datum) -> Expr {
-        let mut expr = Expr::new();
-        match datum {
-            Datum::I64(i) => {
-                expr.set_tp(ExprType::Int64);
-                let mut buf = vec![0; 8];
-                number::encode_i64(&mut buf, i).unwrap();
-                expr.set_val(buf);
+    fn compile_expr(&mut self, expr: &Expr) -> Result<CompiledExpr> {
+        let children = expr.get_children();
+        let compiled = match expr.get_tp() {
+            ExprType::ColumnRef => {
+                let i = try!(number::decode_i64(expr.get_val()));
+                CompiledExpr::ColumnRef(i)
+            }
+            tp @ ExprType::LT | tp @ ExprType::LE | tp @ ExprType::EQ | tp @ ExprType::NE |
+            tp @ ExprType::GE | tp @ ExprType::GT => {
+                let (l, r) = try!(self.compile_two_children(expr));
+                match (tp, l, r) {
+                    (ExprType::EQ, CompiledExpr::ColumnRef(i), CompiledExpr::Constant(d)) |
+                    (ExprType::EQ, CompiledExpr::Constant(d), CompiledExpr::ColumnRef(i)) => {
+                        CompiledExpr::ColumnEq(i, d)
+                    }
+                    (tp, l, r) => CompiledExpr::Compare(tp, box l, box r),
+                }
+            }
+            ExprType::NullEQ => {
+                let (l, r) = try!(self.compile_two_children(expr));
+                CompiledExpr::NullEQ(box l, box r)
+            }
+            ExprType::And => CompiledExpr::And(try!(self.compile_logic_children(expr))),
+            ExprType::Or => CompiledExpr::Or(try!(self.compile_logic_children(expr))),
+            ExprType::Not => {
+                if children.len() != 1 {
+                    return Err(Error::Expr(format!("expect 1 operand, got {}", children.len())));
+                }
+                if children[0].get_tp() == ExprType::In {
+                    try!(self.compile_in(&children[0], true))
+                } else {
+                    CompiledExpr::Not(box try!(self.compile(&children[0])))
+                }
+            }
+            ExprType::In => try!(self.compile_in(expr, false)),
+            ExprType::Like => {
+                if children.len() != 2 {
+                    return Err(Error::Expr(format!("need 2 operands but got {}", children.len())));
+                }
+                let pattern = &children[1];
+                if pattern.get_tp() != ExprType::String && pattern.get_tp() != ExprType::Bytes {
+                    return Err(Error::Expr("LIKE pattern must be a constant".to_owned()));
+                }
+                let binary = try!(self.collation_of(&children[0])) == Collation::Binary;
+                let matcher = try!(new_like_matcher(pattern.get_val(), binary));
+                CompiledExpr::Like(box try!(self.compile(&children[0])), matcher)
+            }
+            ExprType::Plus => try!(self.compile_arith(expr, arith_plus)),
+            ExprType::Minus => try!(self.compile_arith(expr, arith_minus)),
+            ExprType::Mul => try!(self.compile_arith(expr, arith_mul)),
+            ExprType::Div => try!(self.compile_arith(expr, arith_div)),
+            ExprType::IntDiv => try!(self.compile_arith(expr, arith_int_div)),
+            ExprType::Mod => try!(self.compile_arith(expr, arith_mod)),
+            tp => return Err(Error::Expr(format!("{:?} can't be compiled", tp))),
+        };
+        Ok(compiled)
+    }
+
+    fn compile_two_children(&mut self, expr: &Expr) -> Result<(CompiledExpr, CompiledExpr)> {
+        let l = expr.get_children().len();
+        if l != 2 {
+            return Err(Error::Expr(format!("need 2 operands but got {}", l)));
+        }
+        let children = expr.get_children();
+        let left = try!(self.compile(&children[0]));
+        let right = try!(self.compile(&children[1]));
+        Ok((left, right))
+    }
+
+    fn compile_in(&mut self, expr: &Expr, negated: bool) -> Result<CompiledExpr> {
+        let children = expr.get_children();
+        if children.len() != 2 || children[1].get_tp() != ExprType::ValueList {
+            return Err(Error::Expr("IN need a target and a value list".to_owned()));
+        }
+        let target = try!(self.compile(&children[0]));
+        let list = try!(self.decode_value_list(&children[1])).clone();
+        Ok(CompiledExpr::In(box target, list, negated))
+    }
+
+    fn compile_logic_children(&mut self, expr: &Expr) -> Result<Vec<CompiledExpr>> {
+        let children = expr.get_children();
+        if children.len() < 2 {
+            return Err(Error::Expr(format!("need at least 2 operands but got {}",
+                                           children.len())));
+        }
+        children.iter().map(|c| self.compile(c)).collect()
+    }
+
+    fn compile_arith(&mut self, expr: &Expr, f: ArithFn) -> Result<CompiledExpr> {
+        let (l, r) = try!(self.compile_two_children(expr));
+        Ok(CompiledExpr::Arith(box l, box r, f))
+    }
+}
+
+/// An arithmetic operation, overflows are recorded in the warning list
+/// if it's given, otherwise they are errors.
+type ArithFn = fn(Datum, Datum, Option<&mut Vec<Error>>) -> Result<Datum>;
+
+/// `CompiledExpr` is an expression prepared by `Evaluator::compile`.
+pub enum CompiledExpr {
+    Constant(Datum),
+    ColumnRef(i64),
+    Compare(ExprType, Box<CompiledExpr>, Box<CompiledExpr>),
+    NullEQ(Box<CompiledExpr>, Box<CompiledExpr>),
+    And(Vec<CompiledExpr>),
+    Or(Vec<CompiledExpr>),
+    Not(Box<CompiledExpr>),
+    // `column = constant`, the most common filter, compared without dispatch.
+    ColumnEq(i64, Datum),
+    // the flag is set for `NOT IN`.
+    In(Box<CompiledExpr>, Vec<Datum>, bool),
+    Like(Box<CompiledExpr>, LikeMatcher),
+    Arith(Box<CompiledExpr>, Box<CompiledExpr>, ArithFn),
+}
+
+impl CompiledExpr {
+    /// Eval evaluates self to a Datum against the row, which maps column_id
+    /// to column_value.
+    pub fn eval(&self, row: &HashMap<i64, Datum>) -> Result<Datum> {
+        match *self {
+            CompiledExpr::Constant(ref d) => Ok(d.clone()),
+            CompiledExpr::ColumnRef(i) => {
+                row.get(&i).map(clone_column).ok_or(Error::ColumnNotFound(i))
+            }
+            CompiledExpr::Compare(tp, ref l, ref r) => {
+                let (left, right) = (try!(l.eval_cow(row)), try!(r.eval_cow(row)));
+                if *left == Datum::Null || *right == Datum::Null {
+                    return Ok(Datum::Null);
+                }
+                let ord = try!(cmp_datum(&left, &right));
+                Ok(ordering_matches(tp, ord).into())
+            }
+            CompiledExpr::ColumnEq(i, ref d) => {
+                let col = try!(row.get(&i).ok_or(Error::ColumnNotFound(i)));
+                if *col == Datum::Null || *d == Datum::Null {
+                    return Ok(Datum::Null);
+                }
+                Ok((try!(cmp_datum(col, d)) == Ordering::Equal).into())
+            }
+            CompiledExpr::NullEQ(ref l, ref r) => {
+                let (left, right) = (try!(l.eval_cow(row)), try!(r.eval_cow(row)));
+                let ord = try!((*left).cmp(&right));
+                Ok((ord == Ordering::Equal).into())
+            }
+            CompiledExpr::And(ref children) => eval_compiled_logic(children, row, false),
+            CompiledExpr::Or(ref children) => eval_compiled_logic(children, row, true),
+            CompiledExpr::Not(ref c) => {
+                match try!(eval_into_bool(try!(c.eval(row)))) {
+                    Some(b) => Ok((!b).into()),
+                    None => Ok(Datum::Null),
+                }
+            }
+            CompiledExpr::In(ref target, ref list, negated) => {
+                let target = try!(target.eval_cow(row));
+                if *target == Datum::Null {
+                    return Ok(Datum::Null);
+                }
+                let res = try!(check_in(&target, list));
+                Ok(res.map(|b| b != negated).into())
+            }
+            CompiledExpr::Like(ref target, ref matcher) => {
+                let target = try!(target.eval(row));
+                if target == Datum::Null {
+                    return Ok(Datum::Null);
+                }
+                Ok(try!(matcher.matches_datum(target)).into())
+            }
+            CompiledExpr::Arith(ref l, ref r, f) => {
+                let (left, right) = (try!(l.eval(row)), try!(r.eval(row)));
+                if left == Datum::Null || right == Datum::Null {
+                    return Ok(Datum::Null);
+                }
+                f(left, right, None)
+            }
+        }
+    }
+
+    /// Like `eval`, but constants and column values are borrowed instead of
+    /// cloned.
+    pub fn eval_cow<'a>(&'a self, row: &'a HashMap<i64, Datum>) -> Result<Cow<'a, Datum>> {
+        match *self {
+            CompiledExpr::Constant(ref d) => Ok(Cow::Borrowed(d)),
+            CompiledExpr::ColumnRef(i) => {
+                row.get(&i).map(Cow::Borrowed).ok_or(Error::ColumnNotFound(i))
+            }
+            _ => self.eval(row).map(Cow::Owned),
+        }
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    // how many times column values are cloned out of rows in this thread.
+    static COLUMN_CLONES: ::std::cell::Cell<usize> = ::std::cell::Cell::new(0)
+}
+
+#[cfg(test)]
+fn count_column_clone() {
+    COLUMN_CLONES.with(|c| c.set(c.get() + 1));
+}
+
+#[cfg(not(test))]
+fn count_column_clone() {}
+
+// Clone a column value out of the row, the clones are counted in tests to
+// make sure large values are borrowed where possible.
+fn clone_column(d: &Datum) -> Datum {
+    count_column_clone();
+    d.clone()
+}
+
+/// Compare two non-null operands of a comparison, both sides are compared
+/// as floats if any of them is a float, bytes are converted from their
+/// numeric prefix. Both the evaluator and compiled exprs compare with it.
+fn cmp_datum(left: &Datum, right: &Datum) -> Result<Ordering> {
+    let is_float = |d: &Datum| {
+        match *d {
+            Datum::F32(_) | Datum::F64(_) => true,
+            _ => false,
+        }
+    };
+    if !is_float(left) && !is_float(right) {
+        return left.cmp(right).map_err(From::from);
+    }
+    // convert both sides to float once instead of through every
+    // comparison path.
+    let l = try!(left.clone().into_f64());
+    let r = try!(right.clone().into_f64());
+    let ord = try!(Datum::F64(l).cmp(&Datum::F64(r)));
+    Ok(ord)
+}
+
+/// Check if the ordering of two operands satisfies comparison `tp`.
+fn ordering_matches(tp: ExprType, ord: Ordering) -> bool {
+    match tp {
+        ExprType::LT => ord == Ordering::Less,
+        ExprType::LE => ord != Ordering::Greater,
+        ExprType::EQ => ord == Ordering::Equal,
+        ExprType::NE => ord != Ordering::Equal,
+        ExprType::GE => ord != Ordering::Less,
+        ExprType::GT => ord == Ordering::Greater,
+        _ => unreachable!(),
+    }
+}
+
+fn eval_compiled_logic(children: &[CompiledExpr],
+                       row: &HashMap<i64, Datum>,
+                       short_circuit: bool)
+                       -> Result<Datum> {
+    let mut has_null = false;
+    for child in children {
+        match try!(eval_into_bool(try!(child.eval(row)))) {
+            Some(b) if b == short_circuit => return Ok(b.into()),
+            Some(_) => {}
+            None => has_null = true,
+        }
+    }
+    if has_null {
+        Ok(Datum::Null)
+    } else {
+        Ok((!short_circuit).into())
+    }
+}
+
+/// Check that the payload of a literal or column ref expr can be decoded.
+fn validate_literal(expr: &Expr) -> Result<()> {
+    let val = expr.get_val();
+    match expr.get_tp() {
+        ExprType::Int64 | ExprType::ColumnRef | ExprType::MysqlDuration => {
+            let i = try!(number::decode_i64(val));
+            if expr.get_tp() == ExprType::MysqlDuration {
+                try!(Duration::from_nanos(i));
+            }
+        }
+        ExprType::Uint64 => {
+            try!(number::decode_u64(val));
+        }
+        ExprType::MysqlTime => {
+            let u = try!(number::decode_u64(val));
+            try!(Time::from_packed_u64(u));
+        }
+        ExprType::MysqlDecimal => {
+            try!(Decimal::from_bytes(val));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Get the minimum and maximum number of children an expr takes, an error
+/// is returned if the expr can't be evaluated at all.
+fn expr_arity(expr: &Expr) -> Result<(usize, usize)> {
+    let arity = match expr.get_tp() {
+        ExprType::Null | ExprType::Int64 | ExprType::Uint64 | ExprType::String |
+        ExprType::Bytes | ExprType::MysqlDecimal | ExprType::MysqlTime |
+        ExprType::MysqlDuration | ExprType::ColumnRef => (0, 0),
+        ExprType::Not | ExprType::Length | ExprType::Lower | ExprType::Upper |
+        ExprType::Trim | ExprType::LTrim | ExprType::RTrim | ExprType::Year |
+        ExprType::Month | ExprType::DayOfMonth | ExprType::Neg | ExprType::Sqrt |
+        ExprType::Sign => (1, 1),
+        ExprType::LT | ExprType::LE | ExprType::EQ | ExprType::NE | ExprType::GE |
+        ExprType::GT | ExprType::NullEQ | ExprType::Like | ExprType::In | ExprType::Locate |
+        ExprType::Left | ExprType::Right | ExprType::JsonExtract |
+        ExprType::IfNull | ExprType::Plus | ExprType::Minus | ExprType::Mul | ExprType::Div |
+        ExprType::IntDiv | ExprType::Mod | ExprType::Pow => (2, 2),
+        ExprType::Replace => (3, 3),
+        ExprType::Substring => (2, 3),
+        ExprType::And | ExprType::Or | ExprType::Case => (2, usize::MAX),
+        ExprType::Concat | ExprType::Coalesce => (0, usize::MAX),
+        tp => return Err(Error::Expr(format!("unsupported expr type {:?}", tp))),
+    };
+    Ok(arity)
+}
+
+/// Visit expr and all its descendants in pre-order, children are visited
+/// in order. It's iterative so that deep expressions can't overflow the stack.
+pub fn walk_expr<F: FnMut(&Expr)>(expr: &Expr, visitor: &mut F) {
+    let mut stack = vec![expr];
+    while let Some(e) = stack.pop() {
+        visitor(e);
+        stack.extend(e.get_children().iter().rev());
+    }
+}
+
+/// Collect the ids of the columns referenced by expr, sorted and deduplicated.
+/// Column refs that can't be decoded are skipped, evaluating them fails anyway.
+pub fn collect_column_ids(expr: &Expr) -> Vec<i64> {
+    let mut ids = vec![];
+    walk_expr(expr, &mut |e: &Expr| {
+        if e.get_tp() != ExprType::ColumnRef {
+            return;
+        }
+        if let Ok(id) = number::decode_i64(e.get_val()) {
+            ids.push(id);
+        }
+    });
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// Check if expr references any column, it's iterative so that deep
+/// expressions can't overflow the stack.
+fn has_column_ref(expr: &Expr) -> bool {
+    let mut stack = vec![expr];
+    while let Some(e) = stack.pop() {
+        if e.get_tp() == ExprType::ColumnRef {
+            return true;
+        }
+        stack.extend(e.get_children());
+    }
+    false
+}
+
+/// Convert datum into a literal expr, None is returned if it has no literal form.
+fn datum_to_expr(d: Datum) -> Result<Option<Expr>> {
+    let mut expr = Expr::new();
+    match d {
+        Datum::Null => expr.set_tp(ExprType::Null),
+        Datum::I64(i) => {
+            expr.set_tp(ExprType::Int64);
+            let mut buf = vec![0; 8];
+            try!(number::encode_i64(&mut buf, i));
+            expr.set_val(buf);
+        }
+        Datum::U64(u) => {
+            expr.set_tp(ExprType::Uint64);
+            let mut buf = vec![0; 8];
+            try!(number::encode_u64(&mut buf, u));
+            expr.set_val(buf);
+        }
+        Datum::Bytes(bs) => {
+            expr.set_tp(ExprType::Bytes);
+            expr.set_val(bs);
+        }
+        Datum::Decimal(d) => {
+            expr.set_tp(ExprType::MysqlDecimal);
+            expr.set_val(format!("{}", d).into_bytes());
+        }
+        Datum::Time(t) => {
+            expr.set_tp(ExprType::MysqlTime);
+            let mut buf = vec![0; 8];
+            try!(number::encode_u64(&mut buf, t.to_packed_u64()));
+            expr.set_val(buf);
+        }
+        Datum::Duration(d) => {
+            expr.set_tp(ExprType::MysqlDuration);
+            let mut buf = vec![0; 8];
+            try!(number::encode_i64(&mut buf, d.to_nanos()));
+            expr.set_val(buf);
+        }
+        // TODO: fold floats after float literals can be evaluated.
+        Datum::F32(_) | Datum::F64(_) | Datum::Json(_) | Datum::Min | Datum::Max => {
+            return Ok(None)
+        }
+    }
+    Ok(Some(expr))
+}
+
+/// Create a LIKE matcher, patterns of binary collation are matched byte by
+/// byte, so that binary data which is not valid UTF-8 can be matched too.
+fn new_like_matcher(pattern: &[u8], binary: bool) -> Result<LikeMatcher> {
+    if binary {
+        Ok(LikeMatcher::binary(pattern.to_vec()))
+    } else {
+        LikeMatcher::from_bytes(pattern, false)
+    }
+}
+
+/// eval datum into bool, if expr is Null, then None is return.
+fn eval_into_bool(datum: Datum) -> Result<Option<bool>> {
+    if datum == Datum::Null {
+        Ok(None)
+    } else {
+        let b = try!(datum.into_bool());
+        Ok(Some(b))
+    }
+}
+
+/// The operand types an arithmetic operation is evaluated on.
+enum ArithOperands {
+    Int(i64, i64),
+    Uint(u64, u64),
+    // a negative integer and an unsigned one, the result is unsigned.
+    IntUint(i64, u64),
+    UintInt(u64, i64),
+    Dec(Decimal, Decimal),
+    Float(f64, f64),
+}
+
+/// Decide the type an arithmetic operation works on, see `Datum::coerce_numeric_pair`.
+fn arith_operands(left: Datum, right: Datum) -> Result<ArithOperands> {
+    match try!(left.coerce_numeric_pair(right)) {
+        (Datum::I64(l), Datum::I64(r)) => Ok(ArithOperands::Int(l, r)),
+        (Datum::U64(l), Datum::U64(r)) => Ok(ArithOperands::Uint(l, r)),
+        (Datum::I64(l), Datum::U64(r)) => Ok(ArithOperands::IntUint(l, r)),
+        (Datum::U64(l), Datum::I64(r)) => Ok(ArithOperands::UintInt(l, r)),
+        (Datum::Decimal(l), Datum::Decimal(r)) => Ok(ArithOperands::Dec(l, r)),
+        (Datum::F64(l), Datum::F64(r)) => Ok(ArithOperands::Float(l, r)),
+        (l, r) => Err(Error::Eval(format!("unexpected operands {:?} and {:?}", l, r))),
+    }
+}
+
+fn overflow_err(op: &str, l: &Display, r: &Display) -> Error {
+    Error::Eval(format!("{} overflows in {} {} {}", op, l, op, r))
+}
+
+/// `on_overflow` returns the error in strict mode, otherwise records it as
+/// a warning and returns the saturated value.
+fn on_overflow(warnings: Option<&mut Vec<Error>>, err: Error, saturated: Datum) -> Result<Datum> {
+    match warnings {
+        Some(warnings) => {
+            warnings.push(err);
+            Ok(saturated)
+        }
+        None => Err(err),
+    }
+}
+
+// the saturated value of an overflowed signed operation, which is decided
+// by the sign of the exact result.
+fn saturated_i64(negative: bool) -> Datum {
+    Datum::I64(if negative { i64::MIN } else { i64::MAX })
+}
+
+// the absolute value of a negative integer, which never overflows in u64.
+fn neg_abs(i: i64) -> u64 {
+    (i as u64).wrapping_neg()
+}
+
+/// Date arithmetic: a time shifted by a duration is a time, and the
+/// difference of two times is a duration. None is returned for other types.
+fn arith_time(left: &Datum, right: &Datum, minus: bool) -> Option<Result<Datum>> {
+    let res = match (left, right, minus) {
+        (&Datum::Time(ref t), &Datum::Duration(ref d), false) |
+        (&Datum::Duration(ref d), &Datum::Time(ref t), false) => {
+            t.checked_add(d).map(Datum::Time).ok_or_else(|| overflow_err("+", t, d))
+        }
+        (&Datum::Time(ref t), &Datum::Duration(ref d), true) => {
+            t.checked_sub(d).map(Datum::Time).ok_or_else(|| overflow_err("-", t, d))
+        }
+        (&Datum::Time(ref l), &Datum::Time(ref r), true) => {
+            l.checked_sub_time(r).map(Datum::Duration).ok_or_else(|| overflow_err("-", l, r))
+        }
+        _ => return None,
+    };
+    Some(res)
+}
+
+fn arith_plus(left: Datum, right: Datum, warnings: Option<&mut Vec<Error>>) -> Result<Datum> {
+    if let Some(res) = arith_time(&left, &right, false) {
+        return res;
+    }
+    match try!(arith_operands(left, right)) {
+        ArithOperands::Int(l, r) => {
+            match l.checked_add(r) {
+                Some(v) => Ok(Datum::I64(v)),
+                None => on_overflow(warnings, overflow_err("+", &l, &r), saturated_i64(l < 0)),
+            }
+        }
+        ArithOperands::Uint(l, r) => {
+            match l.checked_add(r) {
+                Some(v) => Ok(Datum::U64(v)),
+                None => on_overflow(warnings, overflow_err("+", &l, &r), Datum::U64(u64::MAX)),
+            }
+        }
+        ArithOperands::IntUint(l, r) => {
+            match r.checked_sub(neg_abs(l)) {
+                Some(v) => Ok(Datum::U64(v)),
+                None => on_overflow(warnings, overflow_err("+", &l, &r), Datum::U64(0)),
+            }
+        }
+        ArithOperands::UintInt(l, r) => {
+            match l.checked_sub(neg_abs(r)) {
+                Some(v) => Ok(Datum::U64(v)),
+                None => on_overflow(warnings, overflow_err("+", &l, &r), Datum::U64(0)),
+            }
+        }
+        ArithOperands::Dec(l, r) => {
+            l.checked_add(&r).map(Datum::Decimal).ok_or_else(|| overflow_err("+", &l, &r))
+        }
+        ArithOperands::Float(l, r) => Ok(Datum::F64(l + r)),
+    }
+}
+
+fn arith_minus(left: Datum, right: Datum, warnings: Option<&mut Vec<Error>>) -> Result<Datum> {
+    if let Some(res) = arith_time(&left, &right, true) {
+        return res;
+    }
+    match try!(arith_operands(left, right)) {
+        ArithOperands::Int(l, r) => {
+            match l.checked_sub(r) {
+                Some(v) => Ok(Datum::I64(v)),
+                None => on_overflow(warnings, overflow_err("-", &l, &r), saturated_i64(l < 0)),
+            }
+        }
+        ArithOperands::Uint(l, r) => {
+            match l.checked_sub(r) {
+                Some(v) => Ok(Datum::U64(v)),
+                None => on_overflow(warnings, overflow_err("-", &l, &r), Datum::U64(0)),
+            }
+        }
+        // a negative number minus an unsigned one is always negative.
+        ArithOperands::IntUint(l, r) => {
+            on_overflow(warnings, overflow_err("-", &l, &r), Datum::U64(0))
+        }
+        ArithOperands::UintInt(l, r) => {
+            match l.checked_add(neg_abs(r)) {
+                Some(v) => Ok(Datum::U64(v)),
+                None => on_overflow(warnings, overflow_err("-", &l, &r), Datum::U64(u64::MAX)),
+            }
+        }
+        ArithOperands::Dec(l, r) => {
+            l.checked_sub(&r).map(Datum::Decimal).ok_or_else(|| overflow_err("-", &l, &r))
+        }
+        ArithOperands::Float(l, r) => Ok(Datum::F64(l - r)),
+    }
+}
+
+fn arith_mul(left: Datum, right: Datum, warnings: Option<&mut Vec<Error>>) -> Result<Datum> {
+    match try!(arith_operands(left, right)) {
+        ArithOperands::Int(l, r) => {
+            match l.checked_mul(r) {
+                Some(v) => Ok(Datum::I64(v)),
+                None => {
+                    let saturated = saturated_i64((l < 0) != (r < 0));
+                    on_overflow(warnings, overflow_err("*", &l, &r), saturated)
+                }
+            }
+        }
+        ArithOperands::Uint(l, r) => {
+            match l.checked_mul(r) {
+                Some(v) => Ok(Datum::U64(v)),
+                None => on_overflow(warnings, overflow_err("*", &l, &r), Datum::U64(u64::MAX)),
+            }
+        }
+        // the product is negative unless the unsigned one is zero.
+        ArithOperands::IntUint(_, 0) | ArithOperands::UintInt(0, _) => Ok(Datum::U64(0)),
+        ArithOperands::IntUint(l, r) => {
+            on_overflow(warnings, overflow_err("*", &l, &r), Datum::U64(0))
+        }
+        ArithOperands::UintInt(l, r) => {
+            on_overflow(warnings, overflow_err("*", &l, &r), Datum::U64(0))
+        }
+        ArithOperands::Dec(l, r) => {
+            l.checked_mul(&r).map(Datum::Decimal).ok_or_else(|| overflow_err("*", &l, &r))
+        }
+        ArithOperands::Float(l, r) => Ok(Datum::F64(l * r)),
+    }
+}
+
+/// `arith_neg` evaluates unary minus. Negating an unsigned integer results
+/// in a signed one.
+fn arith_neg(d: Datum, warnings: Option<&mut Vec<Error>>) -> Result<Datum> {
+    let neg_err = |d: &Display| Error::Eval(format!("- overflows in -{}", d));
+    match d {
+        Datum::I64(i) => {
+            match i.checked_neg() {
+                Some(v) => Ok(Datum::I64(v)),
+                None => on_overflow(warnings, neg_err(&i), Datum::I64(i64::MAX)),
+            }
+        }
+        Datum::U64(u) => {
+            if u <= i64::MAX as u64 {
+                Ok(Datum::I64(-(u as i64)))
+            } else if u == i64::MAX as u64 + 1 {
+                Ok(Datum::I64(i64::MIN))
+            } else {
+                on_overflow(warnings, neg_err(&u), Datum::I64(i64::MIN))
+            }
+        }
+        Datum::F32(f) => Ok(Datum::F64(-(f as f64))),
+        Datum::F64(f) => Ok(Datum::F64(-f)),
+        Datum::Decimal(d) => {
+            d.value()
+             .checked_neg()
+             .map(|v| Datum::Decimal(Decimal::new(v, d.frac())))
+             .ok_or_else(|| neg_err(&d))
+        }
+        Datum::Bytes(bs) => Ok(Datum::F64(-try!(Datum::Bytes(bs).into_f64()))),
+        d => Err(Error::Eval(format!("can't negate {} {:?}", d.kind(), d))),
+    }
+}
+
+/// `arith_div` returns null when dividing by zero, just like MySQL does.
+/// The quotient of integers is a decimal.
+fn arith_div(left: Datum, right: Datum, _: Option<&mut Vec<Error>>) -> Result<Datum> {
+    let (l, r) = match try!(arith_operands(left, right)) {
+        ArithOperands::Int(l, r) => (Decimal::from_i64(l), Decimal::from_i64(r)),
+        ArithOperands::Uint(l, r) => (try!(Decimal::from_u64(l)), try!(Decimal::from_u64(r))),
+        ArithOperands::IntUint(l, r) => (Decimal::from_i64(l), try!(Decimal::from_u64(r))),
+        ArithOperands::UintInt(l, r) => (try!(Decimal::from_u64(l)), Decimal::from_i64(r)),
+        ArithOperands::Dec(l, r) => (l, r),
+        ArithOperands::Float(l, r) => {
+            if r == 0f64 {
+                return Ok(Datum::Null);
+            }
+            return Ok(Datum::F64(l / r));
+        }
+    };
+    if r.is_zero() {
+        return Ok(Datum::Null);
+    }
+    l.checked_div(&r).map(Datum::Decimal).ok_or_else(|| overflow_err("/", &l, &r))
+}
+
+/// `arith_int_div` is the integer division `DIV` which truncates the quotient
+/// towards zero. If any of the operands is unsigned the result is unsigned,
+/// otherwise it's signed. Dividing by zero returns null.
+fn arith_int_div(left: Datum, right: Datum, warnings: Option<&mut Vec<Error>>) -> Result<Datum> {
+    match try!(arith_operands(left, right)) {
+        ArithOperands::Int(_, 0) |
+        ArithOperands::Uint(_, 0) |
+        ArithOperands::IntUint(_, 0) => Ok(Datum::Null),
+        ArithOperands::Int(l, r) => {
+            match l.checked_div(r) {
+                Some(v) => Ok(Datum::I64(v)),
+                None => on_overflow(warnings, overflow_err("DIV", &l, &r), saturated_i64(false)),
+            }
+        }
+        ArithOperands::Uint(l, r) => Ok(Datum::U64(l / r)),
+        // the quotient is negative unless it's truncated to zero.
+        ArithOperands::IntUint(l, r) => {
+            if neg_abs(l) < r {
+                return Ok(Datum::U64(0));
+            }
+            on_overflow(warnings, overflow_err("DIV", &l, &r), Datum::U64(0))
+        }
+        ArithOperands::UintInt(l, r) => {
+            if l < neg_abs(r) {
+                return Ok(Datum::U64(0));
+            }
+            on_overflow(warnings, overflow_err("DIV", &l, &r), Datum::U64(0))
+        }
+        ArithOperands::Dec(l, r) => {
+            if r.is_zero() {
+                return Ok(Datum::Null);
+            }
+            match l.checked_int_div(&r) {
+                Some(v) => Ok(Datum::I64(v)),
+                None => {
+                    let negative = l.is_negative() != r.is_negative();
+                    on_overflow(warnings, overflow_err("DIV", &l, &r), saturated_i64(negative))
+                }
+            }
+        }
+        ArithOperands::Float(l, r) => {
+            if r == 0f64 {
+                return Ok(Datum::Null);
+            }
+            let v = (l / r).trunc();
+            // a NaN quotient has no integer value, and casting it is undefined.
+            if v.is_nan() {
+                return Ok(Datum::Null);
+            }
+            // infinite quotients overflow too.
+            if v < i64::MIN as f64 || v >= i64::MAX as f64 {
+                return on_overflow(warnings, overflow_err("DIV", &l, &r), saturated_i64(v < 0f64));
+            }
+            Ok(Datum::I64(v as i64))
+        }
+    }
+}
+
+/// `arith_mod` returns the remainder which has the sign of the dividend, the
+/// type of the result follows the operands like other arithmetic operations,
+/// except that mixing signed and unsigned integers follows the dividend.
+/// A zero divisor returns null.
+fn arith_mod(left: Datum, right: Datum, _: Option<&mut Vec<Error>>) -> Result<Datum> {
+    match try!(arith_operands(left, right)) {
+        ArithOperands::Int(_, 0) |
+        ArithOperands::Uint(_, 0) |
+        ArithOperands::IntUint(_, 0) => Ok(Datum::Null),
+        // i64::MIN % -1 overflows in rust, but the remainder is 0.
+        ArithOperands::Int(l, r) => Ok(Datum::I64(l.wrapping_rem(r))),
+        ArithOperands::Uint(l, r) => Ok(Datum::U64(l % r)),
+        // the remainder is at most 2^63, whose negation is i64::MIN.
+        ArithOperands::IntUint(l, r) => Ok(Datum::I64(((neg_abs(l) % r) as i64).wrapping_neg())),
+        ArithOperands::UintInt(l, r) => Ok(Datum::U64(l % neg_abs(r))),
+        ArithOperands::Dec(l, r) => {
+            if r.is_zero() {
+                return Ok(Datum::Null);
+            }
+            l.checked_rem(&r).map(Datum::Decimal).ok_or_else(|| overflow_err("%", &l, &r))
+        }
+        ArithOperands::Float(l, r) => {
+            if r == 0f64 {
+                return Ok(Datum::Null);
+            }
+            Ok(Datum::F64(l % r))
+        }
+    }
+}
+
+/// The type of the result of functions returning one of their arguments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ResultKind {
+    Int,
+    Uint,
+    Decimal,
+    Float,
+    String,
+    // other types are kept only if all the arguments are of the same type.
+    Other(&'static str),
+}
+
+/// Unify `kind` with the type of `d`: numbers are unified into the widest
+/// numeric type, mixing signed and unsigned integers results in decimal,
+/// and anything else mixed results in string. Nulls don't affect the type.
+fn unify_kind(kind: Option<ResultKind>, d: &Datum) -> Option<ResultKind> {
+    let k = match *d {
+        Datum::Null => return kind,
+        Datum::I64(_) => ResultKind::Int,
+        Datum::U64(_) => ResultKind::Uint,
+        Datum::Decimal(_) => ResultKind::Decimal,
+        Datum::F32(_) | Datum::F64(_) => ResultKind::Float,
+        Datum::Bytes(_) => ResultKind::String,
+        ref d => ResultKind::Other(d.kind()),
+    };
+    let kind = match kind {
+        None => return Some(k),
+        Some(kind) => kind,
+    };
+    let unified = match (kind, k) {
+        (l, r) if l == r => l,
+        (ResultKind::String, _) | (_, ResultKind::String) |
+        (ResultKind::Other(_), _) | (_, ResultKind::Other(_)) => ResultKind::String,
+        (ResultKind::Float, _) | (_, ResultKind::Float) => ResultKind::Float,
+        _ => ResultKind::Decimal,
+    };
+    Some(unified)
+}
+
+fn convert_to_kind(d: Datum, kind: ResultKind) -> Result<Datum> {
+    match kind {
+        ResultKind::Decimal => {
+            match d {
+                Datum::I64(i) => Ok(Datum::Decimal(Decimal::from_i64(i))),
+                Datum::U64(u) => Ok(Datum::Decimal(try!(Decimal::from_u64(u)))),
+                d => Ok(d),
+            }
+        }
+        ResultKind::Float => Ok(Datum::F64(try!(d.into_f64()))),
+        ResultKind::String => Ok(Datum::Bytes(try!(d.into_bytes()))),
+        ResultKind::Int | ResultKind::Uint | ResultKind::Other(_) => Ok(d),
+    }
+}
+
+/// Describe an operand for error messages, like `column 3 (bytes)` or `literal (int)`.
+fn describe_operand(expr: &Expr, d: &Datum) -> String {
+    let tp = d.kind();
+    match expr.get_tp() {
+        ExprType::ColumnRef => {
+            match number::decode_i64(expr.get_val()) {
+                Ok(id) => format!("column {} ({})", id, tp),
+                Err(_) => format!("column ({})", tp),
+            }
+        }
+        ExprType::Null | ExprType::Int64 | ExprType::Uint64 | ExprType::Float32 |
+        ExprType::Float64 | ExprType::String | ExprType::Bytes | ExprType::MysqlDecimal |
+        ExprType::MysqlTime | ExprType::MysqlDuration => {
+            format!("literal ({})", tp)
+        }
+        t => format!("{:?} ({})", t, tp),
+    }
+}
+
+fn is_nan(d: &Datum) -> bool {
+    match *d {
+        Datum::F32(f) => f.is_nan(),
+        Datum::F64(f) => f.is_nan(),
+        _ => false,
+    }
+}
+
+/// Check if `target` is in `value_list`, which needn't be sorted.
+///
+/// None is returned if `target` is not found but the list contains null.
+/// NaN can't be compared, so it never matches anything, including NaN.
+fn check_in(target: &Datum, value_list: &[Datum]) -> Result<Option<bool>> {
+    let target_is_nan = is_nan(target);
+    let mut has_null = false;
+    for d in value_list {
+        if *d == Datum::Null {
+            has_null = true;
+            continue;
+        }
+        if target_is_nan || is_nan(d) {
+            continue;
+        }
+        if try!(target.cmp(d)) == Ordering::Equal {
+            return Ok(Some(true));
+        }
+    }
+    if has_null {
+        Ok(None)
+    } else {
+        Ok(Some(false))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::COLUMN_CLONES;
+    use super::super::Error;
+    use util::codec::{Datum, Decimal, Time, Duration, number, datum};
+
+    use std::{i64, u64, f64};
+    use std::cmp::Ordering;
+    use std::collections::HashMap;
+
+    use rustc_serialize::json::Json;
+
+    use tipb::expression::{Expr, ExprType};
+    use protobuf::RepeatedField;
+
+    fn datum_expr(datum: Datum) -> Expr {
+        let mut expr = Expr::new();
+        match datum {
+            Datum::I64(i) => {
+                expr.set_tp(ExprType::Int64);
+                let mut buf = vec![0; 8];
+                number::encode_i64(&mut buf, i).unwrap();
+                expr.set_val(buf);
+            }
+            Datum::U64(u) => {
+                expr.set_tp(ExprType::Uint64);
+                let mut buf = vec![0; 8];
+                number::encode_u64(&mut buf, u).unwrap();
+                expr.set_val(buf);
+            }
+            Datum::Bytes(bs) => {
+                expr.set_tp(ExprType::Bytes);
+                expr.set_val(bs);
+            }
+            Datum::Decimal(d) => {
+                expr.set_tp(ExprType::MysqlDecimal);
+                expr.set_val(format!("{}", d).into_bytes());
+            }
+            Datum::Time(t) => {
+                expr.set_tp(ExprType::MysqlTime);
+                let mut buf = vec![0; 8];
+                number::encode_u64(&mut buf, t.to_packed_u64()).unwrap();
+                expr.set_val(buf);
+            }
+            Datum::Duration(d) => {
+                expr.set_tp(ExprType::MysqlDuration);
+                let mut buf = vec![0; 8];
+                number::encode_i64(&mut buf, d.to_nanos()).unwrap();
+                expr.set_val(buf);
+            }
+            Datum::F32(_) => unimplemented!(),
+            Datum::F64(_) => unimplemented!(),
+            _ => expr.set_tp(ExprType::Null),
+        };
+        expr
+    }
+
+    fn col_expr(col_id: i64) -> Expr {
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::ColumnRef);
+        let mut buf = vec![0; 8];
+        number::encode_i64(&mut buf, col_id).unwrap();
+        expr.set_val(buf);
+        expr
+    }
+
+    fn bin_expr(left: Datum, right: Datum, tp: ExprType) -> Expr {
+        bin_expr_r(datum_expr(left), datum_expr(right), tp)
+    }
+
+    fn bin_expr_r(left: Expr, right: Expr, tp: ExprType) -> Expr {
+        let mut expr = Expr::new();
+        expr.set_tp(tp);
+        expr.set_children(RepeatedField::from_vec(vec![left, right]));
+        expr
+    }
+
+    fn not_expr(value: Datum) -> Expr {
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::Not);
+        expr.mut_children().push(datum_expr(value));
+        expr
+    }
+
+    fn build_expr(children: Vec<Expr>, tp: ExprType) -> Expr {
+        let mut expr = Expr::new();
+        expr.set_tp(tp);
+        expr.set_children(RepeatedField::from_vec(children));
+        expr
+    }
+
+    fn str_datum(s: &'static str) -> Datum {
+        Datum::Bytes(s.as_bytes().to_vec())
+    }
+
+    fn check_eval(eval: &mut Evaluator, cases: Vec<(Expr, Datum)>) {
+        for (expr, expect) in cases {
+            let res = eval.eval(&expr);
+            if res.is_err() {
+                panic!("failed to eval {:?}: {:?}", expr, res);
+            }
+            let res = res.unwrap();
+            if res != expect {
+                panic!("failed to eval {:?} expect {:?}, got {:?}",
+                       expr,
+                       expect,
+                       res);
+            }
+        }
+    }
+
+    fn like_expr(target: &'static str, pattern: &'static str) -> Expr {
+        let target_expr = datum_expr(Datum::Bytes(target.as_bytes().to_vec()));
+        let pattern_expr = datum_expr(Datum::Bytes(pattern.as_bytes().to_vec()));
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::Like);
+        expr.mut_children().push(target_expr);
+        expr.mut_children().push(pattern_expr);
+        expr
+    }
+
+    // TODO: add more tests.
+    #[test]
+    fn test_eval() {
+        let tests = vec![
+			(datum_expr(Datum::I64(1)), Datum::I64(1)),
+			(datum_expr(Datum::U64(1)), Datum::U64(1)),
+			(datum_expr(b"abc".as_ref().into()), b"abc".as_ref().into()),
+			(datum_expr(Datum::Null), Datum::Null),
+			(col_expr(1), Datum::I64(100)),
+			(bin_expr(Datum::I64(100), Datum::I64(1), ExprType::LT), Datum::I64(0)),
+			(bin_expr(Datum::I64(1), Datum::I64(100), ExprType::LT), Datum::I64(1)),
+			(bin_expr(Datum::I64(100), Datum::Null, ExprType::LT), Datum::Null),
+			(bin_expr(Datum::I64(100), Datum::I64(1), ExprType::LE), Datum::I64(0)),
+			(bin_expr(Datum::I64(1), Datum::I64(1), ExprType::LE), Datum::I64(1)),
+			(bin_expr(Datum::I64(100), Datum::Null, ExprType::LE), Datum::Null),
+			(bin_expr(Datum::I64(100), Datum::I64(1), ExprType::EQ), Datum::I64(0)),
+			(bin_expr(Datum::I64(100), Datum::I64(100), ExprType::EQ), Datum::I64(1)),
+			(bin_expr(Datum::I64(100), Datum::Null, ExprType::EQ), Datum::Null),
+			(bin_expr(Datum::I64(100), Datum::I64(100), ExprType::NE), Datum::I64(0)),
+			(bin_expr(Datum::I64(100), Datum::I64(1), ExprType::NE), Datum::I64(1)),
+			(bin_expr(Datum::I64(100), Datum::Null, ExprType::NE), Datum::Null),
+			(bin_expr(Datum::I64(1), Datum::I64(100), ExprType::GE), Datum::I64(0)),
+			(bin_expr(Datum::I64(100), Datum::I64(100), ExprType::GE), Datum::I64(1)),
+			(bin_expr(Datum::I64(100), Datum::Null, ExprType::GE), Datum::Null),
+			(bin_expr(Datum::I64(100), Datum::I64(100), ExprType::GT), Datum::I64(0)),
+			(bin_expr(Datum::I64(100), Datum::I64(1), ExprType::GT), Datum::I64(1)),
+			(bin_expr(Datum::I64(100), Datum::Null, ExprType::GT), Datum::Null),
+			(bin_expr(Datum::I64(1), Datum::Null, ExprType::NullEQ), Datum::I64(0)),
+			(bin_expr(Datum::Null, Datum::Null, ExprType::NullEQ), Datum::I64(1)),
+			// logic operation
+			(bin_expr(Datum::I64(0), Datum::I64(1), ExprType::And), Datum::I64(0)),
+			(bin_expr(Datum::I64(1), Datum::I64(1), ExprType::And), Datum::I64(1)),
+			(bin_expr(Datum::I64(1), Datum::Null, ExprType::And), Datum::Null),
+			(bin_expr(Datum::Null, Datum::I64(0), ExprType::And), Datum::I64(0)),
+			(bin_expr(Datum::Null, Datum::Null, ExprType::And), Datum::Null),
+			(bin_expr(Datum::I64(0), Datum::I64(0), ExprType::Or), Datum::I64(0)),
+			(bin_expr(Datum::I64(0), Datum::I64(1), ExprType::Or), Datum::I64(1)),
+			(bin_expr(Datum::I64(1), Datum::Null, ExprType::Or), Datum::I64(1)),
+			(bin_expr(Datum::Null, Datum::Null, ExprType::Or), Datum::Null),
+			(bin_expr(Datum::Null, Datum::I64(0), ExprType::Or), Datum::Null),
+			(bin_expr_r(bin_expr(Datum::I64(1), Datum::I64(1), ExprType::EQ),
+			 bin_expr(Datum::I64(1), Datum::I64(1), ExprType::EQ), ExprType::And), Datum::I64(1)),
+			(not_expr(Datum::I64(1)), Datum::I64(0)),
+			(not_expr(Datum::I64(0)), Datum::I64(1)),
+			(not_expr(Datum::Null), Datum::Null),
+			(not_expr(str_datum("0")), Datum::I64(1)),
+			(not_expr(str_datum("1x")), Datum::I64(0)),
+			(bin_expr(str_datum("1"), str_datum("1"), ExprType::And), Datum::I64(1)),
+			(bin_expr(str_datum("abc"), str_datum("0"), ExprType::Or), Datum::I64(0)),
+			(bin_expr(str_datum("abc"), str_datum("2x"), ExprType::Or), Datum::I64(1)),
+            // like operation
+            (like_expr("a", ""), Datum::I64(0)),
+            (like_expr("a", "a"), Datum::I64(1)),
+            (like_expr("a", "b"), Datum::I64(0)),
+            (like_expr("aAb", "AaB"), Datum::I64(1)),
+            (like_expr("a", "%"), Datum::I64(1)),
+            (like_expr("aAD", "%d"), Datum::I64(1)),
+            (like_expr("aAeD", "%e"), Datum::I64(0)),
+            (like_expr("aAb", "Aa%"), Datum::I64(1)),
+            (like_expr("abAb", "Aa%"), Datum::I64(0)),
+            (like_expr("aAcb", "%C%"), Datum::I64(1)),
+            (like_expr("aAb", "%C%"), Datum::I64(0)),
+        ];
+
+        let mut xevaluator = Evaluator::default();
+        xevaluator.row.insert(1, Datum::I64(100));
+        for (expr, result) in tests {
+            let res = xevaluator.eval(&expr);
+            if res.is_err() {
+                panic!("failed to eval {:?}: {:?}", expr, res);
+            }
+            let res = res.unwrap();
+            if res != result {
+                panic!("failed to eval {:?} expect {:?}, got {:?}",
+                       expr,
+                       result,
+                       res);
+            }
+        }
+
+        let expr = bin_expr_r(col_expr(2), datum_expr(Datum::I64(1)), ExprType::EQ);
+        match xevaluator.eval(&expr).as_ref().map_err(Error::root_cause) {
+            Err(&Error::ColumnNotFound(2)) => {}
+            res => panic!("expect column 2 not found, got {:?}", res),
+        }
+        let compiled = xevaluator.compile(&expr).unwrap();
+        match compiled.eval(&xevaluator.row) {
+            Err(Error::ColumnNotFound(2)) => {}
+            res => panic!("expect column 2 not found, got {:?}", res),
+        }
+    }
+
+    fn in_expr(target: Datum, list: Vec<Datum>) -> Expr {
+        in_expr_r(datum_expr(target), list)
+    }
+
+    fn in_expr_r(target_expr: Expr, list: Vec<Datum>) -> Expr {
+        let val = datum::encode_value(&list).unwrap();
+        let mut list_expr = Expr::new();
+        list_expr.set_tp(ExprType::ValueList);
+        list_expr.set_val(val);
+        let mut expr = Expr::new();
+        expr.set_tp(ExprType::In);
+        expr.mut_children().push(target_expr);
+        expr.mut_children().push(list_expr);
+        expr
+    }
+
+    fn not_in_expr(target: Datum, list: Vec<Datum>) -> Expr {
+        not_in_expr_r(datum_expr(target), list)
+    }
+
+    fn not_in_expr_r(target_expr: Expr, list: Vec<Datum>) -> Expr {
+        build_expr(vec![in_expr_r(target_expr, list)], ExprType::Not)
+    }
+
+    #[test]
+    fn test_eval_null_and_unsupported() {
+        let mut eval = Evaluator::default();
+        assert_eq!(eval.eval(&datum_expr(Datum::Null)).unwrap(), Datum::Null);
+
+        let expr = build_expr(vec![datum_expr(Datum::I64(1))], ExprType::Count);
+        let err = eval.eval(&expr).unwrap_err();
+        assert!(format!("{}", err).contains("unsupported expr type Count"), "{}", err);
+    }
+
+    #[test]
+    fn test_cmp_total_order() {
+        // bytes are excluded since they are compared with each other as
+        // strings but with numbers as floats, which is inherently not transitive.
+        let mut values = vec![Datum::Null, Datum::Min, Datum::Max];
+        for &i in &[i64::MIN, i64::MIN + 1, -2, -1, 0, 1, 2, i64::MAX - 1, i64::MAX] {
+            values.push(Datum::I64(i));
+        }
+        for &u in &[0, 1, 2, i64::MAX as u64, i64::MAX as u64 + 1, u64::MAX - 1, u64::MAX] {
+            values.push(Datum::U64(u));
+        }
+        for &f in &[-9223372036854775808f64, -1.5, -1f64, -0.5, 0f64, 0.5, 1f64, 1.5, 2f64,
+                    9223372036854775808f64, 18446744073709551616f64] {
+            values.push(Datum::F64(f));
+            values.push(Datum::F32(f as f32));
+        }
+        // 0.1 as a float lies between the two decimals around it.
+        values.push(Datum::F64(0.1));
+        for s in &["-9223372036854775807", "-1.5", "-1", "-0.50", "0", "0.1",
+                   "0.100000000000000001", "0.100000000000000006", "0.5", "1.000", "1.5",
+                   "9223372036854775807", "9223372036854775.807"] {
+            values.push(dec_datum(s));
+        }
+
+        let cmp = |a: &Datum, b: &Datum| a.cmp(b).unwrap();
+        for a in &values {
+            for b in &values {
+                let ord = cmp(a, b);
+                assert!(cmp(b, a) == ord.reverse(), "{:?} vs {:?}", a, b);
+                for c in &values {
+                    let ord2 = cmp(b, c);
+                    if ord == ord2 || ord2 == Ordering::Equal {
+                        assert!(cmp(a, c) == ord, "{:?} {:?} {:?} {:?} {:?}", a, ord, b, ord2, c);
+                    } else if ord == Ordering::Equal {
+                        assert!(cmp(a, c) == ord2, "{:?} {:?} {:?} {:?} {:?}", a, ord, b, ord2, c);
+                    }
+                }
             }
-            Datum::U64(u) => {
-                expr.set_tp(ExprType::Uint64);
-                let mut buf = vec![0; 8];
-                number::encode_u64(&mut buf, u).unwrap();
-                expr.set_val(buf);
+        }
+    }
+
+    #[test]
+    fn test_where_in() {
+        let cases = vec![
+            (in_expr(Datum::I64(1), vec![Datum::I64(1), Datum::I64(2)]), Datum::I64(1)),
+            (in_expr(Datum::I64(1), vec![Datum::I64(2), Datum::Null]), Datum::Null),
+            (in_expr(Datum::Null, vec![Datum::I64(1), Datum::Null]), Datum::Null),
+            (in_expr(Datum::I64(2), vec![Datum::I64(1), Datum::Null]), Datum::Null),
+            (in_expr(Datum::I64(2), vec![]), Datum::I64(0)),
+            (in_expr(b"abc".as_ref().into(), vec![b"abc".as_ref().into(),
+             b"ab".as_ref().into()]), Datum::I64(1)),
+            (in_expr(b"abc".as_ref().into(), vec![b"aba".as_ref().into(),
+             b"bab".as_ref().into()]), Datum::I64(0)),
+            // the list is not sorted and null is not the first element.
+            (in_expr(Datum::I64(2), vec![Datum::I64(3), Datum::Null, Datum::I64(1)]),
+             Datum::Null),
+            (in_expr(Datum::I64(1), vec![Datum::I64(3), Datum::Null, Datum::I64(1)]),
+             Datum::I64(1)),
+            (in_expr(Datum::I64(3), vec![Datum::I64(3), Datum::I64(1), Datum::Null]),
+             Datum::I64(1)),
+            (in_expr(Datum::I64(4), vec![Datum::I64(3), Datum::I64(1), Datum::I64(2)]),
+             Datum::I64(0)),
+        ];
+
+        let mut eval = Evaluator::default();
+        for (expr, expect_res) in cases {
+            let res = eval.eval(&expr);
+            if res.is_err() {
+                panic!("failed to execute {:?}: {:?}", expr, res);
+            }
+            let res = res.unwrap();
+            if res != expect_res {
+                panic!("wrong result {:?}, expect {:?} while executing {:?}",
+                       res,
+                       expect_res,
+                       expr);
+            }
+        }
+    }
+
+    #[test]
+    fn test_where_in_empty_list() {
+        // some encoders emit a value list with empty or even unset val.
+        let mut unset_val = Expr::new();
+        unset_val.set_tp(ExprType::ValueList);
+        let mut empty_val = unset_val.clone();
+        empty_val.set_val(vec![]);
+
+        let mut eval = Evaluator::default();
+        for list in vec![empty_val, unset_val] {
+            let cases = vec![(false, Datum::I64(1), Datum::I64(0)),
+                             (true, Datum::I64(1), Datum::I64(1)),
+                             (false, Datum::Null, Datum::Null)];
+            for (negated, target, expect) in cases {
+                let mut expr = Expr::new();
+                expr.set_tp(ExprType::In);
+                expr.mut_children().push(datum_expr(target));
+                expr.mut_children().push(list.clone());
+                if negated {
+                    expr = build_expr(vec![expr], ExprType::Not);
+                }
+                assert_eq!(eval.eval(&expr).unwrap(), expect);
+                let compiled = eval.compile(&expr).unwrap();
+                assert_eq!(compiled.eval(&HashMap::new()).unwrap(), expect);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsigned_column() {
+        let big = i64::MAX as u64 + 1;
+        let mut row = HashMap::new();
+        // an unsigned column above i64::MAX, and a signed column.
+        row.insert(1, Datum::U64(big));
+        row.insert(2, Datum::I64(-1));
+        row.insert(3, Datum::U64(5));
+        let cases = vec![
+            (bin_expr_r(col_expr(1), datum_expr(Datum::I64(0)), ExprType::GT), Datum::I64(1)),
+            (bin_expr_r(col_expr(1), datum_expr(Datum::I64(i64::MAX)), ExprType::GT),
+             Datum::I64(1)),
+            (bin_expr_r(col_expr(1), datum_expr(Datum::I64(-1)), ExprType::LT), Datum::I64(0)),
+            (bin_expr_r(col_expr(1), datum_expr(Datum::I64(i64::MIN)), ExprType::EQ),
+             Datum::I64(0)),
+            (bin_expr_r(datum_expr(Datum::I64(0)), col_expr(1), ExprType::LT), Datum::I64(1)),
+            (bin_expr_r(col_expr(1), datum_expr(Datum::U64(big)), ExprType::EQ), Datum::I64(1)),
+            (bin_expr_r(col_expr(3), datum_expr(Datum::I64(5)), ExprType::EQ), Datum::I64(1)),
+            (bin_expr_r(col_expr(3), datum_expr(Datum::I64(5)), ExprType::NullEQ),
+             Datum::I64(1)),
+            (bin_expr_r(col_expr(3), datum_expr(Datum::I64(-5)), ExprType::GE), Datum::I64(1)),
+            // a signed column against unsigned literals and columns.
+            (bin_expr_r(col_expr(2), datum_expr(Datum::U64(0)), ExprType::LT), Datum::I64(1)),
+            (bin_expr_r(col_expr(2), datum_expr(Datum::U64(u64::MAX)), ExprType::NE),
+             Datum::I64(1)),
+            (bin_expr_r(col_expr(2), col_expr(1), ExprType::LT), Datum::I64(1)),
+            (bin_expr_r(col_expr(1), col_expr(2), ExprType::GT), Datum::I64(1)),
+            (in_expr_r(col_expr(1), vec![Datum::I64(-1), Datum::U64(big)]), Datum::I64(1)),
+            (in_expr_r(col_expr(1), vec![Datum::I64(i64::MAX), Datum::I64(-1)]),
+             Datum::I64(0)),
+            (in_expr_r(col_expr(3), vec![Datum::I64(5)]), Datum::I64(1)),
+        ];
+        let mut eval = Evaluator::default();
+        eval.row = row.clone();
+        for (expr, expect) in cases {
+            assert!(eval.eval(&expr).unwrap() == expect, "{:?}", expr);
+            let compiled = eval.compile(&expr).unwrap();
+            assert!(compiled.eval(&row).unwrap() == expect, "{:?}", expr);
+        }
+    }
+
+    #[test]
+    fn test_where_in_nan() {
+        let nan = Datum::F64(f64::NAN);
+        let cases = vec![
+            // NaN as the target.
+            (in_expr_r(col_expr(1), vec![Datum::I64(1), Datum::F64(2.5)]), Datum::I64(0)),
+            (in_expr_r(col_expr(1), vec![nan.clone()]), Datum::I64(0)),
+            (in_expr_r(col_expr(1), vec![Datum::I64(1), Datum::Null]), Datum::Null),
+            (not_in_expr_r(col_expr(1), vec![Datum::I64(1)]), Datum::I64(1)),
+            // NaN in the list.
+            (in_expr(Datum::I64(1), vec![nan.clone(), Datum::I64(1)]), Datum::I64(1)),
+            (in_expr(Datum::I64(2), vec![nan.clone(), Datum::I64(1)]), Datum::I64(0)),
+            (in_expr_r(col_expr(2), vec![nan.clone()]), Datum::I64(0)),
+            (in_expr_r(col_expr(2), vec![nan.clone(), Datum::F64(2.5)]), Datum::I64(1)),
+            (in_expr(Datum::I64(2), vec![nan.clone(), Datum::Null]), Datum::Null),
+            (not_in_expr(Datum::I64(2), vec![nan]), Datum::I64(1)),
+        ];
+        let mut row = HashMap::new();
+        row.insert(1, Datum::F64(f64::NAN));
+        row.insert(2, Datum::F64(2.5));
+        let mut eval = Evaluator::default();
+        eval.row = row.clone();
+        for (expr, expect) in cases {
+            assert_eq!(eval.eval(&expr).unwrap(), expect);
+            let compiled = eval.compile(&expr).unwrap();
+            assert_eq!(compiled.eval(&row).unwrap(), expect);
+        }
+    }
+
+    #[test]
+    fn test_where_in_mixed_types() {
+        let list = vec![Datum::I64(1), Datum::F64(2.5), Datum::F64(3.0), Datum::U64(5)];
+        let cases = vec![
+            (in_expr(Datum::I64(3), list.clone()), Datum::I64(1)),
+            (in_expr(Datum::I64(2), list.clone()), Datum::I64(0)),
+            (in_expr(Datum::U64(1), list.clone()), Datum::I64(1)),
+            (in_expr(Datum::I64(5), list.clone()), Datum::I64(1)),
+            (in_expr(Datum::I64(-1), list.clone()), Datum::I64(0)),
+            (in_expr(dec_datum("2.50"), list.clone()), Datum::I64(1)),
+            (in_expr(dec_datum("2.51"), list.clone()), Datum::I64(0)),
+            (in_expr(b"3".as_ref().into(), list.clone()), Datum::I64(1)),
+            (not_in_expr(Datum::I64(3), list.clone()), Datum::I64(0)),
+            (not_in_expr(Datum::I64(4), list), Datum::I64(1)),
+        ];
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
+    }
+
+    #[test]
+    fn test_large_bytes_column() {
+        let large = vec![b'a'; 1024 * 1024];
+        let large_copy = large.clone();
+        let mut larger = large.clone();
+        larger.push(b'b');
+        let mut eval = Evaluator::default();
+        eval.row.insert(1, Datum::Bytes(large.clone()));
+        eval.row.insert(2, Datum::Bytes(larger));
+        eval.row.insert(3, Datum::Null);
+
+        let cases = vec![
+            (bin_expr_r(col_expr(1), col_expr(1), ExprType::EQ), Datum::I64(1)),
+            (bin_expr_r(col_expr(1), col_expr(2), ExprType::LT), Datum::I64(1)),
+            (bin_expr_r(col_expr(2), datum_expr(Datum::Bytes(large)), ExprType::GT),
+             Datum::I64(1)),
+            (bin_expr_r(col_expr(1), col_expr(3), ExprType::EQ), Datum::Null),
+            (bin_expr_r(col_expr(3), col_expr(3), ExprType::NullEQ), Datum::I64(1)),
+            (bin_expr_r(bin_expr_r(col_expr(1), col_expr(1), ExprType::GE),
+                        bin_expr_r(col_expr(2), col_expr(1), ExprType::NE),
+                        ExprType::And),
+             Datum::I64(1)),
+        ];
+        let clones = || COLUMN_CLONES.with(|c| c.get());
+        for (expr, expect) in cases {
+            // comparisons only read the columns, so they are never cloned.
+            let before = clones();
+            assert_eq!(eval.eval(&expr).unwrap(), expect);
+            let compiled = eval.compile(&expr).unwrap();
+            assert_eq!(compiled.eval(&eval.row).unwrap(), expect);
+            assert_eq!(clones(), before);
+        }
+
+        // a column value returned as the result has to be cloned once.
+        let before = clones();
+        assert_eq!(eval.eval(&col_expr(1)).unwrap(), Datum::Bytes(large_copy));
+        assert_eq!(clones(), before + 1);
+
+        let err = eval.eval(&bin_expr_r(col_expr(1), col_expr(4), ExprType::EQ)).unwrap_err();
+        assert!(format!("{:?}", err).contains("ColumnNotFound"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_column_ref_by_name() {
+        let mut columns = HashMap::new();
+        columns.insert("a".to_owned(), 1);
+        columns.insert("b".to_owned(), 2);
+
+        let a = Evaluator::column_ref("a", &columns).unwrap();
+        assert_eq!(a, col_expr(1));
+        let b = Evaluator::column_ref("b", &columns).unwrap();
+        let expr = bin_expr_r(bin_expr_r(a, b, ExprType::Plus),
+                              datum_expr(Datum::I64(3)),
+                              ExprType::GT);
+
+        let mut eval = Evaluator::default();
+        eval.row.insert(1, Datum::I64(1));
+        eval.row.insert(2, Datum::I64(3));
+        assert_eq!(eval.eval(&expr).unwrap(), Datum::I64(1));
+        eval.row.insert(2, Datum::I64(2));
+        assert_eq!(eval.eval(&expr).unwrap(), Datum::I64(0));
+
+        let err = Evaluator::column_ref("c", &columns).unwrap_err();
+        assert!(format!("{}", err).contains("unknown column \"c\""), "{}", err);
+    }
+
+    #[test]
+    fn test_where_not_in() {
+        let cases = vec![
+            (not_in_expr(Datum::I64(1), vec![Datum::I64(1), Datum::I64(2)]), Datum::I64(0)),
+            (not_in_expr(Datum::I64(3), vec![Datum::I64(1), Datum::I64(2)]), Datum::I64(1)),
+            // not found in a list containing null is null rather than true.
+            (not_in_expr(Datum::I64(1), vec![Datum::I64(2), Datum::Null]), Datum::Null),
+            (not_in_expr(Datum::I64(2), vec![Datum::Null, Datum::I64(2)]), Datum::I64(0)),
+            (not_in_expr(Datum::Null, vec![Datum::I64(1)]), Datum::Null),
+            (not_in_expr(Datum::I64(2), vec![]), Datum::I64(1)),
+            (not_in_expr(b"abc".as_ref().into(),
+                         vec![b"aba".as_ref().into(), b"bab".as_ref().into()]),
+             Datum::I64(1)),
+            (not_in_expr(Datum::I64(4), vec![Datum::I64(3), Datum::I64(1), Datum::I64(2)]),
+             Datum::I64(1)),
+        ];
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
+
+        let expr = not_in_expr_r(col_expr(1), vec![Datum::I64(1), Datum::Null]);
+        let compiled = eval.compile(&expr).unwrap();
+        for (v, expect) in vec![(Datum::I64(1), Datum::I64(0)),
+                                (Datum::I64(2), Datum::Null),
+                                (Datum::Null, Datum::Null)] {
+            eval.row.insert(1, v);
+            assert_eq!(eval.eval(&expr).unwrap(), expect);
+            assert_eq!(compiled.eval(&eval.row).unwrap(), expect);
+        }
+    }
+
+    #[test]
+    fn test_string_func() {
+        let cases = vec![
+            (build_expr(vec![datum_expr(str_datum("abc"))], ExprType::Length), Datum::I64(3)),
+            (build_expr(vec![datum_expr(str_datum(""))], ExprType::Length), Datum::I64(0)),
+            (build_expr(vec![datum_expr(Datum::I64(-12))], ExprType::Length), Datum::I64(3)),
+            (build_expr(vec![datum_expr(Datum::Null)], ExprType::Length), Datum::Null),
+            (build_expr(vec![datum_expr(str_datum("aBc"))], ExprType::Upper), str_datum("ABC")),
+            (build_expr(vec![datum_expr(str_datum("aBc"))], ExprType::Lower), str_datum("abc")),
+            (build_expr(vec![datum_expr(Datum::Null)], ExprType::Upper), Datum::Null),
+            (build_expr(vec![datum_expr(str_datum("a")),
+                             datum_expr(str_datum("b")),
+                             datum_expr(str_datum("c"))],
+                        ExprType::Concat),
+             str_datum("abc")),
+            (build_expr(vec![datum_expr(str_datum("a")), datum_expr(Datum::I64(1))],
+                        ExprType::Concat),
+             str_datum("a1")),
+            (build_expr(vec![datum_expr(str_datum("a")), datum_expr(Datum::Null)],
+                        ExprType::Concat),
+             Datum::Null),
+        ];
+
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
+    }
+
+    #[test]
+    fn test_cmp_error_context() {
+        let mut eval = Evaluator::default();
+        eval.row.insert(3, Datum::F64(::std::f64::NAN));
+        let expr = bin_expr_r(col_expr(3), datum_expr(Datum::I64(1)), ExprType::LT);
+        match eval.eval(&expr).as_ref().map_err(Error::root_cause) {
+            Err(&Error::Eval(ref msg)) => {
+                assert!(msg.contains("column 3 (float)"), "unexpected message {}", msg);
+                assert!(msg.contains("literal (int)"), "unexpected message {}", msg);
+            }
+            r => panic!("expect eval error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_cmp_float() {
+        let mut eval = Evaluator::default();
+        eval.row.insert(1, Datum::F64(3.5));
+        eval.row.insert(2, Datum::F32(0.5));
+        let cases = vec![
+            (bin_expr_r(col_expr(1), datum_expr(str_datum("3.5abc")), ExprType::EQ),
+             Datum::I64(1)),
+            (bin_expr_r(datum_expr(str_datum("4e0")), col_expr(1), ExprType::GT), Datum::I64(1)),
+            (bin_expr_r(col_expr(1), datum_expr(Datum::U64(3)), ExprType::GT), Datum::I64(1)),
+            (bin_expr_r(col_expr(2), datum_expr(dec_datum("0.5")), ExprType::EQ), Datum::I64(1)),
+            (bin_expr_r(col_expr(2), col_expr(1), ExprType::LT), Datum::I64(1)),
+            (bin_expr_r(col_expr(1), datum_expr(Datum::Null), ExprType::LT), Datum::Null),
+        ];
+        check_eval(&mut eval, cases);
+    }
+
+    fn time_datum(s: &str) -> Datum {
+        Datum::Time(Time::parse(s.as_bytes()).unwrap())
+    }
+
+    fn duration_datum(s: &str) -> Datum {
+        Datum::Duration(Duration::parse(s.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_time_arith() {
+        let cases = vec![
+            (bin_expr(time_datum("2016-07-01 12:00:00"),
+                      duration_datum("13:30:00"),
+                      ExprType::Plus),
+             time_datum("2016-07-02 01:30:00")),
+            (bin_expr(duration_datum("-12:00:00"),
+                      time_datum("2016-07-01 06:00:00"),
+                      ExprType::Plus),
+             time_datum("2016-06-30 18:00:00")),
+            (bin_expr(time_datum("2016-03-01"), duration_datum("00:00:01"), ExprType::Minus),
+             time_datum("2016-02-29 23:59:59")),
+            (bin_expr(time_datum("2016-07-02 01:30:00"), time_datum("2016-07-01 12:00:00"),
+                      ExprType::Minus),
+             duration_datum("13:30:00")),
+            (bin_expr(time_datum("2016-07-01"), time_datum("2016-07-01 00:00:00.5"),
+                      ExprType::Minus),
+             duration_datum("-00:00:00.5")),
+            (bin_expr(time_datum("2016-07-01"), Datum::Null, ExprType::Plus), Datum::Null),
+            (bin_expr(Datum::Null, duration_datum("01:00:00"), ExprType::Minus), Datum::Null),
+        ];
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
+
+        let overflows = vec![
+            bin_expr(time_datum("9999-12-31 23:00:00"), duration_datum("01:00:00"), ExprType::Plus),
+            bin_expr(time_datum("0000-01-01"), duration_datum("00:00:01"), ExprType::Minus),
+            bin_expr(time_datum("2016-07-01"), time_datum("2015-07-01"), ExprType::Minus),
+        ];
+        for expr in overflows {
+            match eval.eval(&expr).as_ref().map_err(Error::root_cause) {
+                Err(&Error::Eval(_)) => {}
+                res => panic!("expect eval error, got {:?}", res),
+            }
+        }
+        // a duration can't be shifted by a time.
+        let expr = bin_expr(duration_datum("01:00:00"), time_datum("2016-07-01"), ExprType::Minus);
+        assert!(eval.eval(&expr).is_err());
+    }
+
+    #[test]
+    fn test_cmp_time() {
+        let mut eval = Evaluator::default();
+        let t = Time::parse(b"2016-07-01 12:00:00").unwrap();
+        eval.row.insert(1, Datum::Time(t));
+        // time read from storage is a packed u64.
+        eval.row.insert(2, Datum::U64(t.to_packed_u64()));
+        eval.row.insert(3, duration_datum("12:00:00"));
+        let cases = vec![
+            (bin_expr_r(col_expr(1), datum_expr(time_datum("2016-07-01")), ExprType::GT),
+             Datum::I64(1)),
+            (bin_expr_r(col_expr(1), datum_expr(time_datum("2016-07-02")), ExprType::LT),
+             Datum::I64(1)),
+            (bin_expr_r(col_expr(2), datum_expr(time_datum("2016-07-01 12:00:00")), ExprType::EQ),
+             Datum::I64(1)),
+            (bin_expr_r(col_expr(1), datum_expr(str_datum("2016-07-01 12:00:01")), ExprType::LT),
+             Datum::I64(1)),
+            (bin_expr_r(col_expr(1), datum_expr(Datum::Null), ExprType::LT), Datum::Null),
+            (bin_expr_r(col_expr(3), datum_expr(duration_datum("-12:00:00")), ExprType::GT),
+             Datum::I64(1)),
+            (bin_expr_r(col_expr(3), datum_expr(duration_datum("100:00:00")), ExprType::LT),
+             Datum::I64(1)),
+            (bin_expr_r(datum_expr(str_datum("12:00:00")), col_expr(3), ExprType::EQ),
+             Datum::I64(1)),
+        ];
+        check_eval(&mut eval, cases);
+
+        let expr = bin_expr_r(col_expr(1), col_expr(3), ExprType::EQ);
+        assert!(eval.eval(&expr).is_err());
+    }
+
+    #[test]
+    fn test_time_unit() {
+        let mut eval = Evaluator::default();
+        let t = Time::parse(b"2016-07-31 12:00:00").unwrap();
+        eval.row.insert(1, Datum::Time(t));
+        eval.row.insert(2, Datum::U64(t.to_packed_u64()));
+        let unit_expr = |child: Expr, tp: ExprType| build_expr(vec![child], tp);
+        let cases = vec![
+            (unit_expr(col_expr(1), ExprType::Year), Datum::I64(2016)),
+            (unit_expr(col_expr(1), ExprType::Month), Datum::I64(7)),
+            (unit_expr(col_expr(1), ExprType::DayOfMonth), Datum::I64(31)),
+            (unit_expr(col_expr(2), ExprType::Month), Datum::I64(7)),
+            (unit_expr(datum_expr(str_datum("1999-12-01")), ExprType::Year), Datum::I64(1999)),
+            (unit_expr(datum_expr(Datum::Null), ExprType::Year), Datum::Null),
+            (unit_expr(datum_expr(str_datum("2016-02-30")), ExprType::DayOfMonth), Datum::Null),
+            (unit_expr(datum_expr(str_datum("abc")), ExprType::Month), Datum::Null),
+            (unit_expr(datum_expr(Datum::I64(1)), ExprType::Year), Datum::Null),
+        ];
+        check_eval(&mut eval, cases);
+    }
+
+    #[test]
+    fn test_trim() {
+        let trim_expr = |s: Datum, tp: ExprType| build_expr(vec![datum_expr(s)], tp);
+        let cases = vec![
+            (trim_expr(str_datum("  ab  "), ExprType::Trim), str_datum("ab")),
+            (trim_expr(str_datum(" a b "), ExprType::Trim), str_datum("a b")),
+            (trim_expr(str_datum("   "), ExprType::Trim), str_datum("")),
+            (trim_expr(str_datum(""), ExprType::Trim), str_datum("")),
+            (trim_expr(str_datum("\tab"), ExprType::Trim), str_datum("\tab")),
+            (trim_expr(str_datum("  ab  "), ExprType::LTrim), str_datum("ab  ")),
+            (trim_expr(str_datum("  ab  "), ExprType::RTrim), str_datum("  ab")),
+            (trim_expr(Datum::I64(12), ExprType::Trim), str_datum("12")),
+            (trim_expr(Datum::Null, ExprType::Trim), Datum::Null),
+            (trim_expr(Datum::Null, ExprType::RTrim), Datum::Null),
+        ];
+
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
+    }
+
+    #[test]
+    fn test_replace() {
+        let replace_expr = |s: Datum, from: Datum, to: Datum| {
+            build_expr(vec![datum_expr(s), datum_expr(from), datum_expr(to)],
+                       ExprType::Replace)
+        };
+        let cases = vec![
+            (replace_expr(str_datum("aXbXc"), str_datum("X"), str_datum("_")),
+             str_datum("a_b_c")),
+            (replace_expr(str_datum("aaa"), str_datum("aa"), str_datum("b")), str_datum("ba")),
+            (replace_expr(str_datum("abc"), str_datum("b"), str_datum("")), str_datum("ac")),
+            (replace_expr(str_datum("abc"), str_datum("d"), str_datum("e")), str_datum("abc")),
+            (replace_expr(str_datum("abc"), str_datum(""), str_datum("e")), str_datum("abc")),
+            (replace_expr(Datum::I64(101), Datum::I64(1), str_datum("x")), str_datum("x0x")),
+            (replace_expr(Datum::Null, str_datum("a"), str_datum("b")), Datum::Null),
+            (replace_expr(str_datum("abc"), Datum::Null, str_datum("b")), Datum::Null),
+            (replace_expr(str_datum("abc"), str_datum("a"), Datum::Null), Datum::Null),
+        ];
+
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
+    }
+
+    #[test]
+    fn test_left_right() {
+        let cases = vec![
+            (bin_expr(str_datum("hello"), Datum::I64(3), ExprType::Left), str_datum("hel")),
+            (bin_expr(str_datum("hello"), Datum::I64(2), ExprType::Right), str_datum("lo")),
+            (bin_expr(str_datum("hello"), Datum::I64(10), ExprType::Left), str_datum("hello")),
+            (bin_expr(str_datum("hello"), Datum::I64(10), ExprType::Right), str_datum("hello")),
+            (bin_expr(str_datum("hello"), Datum::I64(5), ExprType::Right), str_datum("hello")),
+            (bin_expr(str_datum("hello"), Datum::I64(0), ExprType::Left), str_datum("")),
+            (bin_expr(str_datum("hello"), Datum::I64(-1), ExprType::Right), str_datum("")),
+            (bin_expr(str_datum("日本語"), Datum::I64(2), ExprType::Left), str_datum("日本")),
+            (bin_expr(str_datum("café"), Datum::I64(2), ExprType::Right), str_datum("fé")),
+            // the length is truncated like any other conversion to int.
+            (bin_expr(str_datum("hello"), dec_datum("2.7"), ExprType::Left), str_datum("he")),
+            (bin_expr(Datum::Null, Datum::I64(2), ExprType::Left), Datum::Null),
+            (bin_expr(str_datum("hello"), Datum::Null, ExprType::Right), Datum::Null),
+        ];
+
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
+    }
+
+    #[test]
+    fn test_json_extract() {
+        let mut eval = Evaluator::default();
+        let doc = Json::from_str(r#"{"a": 1, "b": [true, "x"]}"#).unwrap();
+        eval.row.insert(1, Datum::Json(doc));
+        let cases = vec![
+            (bin_expr_r(datum_expr(str_datum(r#"{"a":1}"#)),
+                        datum_expr(str_datum("$.a")),
+                        ExprType::JsonExtract),
+             Datum::Json(Json::U64(1))),
+            (bin_expr_r(col_expr(1), datum_expr(str_datum("$.b[1]")), ExprType::JsonExtract),
+             Datum::Json(Json::String("x".to_owned()))),
+            (bin_expr_r(col_expr(1), datum_expr(str_datum("$.b")), ExprType::JsonExtract),
+             Datum::Json(Json::from_str(r#"[true, "x"]"#).unwrap())),
+            (bin_expr_r(col_expr(1), datum_expr(str_datum("$.c")), ExprType::JsonExtract),
+             Datum::Null),
+            (bin_expr_r(col_expr(1), datum_expr(str_datum("$.a.b")), ExprType::JsonExtract),
+             Datum::Null),
+            (bin_expr_r(col_expr(1), datum_expr(Datum::Null), ExprType::JsonExtract),
+             Datum::Null),
+            (bin_expr_r(datum_expr(Datum::Null),
+                        datum_expr(str_datum("$.a")),
+                        ExprType::JsonExtract),
+             Datum::Null),
+        ];
+        check_eval(&mut eval, cases);
+
+        let bad = vec![(datum_expr(str_datum("{a:1}")), "$.a"),
+                       (col_expr(1), "a"),
+                       (col_expr(1), "$[x]"),
+                       (datum_expr(Datum::I64(1)), "$")];
+        for (doc, path) in bad {
+            let expr = bin_expr_r(doc, datum_expr(str_datum(path)), ExprType::JsonExtract);
+            match eval.eval(&expr).as_ref().map_err(Error::root_cause) {
+                Err(&Error::Eval(_)) => {}
+                res => panic!("{:?} should fail, got {:?}", expr, res),
+            }
+        }
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let mut eval = Evaluator::default();
+        eval.row.insert(1, Datum::Null);
+        let coalesce = |args: Vec<Expr>| build_expr(args, ExprType::Coalesce);
+        let cases = vec![
+            (coalesce(vec![col_expr(1), datum_expr(Datum::I64(1))]), Datum::I64(1)),
+            (coalesce(vec![datum_expr(Datum::I64(1)), datum_expr(Datum::I64(2))]), Datum::I64(1)),
+            (coalesce(vec![col_expr(1), datum_expr(str_datum("1.5"))]), str_datum("1.5")),
+            (coalesce(vec![datum_expr(Datum::I64(1)), datum_expr(dec_datum("2.5"))]),
+             dec_datum("1")),
+            (coalesce(vec![datum_expr(Datum::I64(-1)), datum_expr(Datum::U64(2))]),
+             dec_datum("-1")),
+            (coalesce(vec![datum_expr(Datum::I64(1)), datum_expr(str_datum("a"))]),
+             str_datum("1")),
+            (coalesce(vec![col_expr(1), datum_expr(Datum::Null)]), Datum::Null),
+            (coalesce(vec![]), Datum::Null),
+            (build_expr(vec![col_expr(1), datum_expr(Datum::U64(3))], ExprType::IfNull),
+             Datum::U64(3)),
+        ];
+        check_eval(&mut eval, cases);
+
+        // floats can't be literals, so use columns for them.
+        eval.row.insert(2, Datum::F64(1.5));
+        eval.row.insert(3, Datum::I64(1));
+        let cases = vec![
+            (coalesce(vec![col_expr(1), col_expr(2)]), Datum::F64(1.5)),
+            (coalesce(vec![col_expr(3), col_expr(2)]), Datum::F64(1f64)),
+            (coalesce(vec![col_expr(1), col_expr(3), col_expr(2)]), Datum::F64(1f64)),
+            (build_expr(vec![col_expr(3), col_expr(2)], ExprType::IfNull), Datum::F64(1f64)),
+        ];
+        for (expr, expect) in cases {
+            let res = eval.eval(&expr).unwrap();
+            assert_eq!(res.kind(), "float");
+            assert_eq!(res, expect);
+        }
+
+        let expr = build_expr(vec![col_expr(1)], ExprType::IfNull);
+        assert!(eval.eval(&expr).is_err());
+    }
+
+    #[test]
+    fn test_substring_and_locate() {
+        let substr = |s, pos, len| {
+            build_expr(vec![datum_expr(str_datum(s)),
+                            datum_expr(Datum::I64(pos)),
+                            datum_expr(Datum::I64(len))],
+                       ExprType::Substring)
+        };
+        let locate = |sub, s| {
+            build_expr(vec![datum_expr(str_datum(sub)), datum_expr(str_datum(s))],
+                       ExprType::Locate)
+        };
+        let cases = vec![
+            (substr("hello", 2, 3), str_datum("ell")),
+            (substr("hello", 1, 100), str_datum("hello")),
+            (substr("hello", -3, 2), str_datum("ll")),
+            (substr("hello", -5, 1), str_datum("h")),
+            (substr("hello", -6, 1), str_datum("")),
+            (substr("hello", 0, 1), str_datum("")),
+            (substr("hello", 6, 1), str_datum("")),
+            (substr("hello", 2, -1), str_datum("")),
+            (build_expr(vec![datum_expr(str_datum("hello")), datum_expr(Datum::I64(3))],
+                        ExprType::Substring),
+             str_datum("llo")),
+            (build_expr(vec![datum_expr(str_datum("hello")),
+                             datum_expr(Datum::Null),
+                             datum_expr(Datum::I64(1))],
+                        ExprType::Substring),
+             Datum::Null),
+            (locate("ll", "hello"), Datum::I64(3)),
+            (locate("h", "hello"), Datum::I64(1)),
+            (locate("x", "hello"), Datum::I64(0)),
+            (locate("", "hello"), Datum::I64(1)),
+            (locate("hello!", "hello"), Datum::I64(0)),
+            (build_expr(vec![datum_expr(Datum::Null), datum_expr(str_datum("hello"))],
+                        ExprType::Locate),
+             Datum::Null),
+        ];
+
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
+    }
+
+    fn dec_datum(s: &'static str) -> Datum {
+        Datum::Decimal(Decimal::from_bytes(s.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_decimal() {
+        let sum = bin_expr(dec_datum("0.1"), dec_datum("0.2"), ExprType::Plus);
+        let cases = vec![
+            (datum_expr(dec_datum("-1.25")), dec_datum("-1.25")),
+            (sum.clone(), dec_datum("0.3")),
+            (bin_expr_r(sum, datum_expr(dec_datum("0.3")), ExprType::EQ), Datum::I64(1)),
+            (bin_expr(dec_datum("0.1"), dec_datum("0.3"), ExprType::GE), Datum::I64(0)),
+            (bin_expr(dec_datum("1.5"), Datum::I64(1), ExprType::GT), Datum::I64(1)),
+            (bin_expr(dec_datum("1.5"), Datum::I64(2), ExprType::Minus), dec_datum("-0.5")),
+            (bin_expr(dec_datum("1.5"), dec_datum("1.5"), ExprType::Mul), dec_datum("2.25")),
+            (bin_expr(dec_datum("1"), dec_datum("3"), ExprType::Div), dec_datum("0.3333")),
+            (bin_expr(dec_datum("1"), dec_datum("0"), ExprType::Div), Datum::Null),
+            (bin_expr(dec_datum("1"), Datum::Null, ExprType::Plus), Datum::Null),
+        ];
+
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
+    }
+
+    #[test]
+    fn test_arith() {
+        let cases = vec![
+            (bin_expr(Datum::I64(1), Datum::I64(2), ExprType::Plus), Datum::I64(3)),
+            (bin_expr(Datum::I64(1), Datum::I64(2), ExprType::Minus), Datum::I64(-1)),
+            (bin_expr(Datum::I64(3), Datum::U64(2), ExprType::Mul), Datum::U64(6)),
+            (bin_expr(Datum::I64(3), b"1.5".as_ref().into(), ExprType::Mul), Datum::F64(4.5)),
+            (bin_expr(Datum::I64(6), Datum::I64(4), ExprType::Div), dec_datum("1.5")),
+            (bin_expr(Datum::I64(6), Datum::I64(0), ExprType::Div), Datum::Null),
+            (bin_expr(Datum::Null, Datum::I64(0), ExprType::Minus), Datum::Null),
+            (bin_expr(Datum::U64(i64::MAX as u64), Datum::I64(1), ExprType::Plus),
+             Datum::U64(i64::MAX as u64 + 1)),
+            (bin_expr(Datum::I64(-1), Datum::U64(5), ExprType::Plus), Datum::U64(4)),
+            (bin_expr(Datum::I64(-1), Datum::U64(i64::MAX as u64 + 1), ExprType::Plus),
+             Datum::U64(i64::MAX as u64)),
+            (bin_expr(Datum::U64(5), Datum::I64(-2), ExprType::Minus), Datum::U64(7)),
+            (bin_expr(Datum::I64(-3), Datum::U64(0), ExprType::Mul), Datum::U64(0)),
+            (bin_expr(Datum::I64(-1), Datum::U64(2), ExprType::Div), dec_datum("-0.5")),
+            (bin_expr(Datum::I64(-1), Datum::U64(5), ExprType::IntDiv), Datum::U64(0)),
+            (bin_expr(Datum::I64(-7), Datum::U64(5), ExprType::Mod), Datum::I64(-2)),
+            (bin_expr(Datum::I64(i64::MIN), Datum::U64(u64::MAX), ExprType::Mod),
+             Datum::I64(i64::MIN)),
+            (bin_expr(Datum::U64(7), Datum::I64(-5), ExprType::Mod), Datum::U64(2)),
+            (bin_expr(Datum::U64(6), Datum::U64(4), ExprType::Div), dec_datum("1.5")),
+        ];
+
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
+
+        let expr = bin_expr(Datum::I64(i64::MAX), Datum::I64(1), ExprType::Plus);
+        assert!(eval.eval(&expr).is_err());
+        let expr = bin_expr(Datum::U64(1), Datum::U64(2), ExprType::Minus);
+        assert!(eval.eval(&expr).is_err());
+        let cases = vec![
+            bin_expr(Datum::I64(-6), Datum::U64(5), ExprType::Plus),
+            bin_expr(Datum::U64(5), Datum::I64(-6), ExprType::Plus),
+            bin_expr(Datum::I64(-1), Datum::U64(0), ExprType::Minus),
+            bin_expr(Datum::U64(u64::MAX), Datum::I64(-1), ExprType::Minus),
+            bin_expr(Datum::U64(2), Datum::I64(-1), ExprType::Mul),
+            bin_expr(Datum::I64(-10), Datum::U64(5), ExprType::IntDiv),
+        ];
+        for expr in cases {
+            assert!(eval.eval(&expr).is_err(), "{:?} should overflow", expr);
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        // (c1 + 3 > 1) and (c2 = 1) and concat()
+        let valid = build_expr(vec![
+            build_expr(vec![
+                build_expr(vec![col_expr(1), datum_expr(Datum::I64(3))], ExprType::Plus),
+                datum_expr(Datum::I64(1)),
+            ], ExprType::GT),
+            build_expr(vec![col_expr(2), datum_expr(Datum::I64(1))], ExprType::EQ),
+            build_expr(vec![], ExprType::Concat),
+        ], ExprType::And);
+        Evaluator::validate(&valid).unwrap();
+        Evaluator::validate(&col_expr(1)).unwrap();
+
+        let cases = vec![
+            // a comparison with one operand.
+            (build_expr(vec![
+                datum_expr(Datum::I64(1)),
+                build_expr(vec![col_expr(1)], ExprType::LT),
+            ], ExprType::Or), "LT need 2 operands, got 1"),
+            (build_expr(vec![datum_expr(Datum::I64(1))], ExprType::And),
+             "And need at least 2 operands, got 1"),
+            (build_expr(vec![col_expr(1)], ExprType::Substring),
+             "Substring need 2 to 3 operands, got 1"),
+            (build_expr(vec![col_expr(1), col_expr(2)], ExprType::Not),
+             "Not need 1 operands, got 2"),
+            (build_expr(vec![col_expr(1), col_expr(2)], ExprType::In),
+             "value list"),
+            (build_expr(vec![], ExprType::Count), "unsupported expr type Count"),
+        ];
+        for (expr, msg) in cases {
+            let err = Evaluator::validate(&expr).unwrap_err();
+            assert!(format!("{}", err).contains(msg), "{} vs {}", err, msg);
+        }
+
+        // literals that can't be decoded.
+        for tp in &[ExprType::Int64, ExprType::ColumnRef, ExprType::Uint64] {
+            let mut expr = Expr::new();
+            expr.set_tp(*tp);
+            expr.set_val(vec![1, 2]);
+            let cmp = build_expr(vec![col_expr(1), expr], ExprType::EQ);
+            assert!(Evaluator::validate(&cmp).is_err(), "{:?}", tp);
+        }
+    }
+
+    #[test]
+    fn test_walk_expr() {
+        // (c2 > 1) and (c1 = c2 + 3)
+        let expr = build_expr(vec![
+            build_expr(vec![col_expr(2), datum_expr(Datum::I64(1))], ExprType::GT),
+            build_expr(vec![
+                col_expr(1),
+                build_expr(vec![col_expr(2), datum_expr(Datum::I64(3))], ExprType::Plus),
+            ], ExprType::EQ),
+        ], ExprType::And);
+
+        let mut tps = vec![];
+        walk_expr(&expr, &mut |e: &Expr| tps.push(e.get_tp()));
+        assert_eq!(tps,
+                   vec![ExprType::And, ExprType::GT, ExprType::ColumnRef, ExprType::Int64,
+                        ExprType::EQ, ExprType::ColumnRef, ExprType::Plus,
+                        ExprType::ColumnRef, ExprType::Int64]);
+
+        assert_eq!(collect_column_ids(&expr), vec![1, 2]);
+        assert_eq!(collect_column_ids(&datum_expr(Datum::I64(1))), Vec::<i64>::new());
+        assert_eq!(collect_column_ids(&col_expr(-3)), vec![-3]);
+    }
+
+    #[test]
+    fn test_error_context() {
+        let mut eval = Evaluator::default();
+        eval.row.insert(1, Datum::I64(1));
+        // (c1 > 0) and (c1 = c7)
+        let expr = build_expr(vec![
+            bin_expr_r(col_expr(1), datum_expr(Datum::I64(0)), ExprType::GT),
+            bin_expr_r(col_expr(1), col_expr(7), ExprType::EQ),
+        ], ExprType::And);
+        let err = eval.eval(&expr).unwrap_err();
+        assert_eq!(format!("{}", err), "in And -> EQ: column 7 not found");
+        match *err.root_cause() {
+            Error::ColumnNotFound(7) => {}
+            ref e => panic!("expect column not found, got {:?}", e),
+        }
+
+        // errors of the expression itself are reported with its type too.
+        let expr = build_expr(vec![
+            datum_expr(Datum::I64(0)),
+            build_expr(vec![datum_expr(Datum::I64(1))], ExprType::Replace),
+        ], ExprType::Or);
+        let err = eval.eval(&expr).unwrap_err();
+        assert_eq!(format!("{}", err),
+                   "in Or -> Replace: REPLACE need 3 operands, got 1");
+
+        // leaves are not wrapped.
+        let err = eval.eval(&col_expr(7)).unwrap_err();
+        assert_eq!(format!("{}", err), "column 7 not found");
+        assert_eq!(format!("{}", err.root_cause()), "column 7 not found");
+    }
+
+    #[test]
+    fn test_supported_expr_types() {
+        for tp in &[ExprType::Int64, ExprType::ColumnRef, ExprType::EQ, ExprType::In,
+                    ExprType::Like, ExprType::Plus, ExprType::Coalesce] {
+            assert!(Evaluator::is_supported(*tp), "{:?}", tp);
+        }
+        // floats literals are not implemented yet, and value lists and
+        // aggregates are not evaluated on their own.
+        for tp in &[ExprType::Float32, ExprType::Float64, ExprType::ValueList,
+                    ExprType::Count, ExprType::Sum] {
+            assert!(!Evaluator::is_supported(*tp), "{:?}", tp);
+        }
+
+        // every supported type is dispatched by eval, though an empty expr
+        // may still be invalid.
+        let mut eval = Evaluator::default();
+        for &tp in Evaluator::supported_expr_types() {
+            let mut expr = Expr::new();
+            expr.set_tp(tp);
+            if let Err(e) = eval.eval(&expr) {
+                assert!(!format!("{:?}", e).contains("unsupported expr type"), "{:?}", tp);
+            }
+        }
+        for &tp in &[ExprType::ValueList, ExprType::Count, ExprType::Sum] {
+            let mut expr = Expr::new();
+            expr.set_tp(tp);
+            let e = eval.eval(&expr).unwrap_err();
+            assert!(format!("{:?}", e).contains("unsupported expr type"), "{:?}", tp);
+        }
+    }
+
+    #[test]
+    fn test_max_depth() {
+        assert_eq!(Evaluator::default().max_depth, DEFAULT_MAX_DEPTH);
+
+        // a chain of `depth` nested NOTs.
+        fn nested_not(leaf: Expr, depth: usize) -> Expr {
+            let mut expr = leaf;
+            for _ in 1..depth {
+                let mut not = Expr::new();
+                not.set_tp(ExprType::Not);
+                not.mut_children().push(expr);
+                expr = not;
             }
-            Datum::Bytes(bs) => {
-                expr.set_tp(ExprType::Bytes);
-                expr.set_val(bs);
+            expr
+        }
+
+        let mut eval = Evaluator::default();
+        eval.max_depth = 10;
+        eval.row.insert(1, Datum::I64(1));
+        for leaf in vec![datum_expr(Datum::I64(1)), col_expr(1)] {
+            let expr = nested_not(leaf.clone(), 10);
+            assert_eq!(eval.eval(&expr).unwrap(), Datum::I64(0));
+            eval.compile(&expr).unwrap();
+
+            let expr = nested_not(leaf, 11);
+            for _ in 0..2 {
+                // the depth is restored after failures.
+                let err = eval.eval(&expr).unwrap_err();
+                // the error isn't wrapped with the long path.
+                assert_eq!(format!("{}", err), "expression nesting too deep");
+                assert!(eval.compile(&expr).is_err());
             }
-            Datum::F32(_) => unimplemented!(),
-            Datum::F64(_) => unimplemented!(),
-            _ => expr.set_tp(ExprType::Null),
-        };
-        expr
+        }
     }
 
-    fn col_expr(col_id: i64) -> Expr {
+    #[test]
+    fn test_not_float() {
+        let cases = vec![
+            (0f64, Datum::I64(1)),
+            (-0f64, Datum::I64(1)),
+            (0.5, Datum::I64(0)),
+            (-0.5, Datum::I64(0)),
+            (0.4, Datum::I64(0)),
+            (1e-300, Datum::I64(0)),
+            (-1e-300, Datum::I64(0)),
+            (2.0, Datum::I64(0)),
+        ];
+        let mut eval = Evaluator::default();
         let mut expr = Expr::new();
-        expr.set_tp(ExprType::ColumnRef);
-        let mut buf = vec![0; 8];
-        number::encode_i64(&mut buf, col_id).unwrap();
-        expr.set_val(buf);
-        expr
+        expr.set_tp(ExprType::Not);
+        expr.mut_children().push(col_expr(1));
+        let compiled = eval.compile(&expr).unwrap();
+        for (f, expect) in cases {
+            eval.row.insert(1, Datum::F64(f));
+            assert!(eval.eval(&expr).unwrap() == expect, "not {}", f);
+            assert!(compiled.eval(&eval.row).unwrap() == expect, "not {}", f);
+        }
     }
 
-    fn bin_expr(left: Datum, right: Datum, tp: ExprType) -> Expr {
-        bin_expr_r(datum_expr(left), datum_expr(right), tp)
+    #[test]
+    fn test_int_div_and_mod() {
+        let cases = vec![
+            (bin_expr(Datum::I64(7), Datum::I64(2), ExprType::IntDiv), Datum::I64(3)),
+            (bin_expr(Datum::I64(-7), Datum::I64(2), ExprType::IntDiv), Datum::I64(-3)),
+            (bin_expr(Datum::U64(u64::MAX), Datum::U64(2), ExprType::IntDiv),
+             Datum::U64(u64::MAX / 2)),
+            (bin_expr(Datum::U64(7), Datum::U64(2), ExprType::IntDiv), Datum::U64(3)),
+            (bin_expr(dec_datum("7.5"), Datum::I64(2), ExprType::IntDiv), Datum::I64(3)),
+            (bin_expr(str_datum("7.5"), Datum::I64(2), ExprType::IntDiv), Datum::I64(3)),
+            (bin_expr(Datum::I64(7), Datum::I64(0), ExprType::IntDiv), Datum::Null),
+            (bin_expr(Datum::U64(7), Datum::U64(0), ExprType::IntDiv), Datum::Null),
+            (bin_expr(Datum::Null, Datum::I64(2), ExprType::IntDiv), Datum::Null),
+            (bin_expr(Datum::U64(u64::MAX), Datum::U64(10), ExprType::Mod), Datum::U64(5)),
+            (bin_expr(Datum::U64(i64::MAX as u64 + 3), Datum::U64(2), ExprType::Mod),
+             Datum::U64(1)),
+            (bin_expr(Datum::I64(7), Datum::I64(3), ExprType::Mod), Datum::I64(1)),
+            (bin_expr(Datum::I64(-7), Datum::I64(3), ExprType::Mod), Datum::I64(-1)),
+            (bin_expr(Datum::I64(7), Datum::I64(-3), ExprType::Mod), Datum::I64(1)),
+            (bin_expr(Datum::I64(i64::MIN), Datum::I64(-1), ExprType::Mod), Datum::I64(0)),
+            (bin_expr(dec_datum("7.5"), dec_datum("2"), ExprType::Mod), dec_datum("1.5")),
+            (bin_expr(Datum::I64(7), Datum::I64(0), ExprType::Mod), Datum::Null),
+            (bin_expr(Datum::U64(7), Datum::Null, ExprType::Mod), Datum::Null),
+        ];
+
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
+
+        let expr = bin_expr(Datum::I64(i64::MIN), Datum::I64(-1), ExprType::IntDiv);
+        assert!(eval.eval(&expr).is_err());
+        let compiled = eval.compile(&expr).unwrap();
+        assert!(compiled.eval(&HashMap::new()).is_err());
+
+        // a NaN quotient is null, and an infinite one overflows.
+        eval.row.insert(1, Datum::F64(f64::NAN));
+        eval.row.insert(2, Datum::F64(f64::INFINITY));
+        eval.row.insert(3, Datum::F64(f64::NEG_INFINITY));
+        let cases = vec![
+            (bin_expr_r(col_expr(1), datum_expr(Datum::I64(2)), ExprType::IntDiv), Datum::Null),
+            (bin_expr_r(col_expr(2), col_expr(2), ExprType::IntDiv), Datum::Null),
+            (bin_expr_r(col_expr(2), col_expr(3), ExprType::IntDiv), Datum::Null),
+        ];
+        check_eval(&mut eval, cases);
+        for col in 2..4 {
+            let expr = bin_expr_r(col_expr(col), datum_expr(Datum::I64(2)), ExprType::IntDiv);
+            assert!(eval.eval(&expr).is_err());
+        }
+
+        // the variant of unsigned results is kept in the compiled form too.
+        let expr = bin_expr(Datum::U64(u64::MAX), Datum::U64(10), ExprType::Mod);
+        assert_eq!(eval.compile(&expr).unwrap().eval(&HashMap::new()).unwrap(), Datum::U64(5));
     }
 
-    fn bin_expr_r(left: Expr, right: Expr, tp: ExprType) -> Expr {
-        let mut expr = Expr::new();
-        expr.set_tp(tp);
-        expr.set_children(RepeatedField::from_vec(vec![left, right]));
-        expr
+    #[test]
+    fn test_sign_and_float_mod() {
+        let sign_expr = |e: Expr| build_expr(vec![e], ExprType::Sign);
+        let mut row = HashMap::new();
+        row.insert(1, Datum::F64(5.5));
+        row.insert(2, Datum::F64(2.0));
+        row.insert(3, Datum::F64(-5.5));
+        row.insert(4, Datum::F64(-2.0));
+        row.insert(5, Datum::F64(2.5));
+        row.insert(6, Datum::F64(-0.0));
+        let cases = vec![
+            (sign_expr(datum_expr(Datum::I64(-3))), Datum::I64(-1)),
+            (sign_expr(datum_expr(Datum::I64(0))), Datum::I64(0)),
+            (sign_expr(datum_expr(Datum::I64(i64::MIN))), Datum::I64(-1)),
+            (sign_expr(datum_expr(Datum::U64(u64::MAX))), Datum::I64(1)),
+            (sign_expr(datum_expr(Datum::U64(0))), Datum::I64(0)),
+            (sign_expr(datum_expr(dec_datum("-0.001"))), Datum::I64(-1)),
+            (sign_expr(datum_expr(dec_datum("0.00"))), Datum::I64(0)),
+            (sign_expr(datum_expr(str_datum("-2.5"))), Datum::I64(-1)),
+            (sign_expr(col_expr(5)), Datum::I64(1)),
+            (sign_expr(col_expr(3)), Datum::I64(-1)),
+            (sign_expr(col_expr(6)), Datum::I64(0)),
+            (sign_expr(datum_expr(Datum::Null)), Datum::Null),
+            // fmod, the sign of the remainder follows the dividend.
+            (bin_expr_r(col_expr(1), col_expr(2), ExprType::Mod), Datum::F64(1.5)),
+            (bin_expr_r(col_expr(3), col_expr(2), ExprType::Mod), Datum::F64(-1.5)),
+            (bin_expr_r(col_expr(1), col_expr(4), ExprType::Mod), Datum::F64(1.5)),
+            (bin_expr_r(col_expr(3), col_expr(4), ExprType::Mod), Datum::F64(-1.5)),
+            (bin_expr_r(col_expr(1), datum_expr(Datum::I64(2)), ExprType::Mod),
+             Datum::F64(1.5)),
+            (bin_expr(str_datum("5.5"), str_datum("2.0"), ExprType::Mod), Datum::F64(1.5)),
+            (bin_expr_r(col_expr(1), col_expr(6), ExprType::Mod), Datum::Null),
+        ];
+        let mut eval = Evaluator::default();
+        eval.row = row;
+        check_eval(&mut eval, cases);
+
+        let expr = build_expr(vec![col_expr(1), col_expr(2)], ExprType::Sign);
+        assert!(eval.eval(&expr).is_err());
     }
 
-    fn not_expr(value: Datum) -> Expr {
+    // build the searched form of `CASE operand WHEN 1 THEN 'one' WHEN NULL THEN
+    // 'null' WHEN 2.0 THEN 'two' WHEN 2 THEN 'two again' [ELSE 'other'] END`.
+    fn simple_case_expr(operand: Expr, with_else: bool) -> Expr {
+        let branches = vec![(Datum::I64(1), "one"),
+                            (Datum::Null, "null"),
+                            (dec_datum("2.0"), "two"),
+                            (Datum::I64(2), "two again")];
+        let mut children = vec![];
+        for (when, then) in branches {
+            children.push(bin_expr_r(operand.clone(), datum_expr(when), ExprType::EQ));
+            children.push(datum_expr(str_datum(then)));
+        }
+        if with_else {
+            children.push(datum_expr(str_datum("other")));
+        }
+        build_expr(children, ExprType::Case)
+    }
+
+    #[test]
+    fn test_case() {
+        let mut eval = Evaluator::default();
+        eval.row.insert(1, Datum::I64(2));
+        eval.row.insert(2, Datum::Null);
+        let cases = vec![
+            // the first matched branch in the middle.
+            (simple_case_expr(col_expr(1), true), str_datum("two")),
+            (simple_case_expr(datum_expr(Datum::I64(1)), true), str_datum("one")),
+            (simple_case_expr(datum_expr(str_datum("2")), true), str_datum("two")),
+            // null never matches, even a null when value.
+            (simple_case_expr(col_expr(2), true), str_datum("other")),
+            (simple_case_expr(datum_expr(Datum::I64(3)), true), str_datum("other")),
+            (simple_case_expr(col_expr(2), false), Datum::Null),
+            (simple_case_expr(datum_expr(Datum::I64(3)), false), Datum::Null),
+            (build_expr(vec![bin_expr_r(col_expr(1), datum_expr(Datum::I64(1)), ExprType::LT),
+                             datum_expr(str_datum("lt")),
+                             bin_expr_r(col_expr(1), datum_expr(Datum::I64(1)), ExprType::GT),
+                             datum_expr(str_datum("gt"))],
+                        ExprType::Case),
+             str_datum("gt")),
+            (build_expr(vec![col_expr(2),
+                             datum_expr(str_datum("null")),
+                             datum_expr(Datum::I64(0)),
+                             datum_expr(str_datum("false")),
+                             datum_expr(str_datum("else"))],
+                        ExprType::Case),
+             str_datum("else")),
+        ];
+        check_eval(&mut eval, cases);
+
+        // branches after the matched one are not evaluated.
+        let children = vec![col_expr(1), datum_expr(Datum::I64(1)), col_expr(4), col_expr(5)];
+        let expr = build_expr(children, ExprType::Case);
+        assert_eq!(eval.eval(&expr).unwrap(), Datum::I64(1));
+
+        assert!(eval.eval(&build_expr(vec![datum_expr(Datum::I64(1))], ExprType::Case))
+            .is_err());
+    }
+
+    fn neg_expr(value: Datum) -> Expr {
         let mut expr = Expr::new();
-        expr.set_tp(ExprType::Not);
+        expr.set_tp(ExprType::Neg);
         expr.mut_children().push(datum_expr(value));
         expr
     }
 
-    fn like_expr(target: &'static str, pattern: &'static str) -> Expr {
-        let target_expr = datum_expr(Datum::Bytes(target.as_bytes().to_vec()));
-        let pattern_expr = datum_expr(Datum::Bytes(pattern.as_bytes().to_vec()));
-        let mut expr = Expr::new();
-        expr.set_tp(ExprType::Like);
-        expr.mut_children().push(target_expr);
-        expr.mut_children().push(pattern_expr);
-        expr
+    #[test]
+    fn test_pow_and_sqrt() {
+        let cases = vec![
+            (bin_expr(Datum::I64(2), Datum::I64(10), ExprType::Pow), Datum::F64(1024f64)),
+            (bin_expr(Datum::I64(4), str_datum("0.5"), ExprType::Pow), Datum::F64(2f64)),
+            (bin_expr(Datum::I64(2), Datum::I64(-1), ExprType::Pow), Datum::F64(0.5)),
+            (bin_expr(Datum::Null, Datum::I64(2), ExprType::Pow), Datum::Null),
+            (bin_expr(Datum::I64(2), Datum::Null, ExprType::Pow), Datum::Null),
+            (build_expr(vec![datum_expr(Datum::I64(9))], ExprType::Sqrt), Datum::F64(3f64)),
+            (build_expr(vec![datum_expr(str_datum("2.25"))], ExprType::Sqrt), Datum::F64(1.5)),
+            (build_expr(vec![datum_expr(Datum::I64(-1))], ExprType::Sqrt), Datum::Null),
+            (build_expr(vec![datum_expr(Datum::Null)], ExprType::Sqrt), Datum::Null),
+        ];
+
+        let mut eval = Evaluator::default();
+        check_eval(&mut eval, cases);
     }
 
-    // TODO: add more tests.
     #[test]
-    fn test_eval() {
-        let tests = vec![
-			(datum_expr(Datum::I64(1)), Datum::I64(1)),
-			(datum_expr(Datum::U64(1)), Datum::U64(1)),
-			(datum_expr(b"abc".as_ref().into()), b"abc".as_ref().into()),
-			(datum_expr(Datum::Null), Datum::Null),
-			(col_expr(1), Datum::I64(100)),
-			(bin_expr(Datum::I64(100), Datum::I64(1), ExprType::LT), Datum::I64(0)),
-			(bin_expr(Datum::I64(1), Datum::I64(100), ExprType::LT), Datum::I64(1)),
-			(bin_expr(Datum::I64(100), Datum::Null, ExprType::LT), Datum::Null),
-			(bin_expr(Datum::I64(100), Datum::I64(1), ExprType::LE), Datum::I64(0)),
-			(bin_expr(Datum::I64(1), Datum::I64(1), ExprType::LE), Datum::I64(1)),
-			(bin_expr(Datum::I64(100), Datum::Null, ExprType::LE), Datum::Null),
-			(bin_expr(Datum::I64(100), Datum::I64(1), ExprType::EQ), Datum::I64(0)),
-			(bin_expr(Datum::I64(100), Datum::I64(100), ExprType::EQ), Datum::I64(1)),
-			(bin_expr(Datum::I64(100), Datum::Null, ExprType::EQ), Datum::Null),
-			(bin_expr(Datum::I64(100), Datum::I64(100), ExprType::NE), Datum::I64(0)),
-			(bin_expr(Datum::I64(100), Datum::I64(1), ExprType::NE), Datum::I64(1)),
-			(bin_expr(Datum::I64(100), Datum::Null, ExprType::NE), Datum::Null),
-			(bin_expr(Datum::I64(1), Datum::I64(100), ExprType::GE), Datum::I64(0)),
-			(bin_expr(Datum::I64(100), Datum::I64(100), ExprType::GE), Datum::I64(1)),
-			(bin_expr(Datum::I64(100), Datum::Null, ExprType::GE), Datum::Null),
-			(bin_expr(Datum::I64(100), Datum::I64(100), ExprType::GT), Datum::I64(0)),
-			(bin_expr(Datum::I64(100), Datum::I64(1), ExprType::GT), Datum::I64(1)),
-			(bin_expr(Datum::I64(100), Datum::Null, ExprType::GT), Datum::Null),
-			(bin_expr(Datum::I64(1), Datum::Null, ExprType::NullEQ), Datum::I64(0)),
-			(bin_expr(Datum::Null, Datum::Null, ExprType::NullEQ), Datum::I64(1)),
-			// logic operation
-			(bin_expr(Datum::I64(0), Datum::I64(1), ExprType::And), Datum::I64(0)),
-			(bin_expr(Datum::I64(1), Datum::I64(1), ExprType::And), Datum::I64(1)),
-			(bin_expr(Datum::I64(1), Datum::Null, ExprType::And), Datum::Null),
-			(bin_expr(Datum::Null, Datum::I64(0), ExprType::And), Datum::I64(0)),
-			(bin_expr(Datum::Null, Datum::Null, ExprType::And), Datum::Null),
-			(bin_expr(Datum::I64(0), Datum::I64(0), ExprType::Or), Datum::I64(0)),
-			(bin_expr(Datum::I64(0), Datum::I64(1), ExprType::Or), Datum::I64(1)),
-			(bin_expr(Datum::I64(1), Datum::Null, ExprType::Or), Datum::I64(1)),
-			(bin_expr(Datum::Null, Datum::Null, ExprType::Or), Datum::Null),
-			(bin_expr(Datum::Null, Datum::I64(0), ExprType::Or), Datum::Null),
-			(bin_expr_r(bin_expr(Datum::I64(1), Datum::I64(1), ExprType::EQ),
-			 bin_expr(Datum::I64(1), Datum::I64(1), ExprType::EQ), ExprType::And), Datum::I64(1)),
-			(not_expr(Datum::I64(1)), Datum::I64(0)),
-			(not_expr(Datum::I64(0)), Datum::I64(1)),
-			(not_expr(Datum::Null), Datum::Null),
-            // like operation
-            (like_expr("a", ""), Datum::I64(0)),
-            (like_expr("a", "a"), Datum::I64(1)),
-            (like_expr("a", "b"), Datum::I64(0)),
-            (like_expr("aAb", "AaB"), Datum::I64(1)),
-            (like_expr("a", "%"), Datum::I64(1)),
-            (like_expr("aAD", "%d"), Datum::I64(1)),
-            (like_expr("aAeD", "%e"), Datum::I64(0)),
-            (like_expr("aAb", "Aa%"), Datum::I64(1)),
-            (like_expr("abAb", "Aa%"), Datum::I64(0)),
-            (like_expr("aAcb", "%C%"), Datum::I64(1)),
-            (like_expr("aAb", "%C%"), Datum::I64(0)),
+    fn test_arith_overflow_mode() {
+        let cases = vec![
+            (bin_expr(Datum::I64(i64::MAX), Datum::I64(2), ExprType::Mul), Datum::I64(i64::MAX)),
+            (bin_expr(Datum::I64(i64::MAX), Datum::I64(-2), ExprType::Mul), Datum::I64(i64::MIN)),
+            (bin_expr(Datum::U64(u64::MAX), Datum::U64(2), ExprType::Mul), Datum::U64(u64::MAX)),
+            (bin_expr(Datum::I64(i64::MIN), Datum::I64(-1), ExprType::Plus), Datum::I64(i64::MIN)),
+            (bin_expr(Datum::U64(1), Datum::U64(2), ExprType::Minus), Datum::U64(0)),
+            (neg_expr(Datum::I64(i64::MIN)), Datum::I64(i64::MAX)),
+            (neg_expr(Datum::U64(u64::MAX)), Datum::I64(i64::MIN)),
         ];
 
-        let mut xevaluator = Evaluator::default();
-        xevaluator.row.insert(1, Datum::I64(100));
-        for (expr, result) in tests {
-            let res = xevaluator.eval(&expr);
-            if res.is_err() {
-                panic!("failed to eval {:?}: {:?}", expr, res);
-            }
-            let res = res.unwrap();
-            if res != result {
-                panic!("failed to eval {:?} expect {:?}, got {:?}",
-                       expr,
-                       result,
-                       res);
+        let mut eval = Evaluator::default();
+        assert!(eval.in_strict_mode);
+        for &(ref expr, _) in &cases {
+            assert!(eval.eval(expr).is_err(), "{:?} should overflow", expr);
+        }
+        assert!(eval.take_warnings().is_empty());
+
+        eval.in_strict_mode = false;
+        let cnt = cases.len();
+        check_eval(&mut eval, cases);
+        assert_eq!(eval.take_warnings().len(), cnt);
+        assert!(eval.take_warnings().is_empty());
+
+        // no warning if nothing overflows.
+        let cases = vec![
+            (bin_expr(Datum::I64(3), Datum::I64(-2), ExprType::Mul), Datum::I64(-6)),
+            (neg_expr(Datum::I64(3)), Datum::I64(-3)),
+            (neg_expr(Datum::U64(i64::MAX as u64 + 1)), Datum::I64(i64::MIN)),
+            (neg_expr(Datum::Null), Datum::Null),
+        ];
+        check_eval(&mut eval, cases);
+        assert!(eval.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_like_cache() {
+        let cases = vec![
+            ("a", "", Datum::I64(0)),
+            ("a", "a", Datum::I64(1)),
+            ("aAb", "AaB", Datum::I64(1)),
+            ("a", "%", Datum::I64(1)),
+            ("aAD", "%d", Datum::I64(1)),
+            ("aAeD", "%e", Datum::I64(0)),
+            ("aAb", "Aa%", Datum::I64(1)),
+            ("abAb", "Aa%", Datum::I64(0)),
+            ("aAcb", "%C%", Datum::I64(1)),
+            ("aAb", "%C%", Datum::I64(0)),
+            ("abc", "a_c", Datum::I64(1)),
+            ("abc", "a%d", Datum::I64(0)),
+            ("un café noir", "%café%", Datum::I64(1)),
+            ("un cafe noir", "%café%", Datum::I64(0)),
+            ("café", "%é", Datum::I64(1)),
+            ("éa", "é%", Datum::I64(1)),
+            ("é", "%", Datum::I64(1)),
+            ("日本語", "%本%", Datum::I64(1)),
+        ];
+        let exprs: Vec<_> = cases.iter().map(|&(t, p, _)| like_expr(t, p)).collect();
+
+        let mut eval = Evaluator::default();
+        for _ in 0..2 {
+            for (expr, &(t, p, ref expect)) in exprs.iter().zip(&cases) {
+                // a constant pattern is cached after the first round.
+                let res = eval.eval(expr).unwrap();
+                assert!(res == *expect, "{} like {}", t, p);
+
+                // a pattern from a column is never cached.
+                let mut uncached = Evaluator::default();
+                uncached.row.insert(1, str_datum(p));
+                let col_like = bin_expr_r(datum_expr(str_datum(t)), col_expr(1), ExprType::Like);
+                assert!(uncached.eval(&col_like).unwrap() == res, "{} like {}", t, p);
+                assert!(uncached.cached_like_matcher.is_empty());
             }
+            assert_eq!(eval.cached_like_matcher.len(), cases.len());
         }
     }
 
-    fn in_expr(target: Datum, mut list: Vec<Datum>) -> Expr {
-        let target_expr = datum_expr(target);
-        list.sort_by(|l, r| l.cmp(r).unwrap());
-        let val = datum::encode_value(&list).unwrap();
-        let mut list_expr = Expr::new();
-        list_expr.set_tp(ExprType::ValueList);
-        list_expr.set_val(val);
-        let mut expr = Expr::new();
-        expr.set_tp(ExprType::In);
-        expr.mut_children().push(target_expr);
-        expr.mut_children().push(list_expr);
-        expr
+    #[test]
+    fn test_like_binary() {
+        let mut eval = Evaluator::default();
+        // not valid UTF-8.
+        let invalid = b"a\xff\xfeb".to_vec();
+        eval.row.insert(1, Datum::Bytes(invalid.clone()));
+        eval.row.insert(2, Datum::Bytes(invalid.clone()));
+        eval.row.insert(3, Datum::Bytes(b"a\xff%".to_vec()));
+        eval.collations.insert(2, Collation::Binary);
+        fn binary_like(pattern: &[u8]) -> Expr {
+            bin_expr_r(col_expr(2), datum_expr(Datum::Bytes(pattern.to_vec())), ExprType::Like)
+        }
+
+        let cases = vec![
+            (binary_like(b"%"), Datum::I64(1)),
+            (binary_like(b"a%"), Datum::I64(1)),
+            (binary_like(b"a\xff%"), Datum::I64(1)),
+            (binary_like(b"a__b"), Datum::I64(1)),
+            (binary_like(b"a_b"), Datum::I64(0)),
+            (binary_like(b"%\xfe_"), Datum::I64(1)),
+            (binary_like(b"A%"), Datum::I64(0)),
+            (bin_expr_r(col_expr(2), col_expr(3), ExprType::Like), Datum::I64(1)),
+        ];
+        for (expr, expect) in cases {
+            assert_eq!(eval.eval(&expr).unwrap(), expect);
+            // only constant patterns can be compiled.
+            if let Ok(compiled) = eval.compile(&expr) {
+                assert_eq!(compiled.eval(&eval.row).unwrap(), expect);
+            }
+        }
+
+        // the default collation still needs valid UTF-8.
+        let expr = bin_expr_r(col_expr(1), datum_expr(str_datum("%")), ExprType::Like);
+        assert!(eval.eval(&expr).is_err());
     }
 
     #[test]
-    fn test_where_in() {
+    fn test_like_collation() {
+        let mut eval = Evaluator::default();
+        eval.row.insert(1, str_datum("A"));
+        eval.row.insert(2, str_datum("A"));
+        eval.collations.insert(2, Collation::Binary);
         let cases = vec![
-            (in_expr(Datum::I64(1), vec![Datum::I64(1), Datum::I64(2)]), Datum::I64(1)),
-            (in_expr(Datum::I64(1), vec![Datum::I64(2), Datum::Null]), Datum::Null),
-            (in_expr(Datum::Null, vec![Datum::I64(1), Datum::Null]), Datum::Null),
-            (in_expr(Datum::I64(2), vec![Datum::I64(1), Datum::Null]), Datum::Null),
-            (in_expr(Datum::I64(2), vec![]), Datum::I64(0)),
-            (in_expr(b"abc".as_ref().into(), vec![b"abc".as_ref().into(),
-             b"ab".as_ref().into()]), Datum::I64(1)),
-            (in_expr(b"abc".as_ref().into(), vec![b"aba".as_ref().into(),
-             b"bab".as_ref().into()]), Datum::I64(0)),
+            (like_expr("A", "a"), Datum::I64(1)),
+            (bin_expr_r(col_expr(1), datum_expr(str_datum("a")), ExprType::Like), Datum::I64(1)),
+            (bin_expr_r(col_expr(2), datum_expr(str_datum("a")), ExprType::Like), Datum::I64(0)),
+            (bin_expr_r(col_expr(2), datum_expr(str_datum("A")), ExprType::Like), Datum::I64(1)),
+            (bin_expr_r(col_expr(2), datum_expr(str_datum("%")), ExprType::Like), Datum::I64(1)),
+        ];
+        check_eval(&mut eval, cases);
+
+        assert_eq!(Collation::from_id(BINARY_COLLATION_ID), Collation::Binary);
+        for id in &[46, 47, 83] {
+            assert_eq!(Collation::from_id(*id), Collation::Binary);
+        }
+        assert_eq!(Collation::from_id(33), Collation::CaseInsensitive);
+        assert_eq!(Collation::from_id(45), Collation::CaseInsensitive);
+    }
+
+    #[test]
+    fn test_compile() {
+        // (col1 > 1 + 2 and col2 like 'a%') or not col1 in (1, 2)
+        let gt = bin_expr_r(col_expr(1),
+                            bin_expr(Datum::I64(1), Datum::I64(2), ExprType::Plus),
+                            ExprType::GT);
+        let like = bin_expr_r(col_expr(2), datum_expr(str_datum("a%")), ExprType::Like);
+        let mut not_in = Expr::new();
+        not_in.set_tp(ExprType::Not);
+        not_in.mut_children().push(in_expr_r(col_expr(1), vec![Datum::I64(1), Datum::I64(2)]));
+        let expr = bin_expr_r(bin_expr_r(gt, like, ExprType::And), not_in, ExprType::Or);
+
+        let rows = vec![
+            (Datum::I64(1), str_datum("abc")),
+            (Datum::I64(3), str_datum("abc")),
+            (Datum::I64(4), str_datum("abc")),
+            (Datum::I64(4), str_datum("bc")),
+            (Datum::I64(2), Datum::Null),
+            (Datum::Null, str_datum("abc")),
+            (Datum::Null, Datum::Null),
         ];
 
         let mut eval = Evaluator::default();
-        for (expr, expect_res) in cases {
-            let res = eval.eval(&expr);
-            if res.is_err() {
-                panic!("failed to execute {:?}: {:?}", expr, res);
+        let compiled = eval.compile(&expr).unwrap();
+        for (c1, c2) in rows {
+            eval.row.insert(1, c1);
+            eval.row.insert(2, c2);
+            let expect = eval.eval(&expr).unwrap();
+            let got = compiled.eval(&eval.row).unwrap();
+            assert!(got == expect, "row {:?}: {:?} != {:?}", eval.row, got, expect);
+        }
+
+        // constant subtrees are folded.
+        let expr = bin_expr(Datum::I64(1), Datum::I64(2), ExprType::LT);
+        match eval.compile(&expr).unwrap() {
+            CompiledExpr::Constant(d) => assert_eq!(d, Datum::I64(1)),
+            _ => panic!("constant expression should be folded"),
+        }
+        let expr = bin_expr_r(col_expr(1),
+                              bin_expr(Datum::I64(1), Datum::I64(2), ExprType::Plus),
+                              ExprType::GT);
+        match eval.compile(&expr).unwrap() {
+            CompiledExpr::Compare(ExprType::GT, _, r) => {
+                match *r {
+                    CompiledExpr::Constant(ref d) => assert_eq!(*d, Datum::I64(3)),
+                    _ => panic!("constant expression should be folded"),
+                }
             }
-            let res = res.unwrap();
-            if res != expect_res {
-                panic!("wrong result {:?}, expect {:?} while executing {:?}",
-                       res,
-                       expect_res,
-                       expr);
+            _ => panic!("comparison should be compiled"),
+        }
+
+        // compiled comparisons convert operands just like the evaluator.
+        let literals = vec![Datum::I64(1), dec_datum("1.5"), str_datum("1.5abc"), str_datum("2")];
+        let columns = vec![Datum::F64(1.5), Datum::F32(0.5), Datum::I64(2), str_datum("1.5")];
+        for tp in &[ExprType::LT, ExprType::LE, ExprType::NE, ExprType::GE, ExprType::GT] {
+            for lit in &literals {
+                let expr = bin_expr_r(col_expr(1), datum_expr(lit.clone()), *tp);
+                let compiled = eval.compile(&expr).unwrap();
+                for col in &columns {
+                    eval.row.insert(1, col.clone());
+                    match (eval.eval(&expr), compiled.eval(&eval.row)) {
+                        (Ok(expect), Ok(got)) => {
+                            assert!(got == expect, "{:?} {:?} {:?}: {:?}", col, tp, lit, got)
+                        }
+                        (Err(_), Err(_)) => {}
+                        (expect, got) => {
+                            panic!("{:?} {:?} {:?}: {:?} != {:?}", col, tp, lit, got, expect)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compile_column_eq() {
+        let literals = vec![Datum::I64(1), Datum::U64(1), dec_datum("1.0"), str_datum("1"),
+                            str_datum("abc"), str_datum("1.0abc"), Datum::Null];
+        let columns = vec![Datum::I64(1), Datum::I64(2), Datum::U64(u64::MAX), Datum::F64(1.0),
+                           dec_datum("1"), str_datum("1"), str_datum("abc"), Datum::Null];
+        let mut eval = Evaluator::default();
+        for lit in literals {
+            let exprs = vec![bin_expr_r(col_expr(1), datum_expr(lit.clone()), ExprType::EQ),
+                             bin_expr_r(datum_expr(lit.clone()), col_expr(1), ExprType::EQ)];
+            for expr in exprs {
+                let compiled = eval.compile(&expr).unwrap();
+                match compiled {
+                    CompiledExpr::ColumnEq(1, ref d) => assert_eq!(*d, lit),
+                    _ => panic!("column equality should be specialized"),
+                }
+                for col in &columns {
+                    eval.row.insert(1, col.clone());
+                    match (eval.eval(&expr), compiled.eval(&eval.row)) {
+                        (Ok(expect), Ok(got)) => {
+                            assert!(got == expect, "{:?} = {:?}: {:?}", col, lit, got)
+                        }
+                        (Err(_), Err(_)) => {}
+                        (expect, got) => panic!("{:?} = {:?}: {:?} != {:?}", col, lit, got, expect),
+                    }
+                }
+                assert!(compiled.eval(&HashMap::new()).is_err());
+            }
+        }
+
+        // other comparisons are not specialized.
+        let expr = bin_expr_r(col_expr(1), datum_expr(Datum::I64(1)), ExprType::NE);
+        match eval.compile(&expr).unwrap() {
+            CompiledExpr::Compare(ExprType::NE, _, _) => {}
+            _ => panic!("comparison should be compiled"),
+        }
+    }
+
+    #[test]
+    fn test_flatten_logic() {
+        let cmp = |col, v| bin_expr_r(col_expr(col), datum_expr(Datum::I64(v)), ExprType::GT);
+        // ((c1 > 1 and c2 > 2) and (c3 > 3 and (c1 > 4 or c2 > 5 or c3 > 6))) or c1 > 7
+        let or = bin_expr_r(bin_expr_r(cmp(1, 4), cmp(2, 5), ExprType::Or),
+                            cmp(3, 6),
+                            ExprType::Or);
+        let and = bin_expr_r(bin_expr_r(cmp(1, 1), cmp(2, 2), ExprType::And),
+                             bin_expr_r(cmp(3, 3), or, ExprType::And),
+                             ExprType::And);
+        let expr = bin_expr_r(and, cmp(1, 7), ExprType::Or);
+
+        let flattened = Evaluator::flatten_logic(&expr);
+        assert_eq!(flattened.get_tp(), ExprType::Or);
+        assert_eq!(flattened.get_children().len(), 2);
+        let and = &flattened.get_children()[0];
+        assert_eq!(and.get_tp(), ExprType::And);
+        assert_eq!(and.get_children().len(), 4);
+        assert_eq!(and.get_children()[3].get_tp(), ExprType::Or);
+        assert_eq!(and.get_children()[3].get_children().len(), 3);
+
+        let values = vec![Datum::Null, Datum::I64(0), Datum::I64(3), Datum::I64(5), Datum::I64(8)];
+        let mut eval = Evaluator::default();
+        let compiled = eval.compile(&flattened).unwrap();
+        for c1 in &values {
+            for c2 in &values {
+                for c3 in &values {
+                    eval.row.insert(1, c1.clone());
+                    eval.row.insert(2, c2.clone());
+                    eval.row.insert(3, c3.clone());
+                    let expect = eval.eval(&expr).unwrap();
+                    let got = eval.eval(&flattened).unwrap();
+                    assert!(got == expect, "row {:?}: {:?} != {:?}", eval.row, got, expect);
+                    let got = compiled.eval(&eval.row).unwrap();
+                    assert!(got == expect, "row {:?}: {:?} != {:?}", eval.row, got, expect);
+                }
             }
         }
+
+        // the remaining children are skipped once the result is known.
+        let expr = build_expr(vec![datum_expr(Datum::Null), datum_expr(Datum::I64(0)), col_expr(9)],
+                              ExprType::And);
+        assert_eq!(eval.eval(&expr).unwrap(), Datum::I64(0));
+        let expr = build_expr(vec![datum_expr(Datum::I64(0)), datum_expr(Datum::Null)],
+                              ExprType::Or);
+        assert_eq!(eval.eval(&expr).unwrap(), Datum::Null);
+        assert!(eval.eval(&build_expr(vec![datum_expr(Datum::I64(1))], ExprType::And)).is_err());
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        let mut eval = Evaluator::default();
+
+        // col1 > 1 + 2 is folded into col1 > 3.
+        let expr = bin_expr_r(col_expr(1),
+                              bin_expr(Datum::I64(1), Datum::I64(2), ExprType::Plus),
+                              ExprType::GT);
+        let folded = eval.fold_constants(&expr).unwrap();
+        assert_eq!(folded,
+                   bin_expr_r(col_expr(1), datum_expr(Datum::I64(3)), ExprType::GT));
+
+        // a constant comparison collapses to a single literal.
+        let expr = bin_expr(Datum::I64(1), Datum::I64(2), ExprType::LT);
+        assert_eq!(eval.fold_constants(&expr).unwrap(), datum_expr(Datum::I64(1)));
+
+        // (col1 + (1 - null)) > 1 or col2 in (1, 2) and concat('a', 'b') = 'ab'
+        let plus = bin_expr_r(col_expr(1),
+                              bin_expr(Datum::I64(1), Datum::Null, ExprType::Minus),
+                              ExprType::Plus);
+        let gt = bin_expr_r(plus, datum_expr(Datum::I64(1)), ExprType::GT);
+        let concat = build_expr(vec![datum_expr(str_datum("a")), datum_expr(str_datum("b"))],
+                                ExprType::Concat);
+        let eq = bin_expr_r(concat, datum_expr(str_datum("ab")), ExprType::EQ);
+        let in_list = in_expr_r(col_expr(2), vec![Datum::I64(1), Datum::I64(2)]);
+        let and = bin_expr_r(in_list.clone(), eq, ExprType::And);
+        let expr = bin_expr_r(gt, and, ExprType::Or);
+        let folded = eval.fold_constants(&expr).unwrap();
+
+        let folded_gt = &folded.get_children()[0];
+        let folded_plus = &folded_gt.get_children()[0];
+        assert_eq!(folded_plus.get_children()[1], datum_expr(Datum::Null));
+        let folded_and = &folded.get_children()[1];
+        assert_eq!(folded_and.get_children()[0], in_list);
+        assert_eq!(folded_and.get_children()[1], datum_expr(Datum::I64(1)));
+
+        let rows = vec![
+            (Datum::I64(1), Datum::I64(1)),
+            (Datum::I64(1), Datum::I64(3)),
+            (Datum::Null, Datum::I64(2)),
+            (Datum::I64(5), Datum::Null),
+        ];
+        for (c1, c2) in rows {
+            eval.row.insert(1, c1);
+            eval.row.insert(2, c2);
+            let expect = eval.eval(&expr).unwrap();
+            let got = eval.eval(&folded).unwrap();
+            assert!(got == expect, "row {:?}: {:?} != {:?}", eval.row, got, expect);
+        }
+
+        // errors in constant subtrees are reported.
+        let expr = bin_expr_r(col_expr(1),
+                              bin_expr(Datum::I64(i64::MAX), Datum::I64(1), ExprType::Plus),
+                              ExprType::GT);
+        assert!(eval.fold_constants(&expr).is_err());
+    }
+
+    use test::Bencher;
+
+    fn bench_column_eq(b: &mut Bencher, specialized: bool) {
+        let expr = bin_expr_r(col_expr(1), datum_expr(str_datum("abc")), ExprType::EQ);
+        let mut eval = Evaluator::default();
+        let mut compiled = eval.compile(&expr).unwrap();
+        if !specialized {
+            compiled = match compiled {
+                CompiledExpr::ColumnEq(i, d) => {
+                    CompiledExpr::Compare(ExprType::EQ,
+                                          box CompiledExpr::ColumnRef(i),
+                                          box CompiledExpr::Constant(d))
+                }
+                _ => unreachable!(),
+            };
+        }
+        let mut row = HashMap::new();
+        row.insert(1, str_datum("abd"));
+        b.iter(|| compiled.eval(&row).unwrap());
+    }
+
+    #[bench]
+    fn bench_column_eq_specialized(b: &mut Bencher) {
+        bench_column_eq(b, true);
+    }
+
+    #[bench]
+    fn bench_column_eq_general(b: &mut Bencher) {
+        bench_column_eq(b, false);
     }
 }
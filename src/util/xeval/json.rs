@@ -0,0 +1,135 @@
+// Copyright 2016 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use rustc_serialize::json::Json;
+
+use super::{Result, Error};
+
+/// A step of a json path.
+#[derive(Debug, PartialEq)]
+enum PathLeg {
+    Key(String),
+    Index(usize),
+}
+
+fn invalid_path(path: &str) -> Error {
+    Error::Eval(format!("invalid json path {:?}", path))
+}
+
+/// Parse a json path like `$.a.b[1]`, keys may be double quoted.
+fn parse_path(path: &str) -> Result<Vec<PathLeg>> {
+    let path = path.trim();
+    if !path.starts_with('$') {
+        return Err(invalid_path(path));
+    }
+    let mut legs = vec![];
+    let mut rest = &path[1..];
+    while !rest.is_empty() {
+        if rest.starts_with('.') {
+            rest = &rest[1..];
+            let (key, remain) = if rest.starts_with('"') {
+                match rest[1..].find('"') {
+                    Some(end) => (&rest[1..end + 1], &rest[end + 2..]),
+                    None => return Err(invalid_path(path)),
+                }
+            } else {
+                let end = rest.find(|c: char| c == '.' || c == '[').unwrap_or(rest.len());
+                if rest[..end].contains(char::is_whitespace) {
+                    return Err(invalid_path(path));
+                }
+                (&rest[..end], &rest[end..])
+            };
+            if key.is_empty() {
+                return Err(invalid_path(path));
+            }
+            legs.push(PathLeg::Key(key.to_owned()));
+            rest = remain;
+        } else if rest.starts_with('[') {
+            let end = try!(rest.find(']').ok_or_else(|| invalid_path(path)));
+            let idx = try!(rest[1..end].trim().parse().map_err(|_| invalid_path(path)));
+            legs.push(PathLeg::Index(idx));
+            rest = &rest[end + 1..];
+        } else {
+            return Err(invalid_path(path));
+        }
+    }
+    Ok(legs)
+}
+
+/// `extract` gets the value at `path` in `json`, None is returned if the
+/// path doesn't exist.
+pub fn extract<'a>(json: &'a Json, path: &str) -> Result<Option<&'a Json>> {
+    let mut cur = json;
+    for leg in try!(parse_path(path)) {
+        let next = match (leg, cur) {
+            (PathLeg::Key(ref key), &Json::Object(ref obj)) => obj.get(key),
+            (PathLeg::Index(idx), &Json::Array(ref arr)) => arr.get(idx),
+            _ => None,
+        };
+        match next {
+            Some(j) => cur = j,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(cur))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::{PathLeg, parse_path};
+
+    use rustc_serialize::json::Json;
+
+    #[test]
+    fn test_parse_path() {
+        let tests = vec![
+            ("$", vec![]),
+            ("$.a", vec![PathLeg::Key("a".to_owned())]),
+            (" $.a.bc ", vec![PathLeg::Key("a".to_owned()), PathLeg::Key("bc".to_owned())]),
+            ("$[1]", vec![PathLeg::Index(1)]),
+            ("$.a[0].b", vec![PathLeg::Key("a".to_owned()),
+                              PathLeg::Index(0),
+                              PathLeg::Key("b".to_owned())]),
+            ("$.\"a.b\"", vec![PathLeg::Key("a.b".to_owned())]),
+        ];
+        for (path, expect) in tests {
+            assert_eq!(parse_path(path).unwrap(), expect);
+        }
+
+        for path in &["", "a", "$.", "$a", "$..a", "$[", "$[a]", "$[-1]", "$.\"a", "$.a b"] {
+            assert!(parse_path(path).is_err(), "{} should fail", path);
+        }
+    }
+
+    #[test]
+    fn test_extract() {
+        let json = Json::from_str(r#"{"a": 1, "b": {"c": [1, "x", {"d": null}]}}"#).unwrap();
+        let tests = vec![
+            ("$.a", Some("1")),
+            ("$.b.c[1]", Some("\"x\"")),
+            ("$.b.c[2].d", Some("null")),
+            ("$.b.c", Some("[1,\"x\",{\"d\":null}]")),
+            ("$", Some(r#"{"a":1,"b":{"c":[1,"x",{"d":null}]}}"#)),
+            ("$.z", None),
+            ("$.a.b", None),
+            ("$.b.c[3]", None),
+            ("$[0]", None),
+        ];
+        for (path, expect) in tests {
+            let res = extract(&json, path).unwrap().map(|j| j.to_string());
+            assert!(res.as_ref().map(|s| s.as_str()) == expect, "{}: {:?}", path, res);
+        }
+    }
+}
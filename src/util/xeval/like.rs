@@ -0,0 +1,281 @@
+// Copyright 2016 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::ascii::AsciiExt;
+
+use util::codec::Datum;
+use super::Result;
+
+const ANY_CHARS: char = '%';
+const ANY_CHAR: char = '_';
+const ESCAPE_CHAR: char = '\\';
+
+#[derive(Debug, PartialEq)]
+enum Kind {
+    Exact(String),
+    Prefix(String),
+    Suffix(String),
+    Contains(String),
+    // patterns containing `_`, escapes or `%` in the middle.
+    General(Vec<char>),
+    // patterns of binary strings, which are matched byte by byte.
+    Binary(Vec<u8>),
+}
+
+/// `LikeMatcher` is the analyzed form of a LIKE pattern, so that a constant
+/// pattern only needs to be parsed once for all rows.
+#[derive(Debug)]
+pub struct LikeMatcher {
+    fold_case: bool,
+    kind: Kind,
+}
+
+impl LikeMatcher {
+    /// Create a matcher for `pattern`, ASCII letters are compared case
+    /// insensitively unless `case_sensitive` is set.
+    pub fn new(mut pattern: String, case_sensitive: bool) -> LikeMatcher {
+        // folding is unnecessary if there is no letter in pattern.
+        let fold_case = !case_sensitive &&
+                        pattern.chars().any(|x| x.is_ascii() && x.is_alphabetic());
+        if fold_case {
+            pattern = pattern.to_ascii_lowercase();
+        }
+        LikeMatcher {
+            fold_case: fold_case,
+            kind: analyze(pattern),
+        }
+    }
+
+    pub fn from_bytes(pattern: &[u8], case_sensitive: bool) -> Result<LikeMatcher> {
+        let pattern = try!(Datum::Bytes(pattern.to_vec()).into_string());
+        Ok(LikeMatcher::new(pattern, case_sensitive))
+    }
+
+    /// Create a matcher for a binary string `pattern`, which is matched
+    /// against the raw bytes of targets, so neither of them needs to be valid
+    /// UTF-8, and letters are compared case sensitively.
+    pub fn binary(pattern: Vec<u8>) -> LikeMatcher {
+        LikeMatcher {
+            fold_case: false,
+            kind: Kind::Binary(pattern),
+        }
+    }
+
+    pub fn matches(&self, mut target: String) -> bool {
+        if self.fold_case {
+            target = target.to_ascii_lowercase();
+        }
+        match self.kind {
+            Kind::Exact(ref s) => target == *s,
+            Kind::Prefix(ref s) => target.starts_with(s.as_str()),
+            Kind::Suffix(ref s) => target.ends_with(s.as_str()),
+            Kind::Contains(ref s) => target.contains(s.as_str()),
+            Kind::General(ref p) => {
+                let t: Vec<char> = target.chars().collect();
+                general_match(&t, p, ANY_CHARS, ANY_CHAR, ESCAPE_CHAR)
+            }
+            Kind::Binary(ref p) => binary_match(target.as_bytes(), p),
+        }
+    }
+
+    /// Match a datum, a binary matcher takes its raw bytes while others need
+    /// it to be a valid string.
+    pub fn matches_datum(&self, target: Datum) -> Result<bool> {
+        if let Kind::Binary(ref p) = self.kind {
+            let t = try!(target.into_bytes());
+            return Ok(binary_match(&t, p));
+        }
+        Ok(self.matches(try!(target.into_string())))
+    }
+}
+
+fn analyze(pattern: String) -> Kind {
+    let is_special = |c: char| c == ANY_CHARS || c == ANY_CHAR || c == ESCAPE_CHAR;
+    // strip the leading and trailing `%` on char boundaries, so that
+    // multibyte characters are never split.
+    let (any_prefix, any_suffix, inner) = {
+        let mut inner = pattern.as_str();
+        let any_prefix = inner.starts_with(ANY_CHARS);
+        if any_prefix {
+            inner = &inner[ANY_CHARS.len_utf8()..];
+        }
+        let any_suffix = inner.ends_with(ANY_CHARS);
+        if any_suffix {
+            inner = &inner[..inner.len() - ANY_CHARS.len_utf8()];
+        }
+        (any_prefix, any_suffix, inner.to_owned())
+    };
+    if inner.chars().any(is_special) {
+        return Kind::General(pattern.chars().collect());
+    }
+    match (any_prefix, any_suffix) {
+        (true, true) => Kind::Contains(inner),
+        (true, false) => Kind::Suffix(inner),
+        (false, true) => Kind::Prefix(inner),
+        (false, false) => Kind::Exact(inner),
+    }
+}
+
+fn binary_match(target: &[u8], pattern: &[u8]) -> bool {
+    general_match(target,
+                  pattern,
+                  ANY_CHARS as u8,
+                  ANY_CHAR as u8,
+                  ESCAPE_CHAR as u8)
+}
+
+/// `general_match` matches `target` against `pattern` in which `any_chars`
+/// matches any sequence, `any_char` matches exactly one element and `escape`
+/// escapes the next element.
+fn general_match<T>(target: &[T], pattern: &[T], any_chars: T, any_char: T, escape: T) -> bool
+    where T: Copy + PartialEq
+{
+    let (mut t, mut p) = (0, 0);
+    // the pattern position after the last `%` and the target position it
+    // is currently tried against.
+    let mut backtrack = None;
+    while t < target.len() {
+        if p < pattern.len() {
+            let c = pattern[p];
+            if c == any_chars {
+                backtrack = Some((p + 1, t));
+                p += 1;
+                continue;
+            } else if c == any_char {
+                t += 1;
+                p += 1;
+                continue;
+            } else if c == escape && p + 1 < pattern.len() {
+                if pattern[p + 1] == target[t] {
+                    t += 1;
+                    p += 2;
+                    continue;
+                }
+            } else if c == target[t] {
+                t += 1;
+                p += 1;
+                continue;
+            }
+        }
+        match backtrack {
+            Some((bp, bt)) => {
+                p = bp;
+                t = bt + 1;
+                backtrack = Some((bp, bt + 1));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == any_chars)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::{Kind, analyze};
+
+    use util::codec::Datum;
+
+    #[test]
+    fn test_analyze() {
+        let tests = vec![
+            ("", Kind::Exact("".to_owned())),
+            ("abc", Kind::Exact("abc".to_owned())),
+            ("%", Kind::Suffix("".to_owned())),
+            ("%%", Kind::Contains("".to_owned())),
+            ("ab%", Kind::Prefix("ab".to_owned())),
+            ("%ab", Kind::Suffix("ab".to_owned())),
+            ("%ab%", Kind::Contains("ab".to_owned())),
+            ("a_b", Kind::General(vec!['a', '_', 'b'])),
+            ("a%b", Kind::General(vec!['a', '%', 'b'])),
+            ("%café%", Kind::Contains("café".to_owned())),
+            ("é%", Kind::Prefix("é".to_owned())),
+            ("%é", Kind::Suffix("é".to_owned())),
+        ];
+        for (pattern, kind) in tests {
+            assert_eq!(analyze(pattern.to_owned()), kind);
+        }
+    }
+
+    #[test]
+    fn test_general_match() {
+        let tests = vec![
+            ("abc", "a_c", true),
+            ("abc", "a__c", false),
+            ("abc", "a%c", true),
+            ("ac", "a%c", true),
+            ("abcbd", "a%b%d", true),
+            ("abcbe", "a%b%d", false),
+            ("a%c", "a\\%c", true),
+            ("abc", "a\\%c", false),
+            ("a_", "%\\_", true),
+            ("abc", "%_%", true),
+            ("", "%_%", false),
+            ("café", "caf_", true),
+            ("café", "caf__", false),
+            ("un café noir", "%é%r", true),
+            ("一二三", "一_三", true),
+        ];
+        for (target, pattern, expect) in tests {
+            let matcher = LikeMatcher::new(pattern.to_owned(), false);
+            assert!(matcher.matches(target.to_owned()) == expect,
+                    "{} like {}",
+                    target,
+                    pattern);
+        }
+    }
+
+    #[test]
+    fn test_binary() {
+        let tests: Vec<(&[u8], &[u8], bool)> = vec![
+            (b"\xff\x00", b"%", true),
+            (b"\xff\x00", b"\xff_", true),
+            (b"\xff\x00", b"_", false),
+            (b"a\xffb", b"a%b", true),
+            (b"a%b", b"a\\%b", true),
+            (b"aBc", b"abc", false),
+            // multibyte characters are matched byte by byte.
+            ("é".as_bytes(), b"__", true),
+            ("é".as_bytes(), b"_", false),
+        ];
+        for (target, pattern, expect) in tests {
+            let matcher = LikeMatcher::binary(pattern.to_vec());
+            assert_eq!(matcher.matches_datum(Datum::Bytes(target.to_vec())).unwrap(), expect);
+        }
+
+        let matcher = LikeMatcher::new("%".to_owned(), true);
+        assert!(matcher.matches_datum(Datum::Bytes(b"\xff".to_vec())).is_err());
+        assert!(matcher.matches_datum(Datum::I64(1)).unwrap());
+    }
+
+    #[test]
+    fn test_case_sensitive() {
+        let tests = vec![
+            ("A", "a", false, true),
+            ("A", "a", true, false),
+            ("aBc", "%b%", false, true),
+            ("aBc", "%b%", true, false),
+            ("aBc", "a_c", true, true),
+            ("aBc", "A_C", true, false),
+        ];
+        for (target, pattern, case_sensitive, expect) in tests {
+            let matcher = LikeMatcher::new(pattern.to_owned(), case_sensitive);
+            assert!(matcher.matches(target.to_owned()) == expect,
+                    "{} like {} with case sensitive {}",
+                    target,
+                    pattern,
+                    case_sensitive);
+        }
+    }
+}
@@ -13,8 +13,12 @@
 
 
 pub mod evaluator;
+pub mod aggr;
+mod like;
+mod json;
 
 use util::codec;
+use tipb::expression::ExprType;
 
 quick_error! {
 	#[derive(Debug)]
@@ -31,10 +35,38 @@ quick_error! {
 			description("evaluation failed")
 			display("{}", s)
 		}
+		ColumnNotFound(id: i64) {
+			description("column not found")
+			display("column {} not found", id)
+		}
+		Context(path: String, err: Box<Error>) {
+			description("evaluation failed in a sub expression")
+			display("in {}: {}", path, err)
+		}
 	}
 }
 
 use std::result;
 pub type Result<T> = result::Result<T, Error>;
 
-pub use self::evaluator::Evaluator;
+impl Error {
+    /// Wrap the error with the type of the expression it's returned from,
+    /// the path from outer expressions to inner ones is kept in one context.
+    pub fn context(self, tp: ExprType) -> Error {
+        match self {
+            Error::Context(path, err) => Error::Context(format!("{:?} -> {}", tp, path), err),
+            err => Error::Context(format!("{:?}", tp), box err),
+        }
+    }
+
+    /// Get the original error without the context.
+    pub fn root_cause(&self) -> &Error {
+        match *self {
+            Error::Context(_, ref err) => &**err,
+            ref err => err,
+        }
+    }
+}
+
+pub use self::evaluator::{Evaluator, Collation, CompiledExpr, walk_expr, collect_column_ids};
+pub use self::aggr::{Aggregator, build_aggregator};
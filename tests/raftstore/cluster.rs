@@ -495,6 +495,48 @@ impl<T: Simulator> Cluster<T> {
         status_resp.take_region_detail()
     }
 
+    // Regions only carry store ids for now and a peer's raft id is its store id,
+    // so the store id is enough to identify the leader peer.
+    pub fn region_leader(&self, region_id: u64) -> Option<u64> {
+        let node_ids = self.sim.rl().get_node_ids();
+        for store_id in node_ids {
+            let leader = self.query_leader(store_id, region_id);
+            if leader.is_some() {
+                return leader;
+            }
+        }
+        None
+    }
+
+    // Every status response carries the current term of the target peer in
+    // its header, so any status command can be used to query the term.
+    pub fn region_term(&mut self, region_id: u64, store_id: u64) -> u64 {
+        let status_cmd = new_region_leader_cmd();
+        let req = new_status_request(region_id, status_cmd);
+        let resp = self.call_command(store_id, req, Duration::from_secs(3));
+        assert!(resp.is_ok(), format!("{:?}", resp));
+
+        let resp = resp.unwrap();
+        assert!(resp.has_status_response());
+        assert_eq!(resp.get_status_response().get_cmd_type(), StatusCmdType::RegionLeader);
+        resp.get_header().get_current_term()
+    }
+
+    pub fn store_stats(&mut self, store_id: u64) -> StoreStatsResponse {
+        // status request must carry a region id, any region is ok.
+        let status_cmd = new_store_stats_cmd();
+        let req = new_status_request(0, status_cmd);
+        let resp = self.call_command(store_id, req, Duration::from_secs(3));
+        assert!(resp.is_ok(), format!("{:?}", resp));
+
+        let mut resp = resp.unwrap();
+        assert!(resp.has_status_response());
+        let mut status_resp = resp.take_status_response();
+        assert_eq!(status_resp.get_cmd_type(), StatusCmdType::StoreStats);
+        assert!(status_resp.has_store_stats());
+        status_resp.take_store_stats()
+    }
+
     // NOTE: if you have set transport hooks before, call this function will overwrite them
     pub fn partition(&mut self, s1: Arc<HashSet<u64>>, s2: Arc<HashSet<u64>>) {
         for node_id in s1.iter() {
@@ -11,7 +11,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::thread;
+use std::time::Duration;
+
 use super::server::*;
+use super::util::*;
 
 #[test]
 fn test_region_detail() {
@@ -37,4 +41,92 @@ fn test_region_detail() {
 
     assert!(region_detail.has_leader_store_id());
     assert_eq!(region_detail.get_leader_store_id(), leader);
+
+    // every peer reports its own view of the region, and once a write has
+    // been replicated to all of them they all know the leader.
+    cluster.must_put(b"k1", b"v1");
+    for &store_id in region.get_store_ids() {
+        must_get_equal(&cluster.get_engine(store_id), b"k1", b"v1");
+        let detail = cluster.region_detail(1, store_id);
+        assert_eq!(detail.get_region(), region);
+        assert_eq!(detail.get_leader_store_id(), leader);
+    }
+}
+
+#[test]
+fn test_region_term() {
+    let count = 3;
+    let mut cluster = new_server_cluster(0, count);
+    cluster.bootstrap_region().expect("");
+    cluster.start();
+
+    let leader = cluster.leader_of_region(1).unwrap();
+    let term = cluster.region_term(1, leader);
+    assert!(term > 0);
+
+    // stop the leader to force a new election.
+    cluster.stop_node(leader);
+    cluster.reset_leader_of_region(1);
+    let new_leader = cluster.leader_of_region(1).unwrap();
+    assert!(new_leader != leader);
+    assert!(cluster.region_term(1, new_leader) > term);
+}
+
+#[test]
+fn test_region_leader() {
+    let count = 3;
+    let mut cluster = new_server_cluster(0, count);
+    cluster.bootstrap_region().expect("");
+    cluster.start();
+
+    let leader = cluster.leader_of_region(1).unwrap();
+    assert_eq!(cluster.region_leader(1), Some(leader));
+    let region_detail = cluster.region_detail(1, leader);
+    assert_eq!(region_detail.get_leader_store_id(), leader);
+
+    // stop a majority including the leader, the live store can't elect a new
+    // leader and forgets the old one once its election times out.
+    let survivor = (1..count as u64 + 1).find(|&id| id != leader).unwrap();
+    for id in 1..count as u64 + 1 {
+        if id != survivor {
+            cluster.stop_node(id);
+        }
+    }
+    let mut forgotten = false;
+    for _ in 0..500 {
+        if !cluster.region_detail(1, survivor).has_leader_store_id() {
+            forgotten = true;
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(forgotten, "store {} still knows a leader after 5 secs", survivor);
+    assert_eq!(cluster.region_leader(1), None);
+}
+
+#[test]
+fn test_store_stats() {
+    let count = 5;
+    let mut cluster = new_server_cluster(0, count);
+    cluster.bootstrap_region().expect("");
+    cluster.start();
+
+    cluster.must_put(b"k1", b"v1");
+    let region = cluster.get_region(b"k1");
+    cluster.split_region(region.get_id(), Some(b"k2".to_vec()));
+    cluster.must_put(b"k3", b"v3");
+
+    let (mut region_count, mut leader_count) = (0, 0);
+    for store_id in 1..count as u64 + 1 {
+        let stats = cluster.store_stats(store_id);
+        assert_eq!(stats.get_store_id(), store_id);
+        assert!(stats.get_leader_count() <= stats.get_region_count());
+        region_count += stats.get_region_count();
+        leader_count += stats.get_leader_count();
+    }
+    // a majority of stores must have applied the split, and each region
+    // has exactly one leader.
+    assert!(region_count > count as u64 + count as u64 / 2);
+    assert!(region_count <= 2 * count as u64);
+    assert_eq!(leader_count, 2);
 }
@@ -145,6 +145,12 @@ pub fn new_region_detail_cmd() -> StatusRequest {
     cmd
 }
 
+pub fn new_store_stats_cmd() -> StatusRequest {
+    let mut cmd = StatusRequest::new();
+    cmd.set_cmd_type(StatusCmdType::StoreStats);
+    cmd
+}
+
 pub fn new_region_leader_cmd() -> StatusRequest {
     let mut cmd = StatusRequest::new();
     cmd.set_cmd_type(StatusCmdType::RegionLeader);